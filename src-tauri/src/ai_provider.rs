@@ -0,0 +1,382 @@
+use crate::ai_service::{analyze_with_provider, AnalysisRequest, AnalysisResult};
+use crate::error::{AppError, Result};
+use async_trait::async_trait;
+use reqwest::{Client, RequestBuilder};
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+pub const OLLAMA: &str = "ollama";
+pub const OPENAI_COMPATIBLE: &str = "openai_compatible";
+
+/// Connection details of one backend, stored in `AppSettings::ai_backends`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderConfig {
+    pub base_url: String,
+    pub timeout_secs: u64,
+    #[serde(default)]
+    pub auth_header: Option<String>, // Sent as `Authorization`, e.g. "Bearer sk-..."
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AiBackendSettings {
+    pub ollama: ProviderConfig,
+    pub openai_compatible: ProviderConfig, // llama.cpp server, LM Studio, vLLM, ...
+}
+
+impl Default for AiBackendSettings {
+    fn default() -> Self {
+        Self {
+            ollama: ProviderConfig {
+                base_url: "http://127.0.0.1:11434".to_string(),
+                timeout_secs: 30,
+                auth_header: None,
+            },
+            openai_compatible: ProviderConfig {
+                base_url: "http://127.0.0.1:8080/v1".to_string(),
+                timeout_secs: 60,
+                auth_header: None,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProviderHealth {
+    pub provider: String,
+    pub available: bool,
+    pub detail: Option<String>, // Why it is unavailable
+}
+
+/// A model server the app can send analysis prompts to.
+#[async_trait]
+pub trait AiProvider: Send + Sync {
+    fn id(&self) -> &'static str;
+
+    fn config(&self) -> &ProviderConfig;
+
+    async fn health(&self) -> ProviderHealth;
+
+    async fn list_models(&self) -> Result<Vec<String>>;
+
+    /// Sends a prompt and returns the model's text reply. With a JSON
+    /// schema, the server is asked to answer with a matching document.
+    async fn generate(
+        &self,
+        model: &str,
+        prompt: &str,
+        schema: Option<&serde_json::Value>,
+    ) -> Result<String>;
+
+    /// An embedding vector for `input`, from an embedding model such as
+    /// `nomic-embed-text`.
+    async fn embed(&self, _model: &str, _input: &str) -> Result<Vec<f32>> {
+        Err(AppError::AiService(format!(
+            "{} does not support embeddings",
+            self.id()
+        )))
+    }
+
+    async fn analyze(&self, model: &str, request: &AnalysisRequest) -> Result<AnalysisResult> {
+        analyze_with_provider(self, model, request).await
+    }
+}
+
+/// The backend named by `AppSettings::ai_provider`.
+pub fn provider_from_settings(
+    provider: &str,
+    backends: &AiBackendSettings,
+) -> Result<Box<dyn AiProvider>> {
+    match provider.to_lowercase().replace('-', "_").as_str() {
+        OLLAMA => Ok(Box::new(OllamaProvider::new(backends.ollama.clone())?)),
+        OPENAI_COMPATIBLE | "openai" => Ok(Box::new(OpenAiCompatibleProvider::new(
+            backends.openai_compatible.clone(),
+        )?)),
+        other => Err(AppError::Configuration(format!(
+            "Unknown AI provider '{}'",
+            other
+        ))),
+    }
+}
+
+pub struct OllamaProvider {
+    client: Client,
+    config: ProviderConfig,
+}
+
+impl OllamaProvider {
+    pub fn new(config: ProviderConfig) -> Result<Self> {
+        Ok(Self {
+            client: http_client(&config)?,
+            config,
+        })
+    }
+
+    fn get(&self, path: &str) -> RequestBuilder {
+        authorize(self.client.get(url(&self.config, path)), &self.config)
+    }
+
+    fn post(&self, path: &str) -> RequestBuilder {
+        authorize(self.client.post(url(&self.config, path)), &self.config)
+    }
+}
+
+#[async_trait]
+impl AiProvider for OllamaProvider {
+    fn id(&self) -> &'static str {
+        OLLAMA
+    }
+
+    fn config(&self) -> &ProviderConfig {
+        &self.config
+    }
+
+    async fn health(&self) -> ProviderHealth {
+        check_health(self, self.get("/api/tags")).await
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>> {
+        let data = send(self.get("/api/tags")).await?;
+        Ok(names(&data["models"], "name"))
+    }
+
+    async fn generate(
+        &self,
+        model: &str,
+        prompt: &str,
+        schema: Option<&serde_json::Value>,
+    ) -> Result<String> {
+        let mut payload = serde_json::json!({
+            "model": model,
+            "messages": [{"role": "user", "content": prompt}],
+            "stream": false,
+            "options": {
+                "temperature": 0.3,
+                "top_p": 0.9,
+                "num_predict": 500
+            }
+        });
+        if let Some(schema) = schema {
+            payload["format"] = schema.clone();
+        }
+        let data = send(self.post("/api/chat").json(&payload)).await?;
+
+        data["message"]["content"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| AppError::Ollama {
+                message: "Reply has no message content".to_string(),
+                raw_reply: Some(data.to_string()),
+            })
+    }
+
+    async fn embed(&self, model: &str, input: &str) -> Result<Vec<f32>> {
+        let payload = serde_json::json!({"model": model, "prompt": input});
+        let data = send(self.post("/api/embeddings").json(&payload)).await?;
+        vector(&data["embedding"])
+    }
+}
+
+/// Servers that speak the OpenAI `/v1` API. `base_url` includes the `/v1`.
+pub struct OpenAiCompatibleProvider {
+    client: Client,
+    config: ProviderConfig,
+}
+
+impl OpenAiCompatibleProvider {
+    pub fn new(config: ProviderConfig) -> Result<Self> {
+        Ok(Self {
+            client: http_client(&config)?,
+            config,
+        })
+    }
+
+    fn get(&self, path: &str) -> RequestBuilder {
+        authorize(self.client.get(url(&self.config, path)), &self.config)
+    }
+
+    fn post(&self, path: &str) -> RequestBuilder {
+        authorize(self.client.post(url(&self.config, path)), &self.config)
+    }
+}
+
+#[async_trait]
+impl AiProvider for OpenAiCompatibleProvider {
+    fn id(&self) -> &'static str {
+        OPENAI_COMPATIBLE
+    }
+
+    fn config(&self) -> &ProviderConfig {
+        &self.config
+    }
+
+    async fn health(&self) -> ProviderHealth {
+        check_health(self, self.get("/models")).await
+    }
+
+    async fn list_models(&self) -> Result<Vec<String>> {
+        let data = send(self.get("/models")).await?;
+        Ok(names(&data["data"], "id"))
+    }
+
+    async fn generate(
+        &self,
+        model: &str,
+        prompt: &str,
+        schema: Option<&serde_json::Value>,
+    ) -> Result<String> {
+        let mut payload = serde_json::json!({
+            "model": model,
+            "messages": [{"role": "user", "content": prompt}],
+            "temperature": 0.3,
+            "top_p": 0.9,
+            "max_tokens": 500,
+            "stream": false
+        });
+        if let Some(schema) = schema {
+            payload["response_format"] = serde_json::json!({
+                "type": "json_schema",
+                "json_schema": {"name": "reply", "strict": true, "schema": schema}
+            });
+        }
+        let data = send(self.post("/chat/completions").json(&payload)).await?;
+
+        data["choices"][0]["message"]["content"]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| AppError::AiService("Invalid response format".to_string()))
+    }
+
+    async fn embed(&self, model: &str, input: &str) -> Result<Vec<f32>> {
+        let payload = serde_json::json!({"model": model, "input": input});
+        let data = send(self.post("/embeddings").json(&payload)).await?;
+        vector(&data["data"][0]["embedding"])
+    }
+}
+
+fn http_client(config: &ProviderConfig) -> Result<Client> {
+    Ok(Client::builder()
+        .timeout(Duration::from_secs(config.timeout_secs))
+        .build()?)
+}
+
+fn url(config: &ProviderConfig, path: &str) -> String {
+    format!("{}{}", config.base_url.trim_end_matches('/'), path)
+}
+
+fn authorize(request: RequestBuilder, config: &ProviderConfig) -> RequestBuilder {
+    match &config.auth_header {
+        Some(value) => request.header(reqwest::header::AUTHORIZATION, value),
+        None => request,
+    }
+}
+
+async fn send(request: RequestBuilder) -> Result<serde_json::Value> {
+    // Keeps the status in the error so callers can tell transient failures apart
+    let response = request.send().await?.error_for_status()?;
+    Ok(response.json().await?)
+}
+
+async fn check_health(provider: &dyn AiProvider, request: RequestBuilder) -> ProviderHealth {
+    let result = send(request).await;
+    ProviderHealth {
+        provider: provider.id().to_string(),
+        available: result.is_ok(),
+        detail: result.err().map(|e| e.to_string()),
+    }
+}
+
+fn vector(value: &serde_json::Value) -> Result<Vec<f32>> {
+    let vector: Vec<f32> = value
+        .as_array()
+        .map(|arr| {
+            arr.iter()
+                .filter_map(|v| v.as_f64())
+                .map(|v| v as f32)
+                .collect()
+        })
+        .unwrap_or_default();
+    if vector.is_empty() {
+        return Err(AppError::AiService(
+            "Reply has no embedding; is this an embedding model?".to_string(),
+        ));
+    }
+    Ok(vector)
+}
+
+fn names(list: &serde_json::Value, key: &str) -> Vec<String> {
+    list.as_array()
+        .map(|arr| {
+            arr.iter()
+                .filter_map(|m| m[key].as_str())
+                .map(|s| s.to_string())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Answers one request with `body` and returns the raw request text.
+    async fn serve_once(body: &'static str) -> (String, tokio::task::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        let handle = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buffer = vec![0; 16384];
+            let read = socket.read(&mut buffer).await.unwrap();
+            let response = format!(
+                "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                body.len(),
+                body
+            );
+            socket.write_all(response.as_bytes()).await.unwrap();
+            String::from_utf8_lossy(&buffer[..read]).to_string()
+        });
+        (address, handle)
+    }
+
+    #[test]
+    fn test_provider_from_settings() {
+        let settings: AiBackendSettings = serde_json::from_value(serde_json::json!({
+            "openai_compatible": {"base_url": "http://localhost:1234/v1", "timeout_secs": 5}
+        }))
+        .unwrap();
+        assert_eq!(settings.ollama.base_url, "http://127.0.0.1:11434");
+
+        let provider = provider_from_settings("OpenAI-Compatible", &settings).unwrap();
+        assert_eq!(provider.id(), OPENAI_COMPATIBLE);
+        assert_eq!(provider.config().timeout_secs, 5);
+        assert_eq!(
+            provider_from_settings("ollama", &settings).unwrap().id(),
+            OLLAMA
+        );
+        assert!(provider_from_settings("gpt4all", &settings).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_openai_compatible_generate() {
+        let (address, request) =
+            serve_once(r#"{"choices":[{"message":{"role":"assistant","content":"hello"}}]}"#).await;
+        let provider = OpenAiCompatibleProvider::new(ProviderConfig {
+            base_url: format!("{}/v1/", address),
+            timeout_secs: 5,
+            auth_header: Some("Bearer local-key".to_string()),
+        })
+        .unwrap();
+
+        let reply = provider
+            .generate("qwen2.5", "Say hello", None)
+            .await
+            .unwrap();
+        assert_eq!(reply, "hello");
+
+        let request = request.await.unwrap().to_lowercase();
+        assert!(request.starts_with("post /v1/chat/completions "));
+        assert!(request.contains("authorization: bearer local-key"));
+    }
+}
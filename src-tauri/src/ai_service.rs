@@ -1,8 +1,32 @@
+use crate::ai_provider::{provider_from_settings, AiProvider, OLLAMA};
+use crate::categorization::{rank_candidates, SignalSource};
+use crate::content_extraction::{snippet_for_request, ExtractionLimits};
+use crate::corrections::{
+    attach_examples, context_from_corrections, Correction, FewShotExample,
+    MAX_CORRECTIONS_CONSIDERED,
+};
+use crate::database::{AnalysisCacheKey, AppSettings, DatabaseManager};
 use crate::error::{AppError, Result};
-use reqwest::Client;
+use crate::file_operations::FileMetadata;
+use crate::jd_id::{JDId, JDNumber};
+use crate::johnny_decimal::{CategoryAssignment, JDStructure, JohnnyDecimalEngine};
+use crate::model_manager::same_model;
+use crate::ollama_process::{OllamaSupervisor, ProcessState, SupervisorOptions};
+use crate::organization_scheme::{scheme_from_id, OrganizationScheme, JOHNNY_DECIMAL};
+use crate::taxonomy::classify_extension;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use std::process::{Command, Stdio};
-use tokio::time::{timeout, Duration};
+use sha2::{Digest, Sha256};
+
+/// Bump whenever the prompts or reply schemas change, so cached results
+/// produced by the old wording are no longer used.
+pub const PROMPT_VERSION: u32 = 5;
+/// Follow-up prompts sent when a reply names an ID the structure lacks.
+const MAX_STRUCTURE_RETRIES: usize = 2;
+/// Items listed per category in structure-aware prompts.
+const PROMPT_ITEMS_PER_CATEGORY: usize = 15;
+/// Characters of a rejected reply written to the debug log.
+const RAW_REPLY_LOG_CHARS: usize = 500;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AnalysisRequest {
@@ -13,6 +37,80 @@ pub struct AnalysisRequest {
     pub mime_type: Option<String>,
     pub existing_structure: Option<serde_json::Value>,
     pub organization_scheme: String,
+    #[serde(default)]
+    pub content_snippet: Option<String>, // Normalized text from the start of the file
+    #[serde(default)]
+    pub examples: Vec<FewShotExample>, // The user's past corrections of similar files
+}
+
+impl AnalysisRequest {
+    /// A request for a scanned file with its content snippet, before the
+    /// user's examples are added.
+    pub fn from_metadata(
+        metadata: &FileMetadata,
+        organization_scheme: &str,
+        structure: Option<&JDStructure>,
+    ) -> Result<Self> {
+        Ok(Self {
+            file_path: metadata.path.clone(),
+            file_name: metadata.name.clone(),
+            file_extension: metadata.file_type.clone(),
+            file_size: metadata.size,
+            mime_type: metadata.mime_type.clone(),
+            existing_structure: structure.map(serde_json::to_value).transpose()?,
+            organization_scheme: organization_scheme.to_string(),
+            content_snippet: None,
+            examples: Vec::new(),
+        }
+        .with_content(&ExtractionLimits::default()))
+    }
+
+    /// Reads the content snippet from the file unless one was supplied.
+    pub fn with_content(mut self, limits: &ExtractionLimits) -> Self {
+        if self.content_snippet.is_none() {
+            self.content_snippet = snippet_for_request(&self.file_path, limits);
+        }
+        self
+    }
+}
+
+/// Reply to the free-form prompt, see `analysis_schema`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AnalysisReply {
+    pub category: String,
+    pub confidence: f64,
+    pub reasoning: String,
+    pub alternatives: Vec<String>,
+    pub tags: Vec<String>,
+}
+
+/// Reply to the structure-aware prompt, see `structure_reply_schema`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct StructureReply {
+    pub id: String,
+    pub new_item: Option<String>,
+    pub confidence: f64,
+    pub reasoning: String,
+    pub alternatives: Vec<String>,
+    pub tags: Vec<String>,
+}
+
+pub trait ScoredReply {
+    fn confidence(&self) -> f64;
+}
+
+impl ScoredReply for AnalysisReply {
+    fn confidence(&self) -> f64 {
+        self.confidence
+    }
+}
+
+impl ScoredReply for StructureReply {
+    fn confidence(&self) -> f64 {
+        self.confidence
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -22,68 +120,98 @@ pub struct AnalysisResult {
     pub reasoning: String,
     pub alternative_categories: Vec<String>,
     pub tags: Vec<String>,
+    #[serde(default)]
+    pub assignment: Option<CategoryAssignment>, // Resolved JD IDs, Johnny Decimal only
+    #[serde(default)]
+    pub proposed_item: Option<String>, // Name of a new item the assignment creates
 }
 
 #[allow(dead_code)]
 pub struct OllamaService {
-    client: Client,
-    base_url: String,
+    provider: Box<dyn AiProvider>,
     current_model: Option<String>,
+    default_model: String, // Loaded by `start`, from `AppSettings::ai_model`
     is_running: bool,
+    supervisor: Option<OllamaSupervisor>, // Set once `start` has run against Ollama
 }
 
 #[allow(dead_code)]
 impl OllamaService {
-    pub async fn new() -> Result<Self> {
-        let client = Client::new();
-        let base_url = "http://127.0.0.1:11434".to_string();
-
+    /// Uses the backend and model chosen in the settings.
+    pub async fn new(settings: &AppSettings) -> Result<Self> {
         Ok(Self {
-            client,
-            base_url,
+            provider: provider_from_settings(&settings.ai_provider, &settings.ai_backends)?,
             current_model: None,
+            default_model: settings.ai_model.clone(),
             is_running: false,
+            supervisor: None,
         })
     }
 
+    /// Wraps an already running backend with `model` loaded.
+    pub fn from_provider(provider: Box<dyn AiProvider>, model: &str) -> Self {
+        Self {
+            provider,
+            current_model: Some(model.to_string()),
+            default_model: model.to_string(),
+            is_running: true,
+            supervisor: None,
+        }
+    }
+
+    pub fn provider(&self) -> &dyn AiProvider {
+        self.provider.as_ref()
+    }
+
     pub async fn start(&mut self) -> Result<String> {
-        // Check if Ollama is already running
-        if self.is_service_available().await {
-            self.is_running = true;
-            return Ok("Ollama service is already running".to_string());
+        // Only Ollama can be started from here; other servers are run by the user
+        if self.provider.id() != OLLAMA {
+            if self.is_service_available().await {
+                self.is_running = true;
+                if self.current_model.is_none() {
+                    self.current_model = Some(self.default_model.clone());
+                }
+                return Ok(format!("{} is running", self.provider.id()));
+            }
+            return Err(AppError::AiService(format!(
+                "{} is not reachable at {}",
+                self.provider.id(),
+                self.provider.config().base_url
+            )));
         }
 
-        // Try to start Ollama service
-        match self.start_ollama_process().await {
-            Ok(_) => {
-                // Wait a bit for the service to start
-                tokio::time::sleep(Duration::from_secs(3)).await;
-
-                if self.is_service_available().await {
-                    self.is_running = true;
-
-                    // Try to load default model
-                    if let Err(e) = self.ensure_model_available("llama3.2:1b").await {
-                        log::warn!("Failed to ensure default model: {}", e);
-                    }
-
-                    Ok("Ollama service started successfully".to_string())
-                } else {
-                    Err(AppError::AiService(
-                        "Failed to start Ollama service".to_string(),
-                    ))
+        let config = self.provider.config().clone();
+        let supervisor = self
+            .supervisor
+            .get_or_insert_with(|| OllamaSupervisor::new(config, SupervisorOptions::default()));
+        let state = supervisor.start().await?;
+        self.is_running = true;
+
+        let mut message = match state {
+            ProcessState::External => "Ollama service is already running".to_string(),
+            _ => "Ollama service started successfully".to_string(),
+        };
+        if self.current_model.is_none() {
+            let model = self.default_model.clone();
+            match self.load_installed_model(&model).await {
+                Ok(true) => {}
+                Ok(false) => {
+                    message = format!(
+                        "{}; model {} is not installed, pull it first",
+                        message, model
+                    )
                 }
+                Err(e) => log::warn!("Could not list models: {}", e),
             }
-            Err(e) => Err(AppError::AiService(format!(
-                "Failed to start Ollama: {}",
-                e
-            ))),
         }
+        Ok(message)
     }
 
+    /// Stops Ollama if this app started it; an external server keeps running.
     pub async fn stop(&mut self) -> Result<()> {
-        // Note: In a real implementation, we might want to gracefully shutdown
-        // For now, we just mark it as not running
+        if let Some(supervisor) = self.supervisor.as_mut() {
+            supervisor.stop().await?;
+        }
         self.is_running = false;
         self.current_model = None;
         Ok(())
@@ -98,125 +226,345 @@ impl OllamaService {
             }));
         }
 
+        let process = self.supervisor.as_ref().map(|s| s.status());
         match self.get_available_models().await {
             Ok(models) => Ok(serde_json::json!({
                 "status": "running",
                 "model": self.current_model,
+                "missing_model": self.missing_model(),
+                "provider": self.provider.id(),
                 "available": true,
-                "models": models
+                "models": models,
+                "process": process
             })),
-            Err(_) => Ok(serde_json::json!({
+            Err(e) => Ok(serde_json::json!({
                 "status": "error",
                 "model": null,
-                "available": false
+                "available": false,
+                "error": e.to_string(),
+                "process": process
             })),
         }
     }
 
-    pub async fn analyze_file(&self, request: AnalysisRequest) -> Result<AnalysisResult> {
+    /// Analyzes a file with the user's `corrections` as prompt examples and
+    /// as rules that take precedence over the model.
+    pub async fn analyze_file(
+        &self,
+        request: AnalysisRequest,
+        corrections: &[Correction],
+    ) -> Result<AnalysisResult> {
         if !self.is_running {
             return Err(AppError::AiService(
                 "Ollama service is not running".to_string(),
             ));
         }
 
+        let request = prepare_request(request, corrections);
+
         // First try AI analysis, then fallback to rule-based
-        match self.ai_analyze_file(&request).await {
-            Ok(result) => Ok(result),
+        let result = match self.ai_analyze_file(&request).await {
+            Ok(result) => result,
             Err(e) => {
                 log::warn!("AI analysis failed: {}, falling back to rule-based", e);
-                self.rule_based_analysis(&request)
+                self.rule_based_analysis(&request)?
             }
-        }
+        };
+        apply_corrections(&request, result, corrections)
     }
 
-    async fn ai_analyze_file(&self, request: &AnalysisRequest) -> Result<AnalysisResult> {
+    pub async fn ai_analyze_file(&self, request: &AnalysisRequest) -> Result<AnalysisResult> {
         let model = self
             .current_model
             .as_ref()
             .ok_or_else(|| AppError::AiService("No model loaded".to_string()))?;
 
-        let prompt = self.build_analysis_prompt(request);
-
-        let payload = serde_json::json!({
-            "model": model,
-            "prompt": prompt,
-            "stream": false,
-            "options": {
-                "temperature": 0.3,
-                "top_p": 0.9,
-                "max_tokens": 500
-            }
-        });
-
-        let response = timeout(
-            Duration::from_secs(30),
-            self.client
-                .post(format!("{}/api/generate", self.base_url))
-                .json(&payload)
-                .send(),
-        )
-        .await
-        .map_err(|_| AppError::AiService("Request timeout".to_string()))?
-        .map_err(AppError::Http)?;
-
-        if !response.status().is_success() {
-            return Err(AppError::AiService(format!(
-                "HTTP error: {}",
-                response.status()
-            )));
-        }
+        self.provider.analyze(model, request).await
+    }
 
-        let response_data: serde_json::Value = response.json().await.map_err(AppError::Http)?;
+    /// `analyze_file` backed by the persistent cache, with the corrections
+    /// made in the request's structure. `file_hash` is the content hash and
+    /// `structure_revision` the revision of the structure in the request.
+    /// The model's reply is cached; rule-based fallbacks and what the
+    /// corrections change are not.
+    pub async fn analyze_file_cached(
+        &self,
+        db: &DatabaseManager,
+        request: AnalysisRequest,
+        file_hash: &str,
+        structure_revision: Option<u32>,
+    ) -> Result<AnalysisResult> {
+        let corrections = db
+            .recent_corrections(request_structure_id(&request), MAX_CORRECTIONS_CONSIDERED)
+            .await?;
+        let request = &prepare_request(request, &corrections);
+        let key = self.cache_key(request, file_hash, structure_revision)?;
+        let result = match db.get_cached_analysis(&key).await? {
+            Some(result) => result,
+            None => match self.ai_analyze_file(request).await {
+                Ok(result) => {
+                    db.cache_analysis(&key, &result).await?;
+                    result
+                }
+                Err(e) => {
+                    log::warn!("AI analysis failed: {}, falling back to rule-based", e);
+                    self.rule_based_analysis(request)?
+                }
+            },
+        };
+        apply_corrections(request, result, &corrections)
+    }
 
-        let ai_response = response_data["response"]
-            .as_str()
-            .ok_or_else(|| AppError::AiService("Invalid response format".to_string()))?;
+    pub fn cache_key(
+        &self,
+        request: &AnalysisRequest,
+        file_hash: &str,
+        structure_revision: Option<u32>,
+    ) -> Result<AnalysisCacheKey> {
+        let model = self
+            .current_model
+            .as_ref()
+            .ok_or_else(|| AppError::AiService("No model loaded".to_string()))?;
 
-        self.parse_ai_response(ai_response)
+        Ok(AnalysisCacheKey {
+            file_hash: file_hash.to_string(),
+            model: format!("{}/{}", self.provider.id(), model),
+            prompt_version: PROMPT_VERSION,
+            organization_scheme: request.organization_scheme.clone(),
+            prompt_hash: format!(
+                "{:x}",
+                Sha256::digest(build_analysis_prompt(request)?.as_bytes())
+            ),
+            structure_id: request_structure_id(request).map(str::to_string),
+            structure_revision,
+        })
     }
 
-    fn rule_based_analysis(&self, request: &AnalysisRequest) -> Result<AnalysisResult> {
-        let category = match request.file_extension.to_lowercase().as_str() {
-            "pdf" | "doc" | "docx" | "txt" | "rtf" => "20-29 Documents/21 Text Documents",
-            "xls" | "xlsx" | "csv" => "20-29 Documents/22 Spreadsheets",
-            "ppt" | "pptx" => "20-29 Documents/23 Presentations",
-            "jpg" | "jpeg" | "png" | "gif" | "bmp" | "svg" => "30-39 Media/31 Images",
-            "mp4" | "avi" | "mkv" | "mov" | "wmv" => "30-39 Media/32 Videos",
-            "mp3" | "wav" | "flac" | "aac" => "30-39 Media/33 Audio",
-            "zip" | "rar" | "7z" | "tar" | "gz" => "40-49 Archives/41 Compressed Files",
-            "exe" | "msi" | "dmg" | "pkg" => "40-49 Archives/42 Installers",
-            _ => "90-99 Miscellaneous/91 Other Files",
+    pub fn rule_based_analysis(&self, request: &AnalysisRequest) -> Result<AnalysisResult> {
+        let scheme = scheme_for_request(request)?;
+        if scheme.id() != JOHNNY_DECIMAL {
+            let folder = scheme.target_folder(&request_file_info(request));
+            return Ok(AnalysisResult {
+                suggested_category: folder.to_string_lossy().replace('\\', "/"),
+                confidence: 0.75,
+                reasoning: format!(
+                    "File placed by the {} rules using rule-based fallback",
+                    scheme.display_name()
+                ),
+                alternative_categories: vec![],
+                tags: rule_based_tags(request),
+                assignment: None,
+                proposed_item: None,
+            });
+        }
+
+        // Same taxonomy as the engine, resolved against the active structure
+        let engine = JohnnyDecimalEngine::new()?;
+        let file = request_file_info(request);
+        let structure: JDStructure = match request
+            .existing_structure
+            .clone()
+            .and_then(|value| serde_json::from_value(value).ok())
+        {
+            Some(structure) => structure,
+            None => engine.build_structure(std::slice::from_ref(&file), ""),
         };
+        let assignment = engine.assign_file(&file, &structure);
+        let category = engine.assignment_folder(&assignment, &structure);
 
         Ok(AnalysisResult {
-            suggested_category: category.to_string(),
+            suggested_category: category.to_string_lossy().replace('\\', "/"),
             confidence: 0.75, // Rule-based has moderate confidence
             reasoning: format!(
                 "File categorized based on extension '{}' using rule-based fallback",
                 request.file_extension
             ),
-            alternative_categories: vec!["90-99 Miscellaneous/91 Other Files".to_string()],
-            tags: vec![request.file_extension.clone(), "rule-based".to_string()],
+            alternative_categories: vec![],
+            tags: rule_based_tags(request),
+            assignment: Some(assignment),
+            proposed_item: None,
         })
     }
 
-    fn build_analysis_prompt(&self, request: &AnalysisRequest) -> String {
-        format!(
-            r#"You are a file organization assistant using the Johnny Decimal system. 
+    pub async fn get_available_models(&self) -> Result<Vec<String>> {
+        if !self.is_running {
+            return Ok(vec![]);
+        }
+
+        self.provider.list_models().await
+    }
+
+    async fn is_service_available(&self) -> bool {
+        self.provider.health().await.available
+    }
+
+    /// Loads `model_name` if the server has it. A missing model is left for
+    /// the user to pull, so `false` means nothing was loaded.
+    pub async fn load_installed_model(&mut self, model_name: &str) -> Result<bool> {
+        let models = self.get_available_models().await?;
+        if !models.iter().any(|m| same_model(m, model_name)) {
+            return Ok(false);
+        }
+
+        self.current_model = Some(model_name.to_string());
+        Ok(true)
+    }
+
+    /// The configured model, while the service runs without it loaded.
+    pub fn missing_model(&self) -> Option<&str> {
+        match (self.is_running, &self.current_model) {
+            (true, None) => Some(&self.default_model),
+            _ => None,
+        }
+    }
+
+    /// Makes an installed model the active one and saves the choice in
+    /// `AppSettings::ai_model`.
+    pub async fn switch_model(&mut self, model_name: &str, db: &DatabaseManager) -> Result<()> {
+        let models = self.provider.list_models().await?;
+        if !models.iter().any(|m| same_model(m, model_name)) {
+            return Err(AppError::AiService(format!(
+                "Model {} is not installed",
+                model_name
+            )));
+        }
+
+        let mut settings = db.load_settings().await?;
+        settings.ai_model = model_name.to_string();
+        db.save_settings(&settings).await?;
+
+        self.current_model = Some(model_name.to_string());
+        self.default_model = model_name.to_string();
+        Ok(())
+    }
+
+    pub fn current_model(&self) -> Option<&str> {
+        self.current_model.as_deref()
+    }
+}
+
+/// The extension, its taxonomy category and a marker for rule-based results.
+fn rule_based_tags(request: &AnalysisRequest) -> Vec<String> {
+    vec![
+        request.file_extension.clone(),
+        classify_extension(&request.file_extension)
+            .name
+            .to_lowercase(),
+        "rule-based".to_string(),
+    ]
+}
+
+/// Resolves the organization scheme named in the request, using the
+/// request's structure for Johnny Decimal when it has one.
+fn scheme_for_request(request: &AnalysisRequest) -> Result<Box<dyn OrganizationScheme>> {
+    let structure = request
+        .existing_structure
+        .clone()
+        .and_then(|value| serde_json::from_value(value).ok());
+    scheme_from_id(&request.organization_scheme, structure)
+}
+
+/// The request's structure, for Johnny Decimal requests that carry one.
+fn request_structure(request: &AnalysisRequest) -> Option<JDStructure> {
+    scheme_for_request(request)
+        .ok()
+        .filter(|scheme| scheme.id() == JOHNNY_DECIMAL)?;
+    serde_json::from_value(request.existing_structure.clone()?).ok()
+}
+
+fn request_structure_id(request: &AnalysisRequest) -> Option<&str> {
+    request.existing_structure.as_ref()?["id"].as_str()
+}
+
+/// Adds the file's content and, unless the caller chose some, the most
+/// similar corrections as examples.
+fn prepare_request(request: AnalysisRequest, corrections: &[Correction]) -> AnalysisRequest {
+    let mut request = request.with_content(&ExtractionLimits::default());
+    if request.examples.is_empty() {
+        attach_examples(&mut request, corrections);
+    }
+    request
+}
+
+/// Re-ranks a Johnny Decimal result with the rules learned from the
+/// corrections and where the user moved this file before. Those win over
+/// the model and the extension whenever they point elsewhere.
+pub fn apply_corrections(
+    request: &AnalysisRequest,
+    mut result: AnalysisResult,
+    corrections: &[Correction],
+) -> Result<AnalysisResult> {
+    let structure = match request_structure(request) {
+        Some(structure) => structure,
+        None => return Ok(result),
+    };
+    let mut context = context_from_corrections(corrections, &request.file_path);
+    if context.rules.is_empty() && context.correction.is_none() {
+        return Ok(result);
+    }
+    context.ai_suggestion = result.assignment.clone();
+
+    let engine = JohnnyDecimalEngine::new()?;
+    let ranking = rank_candidates(&engine, &request_file_info(request), &structure, &context);
+    let best = match ranking.best() {
+        Some(best)
+            if best
+                .sources
+                .iter()
+                .any(|s| matches!(s, SignalSource::Rule | SignalSource::UserCorrection)) =>
+        {
+            best
+        }
+        _ => return Ok(result),
+    };
+
+    let unchanged = result
+        .assignment
+        .as_ref()
+        .is_some_and(|a| a.item_number == best.assignment.item_number);
+    if !unchanged {
+        result.proposed_item = None;
+    }
+    result.suggested_category = engine
+        .assignment_folder(&best.assignment, &structure)
+        .to_string_lossy()
+        .replace('\\', "/");
+    result.confidence = best.score;
+    result.reasoning = best.assignment.reasoning.clone();
+    result.assignment = Some(best.assignment.clone());
+    Ok(result)
+}
+
+fn request_file_info(request: &AnalysisRequest) -> serde_json::Value {
+    serde_json::json!({
+        "path": request.file_path,
+        "name": request.file_name,
+        "extension": request.file_extension,
+        "size": request.file_size,
+        "mime_type": request.mime_type,
+    })
+}
+
+/// The analysis prompt for the request's organization scheme. Johnny
+/// Decimal requests with a structure get the structure-aware prompt.
+pub fn build_analysis_prompt(request: &AnalysisRequest) -> Result<String> {
+    let scheme = scheme_for_request(request)?;
+    if let Some(structure) = request_structure(request) {
+        return Ok(build_structure_prompt(request, &structure));
+    }
+
+    Ok(format!(
+        r#"{}
 Analyze the following file and suggest the most appropriate category:
 
 File: {}
 Extension: {}
 Size: {} bytes
 Type: {}
-
-Johnny Decimal areas (10-19, 20-29, 30-39, etc.) should be used for broad categories.
-Categories (11, 12, 13, etc.) should be specific within each area.
-
+{}{}
 Respond with JSON:
 {{
-    "category": "Area Name/Category Name",
+    "category": "{}",
     "confidence": 0.0-1.0,
     "reasoning": "explanation",
     "alternatives": ["alt1", "alt2"],
@@ -224,162 +572,337 @@ Respond with JSON:
 }}
 
 Be concise and practical in your categorization."#,
-            request.file_name,
-            request.file_extension,
-            request.file_size,
-            request.mime_type.as_deref().unwrap_or("unknown")
-        )
-    }
-
-    fn parse_ai_response(&self, response: &str) -> Result<AnalysisResult> {
-        // Try to extract JSON from the response
-        if let Some(start) = response.find('{') {
-            if let Some(end) = response.rfind('}') {
-                let json_str = &response[start..=end];
-
-                match serde_json::from_str::<serde_json::Value>(json_str) {
-                    Ok(data) => {
-                        return Ok(AnalysisResult {
-                            suggested_category: data["category"]
-                                .as_str()
-                                .unwrap_or("90-99 Miscellaneous/91 Other Files")
-                                .to_string(),
-                            confidence: data["confidence"].as_f64().unwrap_or(0.5),
-                            reasoning: data["reasoning"]
-                                .as_str()
-                                .unwrap_or("AI analysis")
-                                .to_string(),
-                            alternative_categories: data["alternatives"]
-                                .as_array()
-                                .map(|arr| {
-                                    arr.iter()
-                                        .filter_map(|v| v.as_str())
-                                        .map(|s| s.to_string())
-                                        .collect()
-                                })
-                                .unwrap_or_default(),
-                            tags: data["tags"]
-                                .as_array()
-                                .map(|arr| {
-                                    arr.iter()
-                                        .filter_map(|v| v.as_str())
-                                        .map(|s| s.to_string())
-                                        .collect()
-                                })
-                                .unwrap_or_default(),
-                        });
-                    }
-                    Err(e) => {
-                        log::warn!("Failed to parse AI JSON response: {}", e);
-                    }
-                }
-            }
-        }
+        scheme.prompt_guidance(),
+        request.file_name,
+        request.file_extension,
+        request.file_size,
+        request.mime_type.as_deref().unwrap_or("unknown"),
+        content_section(request),
+        examples_section(request),
+        scheme.category_example()
+    ))
+}
 
-        // Fallback: extract category from text
-        let category = if response.contains("Documents") {
-            "20-29 Documents/21 Text Documents"
-        } else if response.contains("Media") || response.contains("Images") {
-            "30-39 Media/31 Images"
-        } else {
-            "90-99 Miscellaneous/91 Other Files"
-        };
+/// Reads a reply to the free-form prompt. It must match `analysis_schema`.
+pub fn parse_ai_response(response: &str) -> Result<AnalysisResult> {
+    let reply: AnalysisReply = parse_reply(response)?;
 
-        Ok(AnalysisResult {
-            suggested_category: category.to_string(),
-            confidence: 0.6,
-            reasoning: "Parsed from AI text response".to_string(),
-            alternative_categories: vec![],
-            tags: vec!["ai-parsed".to_string()],
-        })
+    Ok(AnalysisResult {
+        suggested_category: reply.category,
+        confidence: reply.confidence,
+        reasoning: reply.reasoning,
+        alternative_categories: reply.alternatives,
+        tags: reply.tags,
+        assignment: None,
+        proposed_item: None,
+    })
+}
+
+/// Strictly deserializes a schema-constrained reply.
+pub fn parse_reply<T: DeserializeOwned + ScoredReply>(response: &str) -> Result<T> {
+    let reply: T =
+        serde_json::from_str(response.trim()).map_err(|e| AppError::invalid_reply(e, response))?;
+    if !(0.0..=1.0).contains(&reply.confidence()) {
+        return Err(AppError::invalid_reply(
+            format!("confidence {} is outside 0.0-1.0", reply.confidence()),
+            response,
+        ));
     }
+    Ok(reply)
+}
 
-    pub async fn get_available_models(&self) -> Result<Vec<String>> {
-        if !self.is_running {
-            return Ok(vec![]);
-        }
+/// JSON schema for replies to the free-form prompt.
+pub fn analysis_schema() -> serde_json::Value {
+    serde_json::json!({
+        "type": "object",
+        "properties": {
+            "category": {"type": "string"},
+            "confidence": {"type": "number", "minimum": 0, "maximum": 1},
+            "reasoning": {"type": "string"},
+            "alternatives": {"type": "array", "items": {"type": "string"}},
+            "tags": {"type": "array", "items": {"type": "string"}}
+        },
+        "required": ["category", "confidence", "reasoning", "alternatives", "tags"],
+        "additionalProperties": false
+    })
+}
 
-        let response = self
-            .client
-            .get(format!("{}/api/tags", self.base_url))
-            .send()
-            .await
-            .map_err(AppError::Http)?;
+/// JSON schema for structure-aware replies. `id` is limited to the
+/// categories and items of `structure`.
+pub fn structure_reply_schema(structure: &JDStructure) -> serde_json::Value {
+    let ids: Vec<String> = structure
+        .areas
+        .iter()
+        .flat_map(|a| &a.categories)
+        .flat_map(|c| {
+            std::iter::once(format!("{:02}", c.number))
+                .chain(c.items.iter().map(|i| i.number.clone()))
+        })
+        .collect();
+
+    serde_json::json!({
+        "type": "object",
+        "properties": {
+            "id": {"type": "string", "enum": ids},
+            "new_item": {"type": ["string", "null"]},
+            "confidence": {"type": "number", "minimum": 0, "maximum": 1},
+            "reasoning": {"type": "string"},
+            "alternatives": {"type": "array", "items": {"type": "string"}},
+            "tags": {"type": "array", "items": {"type": "string"}}
+        },
+        "required": ["id", "new_item", "confidence", "reasoning", "alternatives", "tags"],
+        "additionalProperties": false
+    })
+}
 
-        if !response.status().is_success() {
-            return Err(AppError::AiService("Failed to fetch models".to_string()));
+/// Asks the model for an ID from the request's structure. Replies naming an
+/// ID the structure lacks are answered with a correction, up to
+/// `MAX_STRUCTURE_RETRIES` times. Requests without a structure use the
+/// free-form prompt.
+pub async fn analyze_with_provider<P: AiProvider + ?Sized>(
+    provider: &P,
+    model: &str,
+    request: &AnalysisRequest,
+) -> Result<AnalysisResult> {
+    let prompt = build_analysis_prompt(request)?;
+    let structure = match request_structure(request) {
+        Some(structure) => structure,
+        None => {
+            let reply = provider
+                .generate(model, &prompt, Some(&analysis_schema()))
+                .await?;
+            return parse_ai_response(&reply).map_err(log_raw_reply);
         }
+    };
 
-        let data: serde_json::Value = response.json().await.map_err(AppError::Http)?;
+    let schema = structure_reply_schema(&structure);
+    let mut conversation = prompt;
+    for attempt in 0..=MAX_STRUCTURE_RETRIES {
+        let reply = provider
+            .generate(model, &conversation, Some(&schema))
+            .await?;
+        let parsed = parse_reply(&reply).map_err(log_raw_reply)?;
+        match resolve_structure_reply(&parsed, &structure) {
+            Ok(result) => return Ok(result),
+            Err(problem) => {
+                log::info!("Reply {} rejected: {}", attempt + 1, problem);
+                conversation = format!(
+                    "{}\n\nYour previous answer was:\n{}\n\nThat answer is invalid: {}. \
+                     Answer again with an item ID from the list, or a category ID \
+                     with \"new_item\" set.",
+                    conversation,
+                    reply.trim(),
+                    problem
+                );
+            }
+        }
+    }
 
-        let models = data["models"]
-            .as_array()
-            .map(|arr| {
-                arr.iter()
-                    .filter_map(|m| m["name"].as_str())
-                    .map(|s| s.to_string())
-                    .collect()
-            })
-            .unwrap_or_default();
+    Err(AppError::AiService(format!(
+        "No valid Johnny Decimal ID after {} attempts",
+        MAX_STRUCTURE_RETRIES + 1
+    )))
+}
 
-        Ok(models)
+/// Logs the model output behind a rejected reply, which the error's
+/// message leaves out.
+fn log_raw_reply(error: AppError) -> AppError {
+    if let AppError::Ollama {
+        message,
+        raw_reply: Some(raw),
+    } = &error
+    {
+        let shown: String = raw.chars().take(RAW_REPLY_LOG_CHARS).collect();
+        let more = if shown.len() < raw.len() { "..." } else { "" };
+        log::debug!("{}; the model replied: {}{}", message, shown, more);
     }
+    error
+}
 
-    async fn is_service_available(&self) -> bool {
-        match self
-            .client
-            .get(format!("{}/api/tags", self.base_url))
-            .timeout(Duration::from_secs(5))
-            .send()
-            .await
-        {
-            Ok(response) => response.status().is_success(),
-            Err(_) => false,
+fn build_structure_prompt(request: &AnalysisRequest, structure: &JDStructure) -> String {
+    let mut listing = String::new();
+    for area in &structure.areas {
+        listing.push_str(&format!("{}\n", area.folder_name()));
+        for category in &area.categories {
+            listing.push_str(&format!("  {}\n", category.folder_name()));
+            for item in category.items.iter().take(PROMPT_ITEMS_PER_CATEGORY) {
+                listing.push_str(&format!("    {} {}\n", item.number, item.name));
+            }
+            if category.items.len() > PROMPT_ITEMS_PER_CATEGORY {
+                listing.push_str(&format!(
+                    "    ... {} more items\n",
+                    category.items.len() - PROMPT_ITEMS_PER_CATEGORY
+                ));
+            }
         }
     }
 
-    async fn start_ollama_process(&self) -> Result<()> {
-        // Try to start Ollama as a subprocess
-        let mut cmd = Command::new("ollama");
-        cmd.arg("serve").stdout(Stdio::null()).stderr(Stdio::null());
+    format!(
+        r#"You are filing files into an existing Johnny Decimal system. These are its areas, categories and items:
+
+{}
+File: {}
+Extension: {}
+Size: {} bytes
+Type: {}
+{}{}
+Pick the item this file belongs in. Only use IDs from the list. If no item fits but a category does, give the category ID and a name for a new item.
 
-        match cmd.spawn() {
-            Ok(_) => Ok(()),
-            Err(e) => Err(AppError::AiService(format!(
-                "Failed to spawn Ollama process: {}",
-                e
-            ))),
+Respond with JSON:
+{{
+    "id": "21.01 for an item, or 21 with new_item",
+    "new_item": null,
+    "confidence": 0.0-1.0,
+    "reasoning": "explanation",
+    "alternatives": ["other IDs from the list"],
+    "tags": ["tag1", "tag2"]
+}}"#,
+        listing,
+        request.file_name,
+        request.file_extension,
+        request.file_size,
+        request.mime_type.as_deref().unwrap_or("unknown"),
+        content_section(request),
+        examples_section(request),
+    )
+}
+
+/// Past corrections, so the model follows the user's habits.
+fn examples_section(request: &AnalysisRequest) -> String {
+    if request.examples.is_empty() {
+        return String::new();
+    }
+    let mut section =
+        "The user corrected these placements before; file similar files the same way:\n"
+            .to_string();
+    for example in &request.examples {
+        match &example.suggested {
+            Some(suggested) => section.push_str(&format!(
+                "- {}: {} (not {})\n",
+                example.file_name, example.chosen, suggested
+            )),
+            None => section.push_str(&format!("- {}: {}\n", example.file_name, example.chosen)),
         }
     }
+    section
+}
 
-    async fn ensure_model_available(&mut self, model_name: &str) -> Result<()> {
-        let models = self.get_available_models().await?;
+/// The file's opening text, so files with meaningless names can be placed
+/// by what they say.
+fn content_section(request: &AnalysisRequest) -> String {
+    match &request.content_snippet {
+        Some(snippet) => format!("Content excerpt:\n\"\"\"\n{}\n\"\"\"\n", snippet),
+        None => String::new(),
+    }
+}
+
+/// Checks a structure-aware reply against the structure. The error says
+/// what was wrong so it can be fed back to the model.
+pub fn resolve_structure_reply(
+    reply: &StructureReply,
+    structure: &JDStructure,
+) -> std::result::Result<AnalysisResult, String> {
+    let raw_id = reply.id.trim();
+    let id: JDId = raw_id
+        .parse()
+        .map_err(|_| format!("'{}' is not a Johnny Decimal ID", raw_id))?;
+    let new_item = reply
+        .new_item
+        .as_deref()
+        .map(str::trim)
+        .filter(|name| !name.is_empty());
 
-        if !models.iter().any(|m| m.contains(model_name)) {
-            log::info!("Model {} not found, attempting to pull...", model_name);
-            // In a real implementation, we would pull the model here
-            // For now, we'll just log and continue
+    let category_number = id
+        .category_number()
+        .ok_or_else(|| format!("'{}' is an area, not an item", raw_id))?;
+    let (area, category) = structure
+        .areas
+        .iter()
+        .find_map(|a| {
+            a.categories
+                .iter()
+                .find(|c| c.number == category_number)
+                .map(|c| (a, c))
+        })
+        .filter(|_| structure.lookup_id(&id).is_some() || new_item.is_some())
+        .ok_or_else(|| format!("'{}' is not in the structure", raw_id))?;
+
+    let item_number = match (id.number, new_item) {
+        (JDNumber::Item { .. }, _) if structure.lookup_id(&id).is_some() => id.local_id(),
+        (_, Some(_)) => {
+            let next = category
+                .items
+                .iter()
+                .filter_map(|i| match i.number.parse::<JDId>().ok()?.number {
+                    JDNumber::Item { index, .. } => Some(index),
+                    _ => None,
+                })
+                .max()
+                .unwrap_or(0)
+                + 1;
+            JDId::item(category_number, next)
+                .map_err(|_| format!("category {} has no free item IDs", category_number))?
+                .local_id()
         }
+        _ => return Err(format!("'{}' is a category, not an item", raw_id)),
+    };
+    let proposed_item = new_item
+        .filter(|_| !category.items.iter().any(|i| i.number == item_number))
+        .map(str::to_string);
 
-        self.current_model = Some(model_name.to_string());
-        Ok(())
-    }
+    Ok(AnalysisResult {
+        suggested_category: format!("{}/{}", area.folder_name(), category.folder_name()),
+        confidence: reply.confidence,
+        reasoning: reply.reasoning.clone(),
+        alternative_categories: reply.alternatives.clone(),
+        tags: reply.tags.clone(),
+        assignment: Some(CategoryAssignment {
+            area_number: area.number,
+            category_number,
+            item_number,
+            confidence: reply.confidence,
+            reasoning: reply.reasoning.clone(),
+        }),
+        proposed_item,
+    })
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::ai_provider::AiBackendSettings;
 
     #[tokio::test]
     async fn test_ollama_service_new() {
-        let service = OllamaService::new().await.unwrap();
+        let service = OllamaService::new(&AppSettings::default()).await.unwrap();
         assert!(!service.is_running);
         assert!(service.current_model.is_none());
     }
 
+    #[tokio::test]
+    async fn test_service_uses_configured_provider() {
+        let settings = AppSettings {
+            ai_provider: "openai-compatible".to_string(),
+            ai_model: "qwen2.5:7b".to_string(),
+            ..AppSettings::default()
+        };
+        let service = OllamaService::new(&settings).await.unwrap();
+        assert_eq!(
+            service.provider().id(),
+            crate::ai_provider::OPENAI_COMPATIBLE
+        );
+        assert_eq!(service.default_model, "qwen2.5:7b");
+
+        let settings = AppSettings {
+            ai_provider: "watson".to_string(),
+            ..AppSettings::default()
+        };
+        assert!(OllamaService::new(&settings).await.is_err());
+    }
+
     #[tokio::test]
     async fn test_rule_based_analysis() {
-        let service = OllamaService::new().await.unwrap();
+        let service = OllamaService::new(&AppSettings::default()).await.unwrap();
 
         let request = AnalysisRequest {
             file_path: "/test/document.pdf".to_string(),
@@ -389,10 +912,420 @@ mod tests {
             mime_type: Some("application/pdf".to_string()),
             existing_structure: None,
             organization_scheme: "JOHNNY_DECIMAL".to_string(),
+            content_snippet: None,
+            examples: vec![],
         };
 
         let result = service.rule_based_analysis(&request).unwrap();
         assert!(result.suggested_category.contains("Documents"));
         assert_eq!(result.confidence, 0.75);
+        assert_eq!(
+            result.tags,
+            vec!["pdf", "reports and documents", "rule-based"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_prompt_follows_scheme() {
+        let service = OllamaService::new(&AppSettings::default()).await.unwrap();
+
+        let request = AnalysisRequest {
+            file_path: "/test/photo.jpg".to_string(),
+            file_name: "photo.jpg".to_string(),
+            file_extension: "jpg".to_string(),
+            file_size: 2048,
+            mime_type: Some("image/jpeg".to_string()),
+            existing_structure: None,
+            organization_scheme: "PARA".to_string(),
+            content_snippet: Some("Holiday in Lisbon".to_string()),
+            examples: vec![FewShotExample {
+                file_name: "lisbon-2023.jpg".to_string(),
+                suggested: Some("Resources/Images".to_string()),
+                chosen: "Areas/Travel".to_string(),
+            }],
+        };
+
+        let prompt = build_analysis_prompt(&request).unwrap();
+        assert!(prompt.contains("PARA method"));
+        assert!(prompt.contains("- lisbon-2023.jpg: Areas/Travel (not Resources/Images)\n"));
+        assert!(prompt.contains("Content excerpt:\n\"\"\"\nHoliday in Lisbon\n"));
+        assert!(!prompt.contains("Johnny Decimal"));
+
+        let result = service.rule_based_analysis(&request).unwrap();
+        assert_eq!(result.suggested_category, "Resources/Images");
+    }
+
+    #[tokio::test]
+    async fn test_rule_based_matches_engine() {
+        let service = OllamaService::new(&AppSettings::default()).await.unwrap();
+        let engine = JohnnyDecimalEngine::new().unwrap();
+        let structure = engine.build_structure(
+            &[
+                serde_json::json!({"path": "/home/a.txt", "extension": "txt"}),
+                serde_json::json!({"path": "/home/b.zip", "extension": "zip"}),
+            ],
+            "/home",
+        );
+
+        let mut request = AnalysisRequest {
+            file_path: "/home/c.zip".to_string(),
+            file_name: "c.zip".to_string(),
+            file_extension: "zip".to_string(),
+            file_size: 4096,
+            mime_type: None,
+            existing_structure: None,
+            organization_scheme: "JOHNNY_DECIMAL".to_string(),
+            content_snippet: None,
+            examples: vec![],
+        };
+
+        let result = service.rule_based_analysis(&request).unwrap();
+        assert_eq!(
+            result.suggested_category,
+            "50-59 Archives/51 Compressed Files"
+        );
+
+        request.existing_structure = Some(serde_json::to_value(&structure).unwrap());
+        let result = service.rule_based_analysis(&request).unwrap();
+        let assignment = result.assignment.unwrap();
+        assert_eq!(
+            assignment,
+            engine.assign_file(&request_file_info(&request), &structure)
+        );
+        assert!(structure.lookup(&assignment.item_number).is_some());
+    }
+
+    /// Replays canned replies and records the prompts it was sent.
+    struct ScriptedProvider {
+        config: crate::ai_provider::ProviderConfig,
+        replies: std::sync::Mutex<Vec<String>>,
+        prompts: std::sync::Arc<std::sync::Mutex<Vec<String>>>, // Shared with the test
+    }
+
+    #[async_trait::async_trait]
+    impl AiProvider for ScriptedProvider {
+        fn id(&self) -> &'static str {
+            "scripted"
+        }
+
+        fn config(&self) -> &crate::ai_provider::ProviderConfig {
+            &self.config
+        }
+
+        async fn health(&self) -> crate::ai_provider::ProviderHealth {
+            crate::ai_provider::ProviderHealth {
+                provider: self.id().to_string(),
+                available: true,
+                detail: None,
+            }
+        }
+
+        async fn list_models(&self) -> Result<Vec<String>> {
+            Ok(vec![
+                "llama3.2:1b".to_string(),
+                "qwen2.5:latest".to_string(),
+            ])
+        }
+
+        async fn generate(
+            &self,
+            _model: &str,
+            prompt: &str,
+            _schema: Option<&serde_json::Value>,
+        ) -> Result<String> {
+            self.prompts.lock().unwrap().push(prompt.to_string());
+            Ok(self.replies.lock().unwrap().remove(0))
+        }
+    }
+
+    fn scripted(replies: Vec<String>) -> ScriptedProvider {
+        ScriptedProvider {
+            config: AiBackendSettings::default().ollama,
+            replies: std::sync::Mutex::new(replies),
+            prompts: Default::default(),
+        }
+    }
+
+    fn reply(id: &str, new_item: Option<&str>) -> String {
+        serde_json::json!({
+            "id": id,
+            "new_item": new_item,
+            "confidence": 0.8,
+            "reasoning": "Looks like an invoice",
+            "alternatives": [],
+            "tags": []
+        })
+        .to_string()
+    }
+
+    #[tokio::test]
+    async fn test_structure_aware_analysis() {
+        let engine = JohnnyDecimalEngine::new().unwrap();
+        let structure = engine.build_structure(
+            &[serde_json::json!({"path": "/home/a.pdf", "extension": "pdf"})],
+            "/home",
+        );
+        let request = AnalysisRequest {
+            file_path: "/home/invoice.pdf".to_string(),
+            file_name: "invoice.pdf".to_string(),
+            file_extension: "pdf".to_string(),
+            file_size: 1024,
+            mime_type: None,
+            existing_structure: Some(serde_json::to_value(&structure).unwrap()),
+            organization_scheme: "JOHNNY_DECIMAL".to_string(),
+            content_snippet: None,
+            examples: vec![],
+        };
+
+        let prompt = build_analysis_prompt(&request).unwrap();
+        assert!(prompt.contains("21 Reports and Documents"));
+        assert!(prompt.contains("21.01 "));
+
+        let schema = structure_reply_schema(&structure);
+        assert!(schema["properties"]["id"]["enum"]
+            .as_array()
+            .unwrap()
+            .contains(&serde_json::json!("21.01")));
+
+        let provider = scripted(vec![reply("24.01", None), reply("21", Some("Invoices"))]);
+        let result = provider.analyze("model", &request).await.unwrap();
+        let assignment = result.assignment.unwrap();
+        assert_eq!(assignment.item_number, "21.02");
+        assert_eq!(result.proposed_item.as_deref(), Some("Invoices"));
+        assert_eq!(
+            result.suggested_category,
+            "20-29 Documents/21 Reports and Documents"
+        );
+
+        let prompts = provider.prompts.lock().unwrap();
+        assert_eq!(prompts.len(), 2);
+        assert!(prompts[1].contains("'24.01' is not in the structure"));
+
+        let parsed = parse_reply(&reply("21.01", None)).unwrap();
+        assert_eq!(
+            resolve_structure_reply(&parsed, &structure)
+                .unwrap()
+                .proposed_item,
+            None
+        );
+        let parsed = parse_reply(&reply("21", None)).unwrap();
+        assert!(resolve_structure_reply(&parsed, &structure).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_schema_violation_keeps_raw_reply() {
+        let raw = r#"Sure! {"category": "Finance/Invoices", "confidence": 0.9}"#;
+        let request = AnalysisRequest {
+            file_path: "/home/invoice.pdf".to_string(),
+            file_name: "invoice.pdf".to_string(),
+            file_extension: "pdf".to_string(),
+            file_size: 1024,
+            mime_type: None,
+            existing_structure: None,
+            organization_scheme: "JOHNNY_DECIMAL".to_string(),
+            content_snippet: None,
+            examples: vec![],
+        };
+
+        match scripted(vec![raw.to_string()])
+            .analyze("model", &request)
+            .await
+        {
+            Err(AppError::Ollama { raw_reply, .. }) => assert_eq!(raw_reply.as_deref(), Some(raw)),
+            other => panic!(
+                "expected a schema error, got {:?}",
+                other.map(|r| r.reasoning)
+            ),
+        }
+
+        let valid = r#"{"category": "20-29 Documents/21 Reports and Documents", "confidence": 0.9,
+            "reasoning": "An invoice", "alternatives": [], "tags": ["finance"]}"#;
+        assert_eq!(parse_ai_response(valid).unwrap().tags, vec!["finance"]);
+        assert!(parse_ai_response(&valid.replace("0.9", "9")).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_requests_carry_file_content() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("notes.txt");
+        std::fs::write(&path, "Quarterly   report\n\nfor Q3").unwrap();
+        let metadata = crate::file_operations::FileScanner::new()
+            .unwrap()
+            .get_file_metadata(path.to_str().unwrap())
+            .await
+            .unwrap();
+
+        let request = AnalysisRequest::from_metadata(&metadata, "PARA", None).unwrap();
+        assert_eq!(request.file_extension, "txt");
+        assert_eq!(
+            request.content_snippet.as_deref(),
+            Some("Quarterly report\nfor Q3")
+        );
+
+        // A snippet the caller supplied is kept
+        let supplied = AnalysisRequest {
+            content_snippet: Some("From the preview".to_string()),
+            ..request
+        }
+        .with_content(&ExtractionLimits::default());
+        assert_eq!(
+            supplied.content_snippet.as_deref(),
+            Some("From the preview")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cache_key_covers_prompt_inputs() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db = DatabaseManager::new(temp_dir.path().join("test.db").to_str().unwrap()).unwrap();
+        let answer = r#"{"category": "20-29 Documents/21 Reports and Documents", "confidence": 0.9,
+            "reasoning": "An invoice", "alternatives": [], "tags": []}"#;
+        let service = OllamaService::from_provider(
+            Box::new(scripted(vec![answer.to_string(), answer.to_string()])),
+            "llama3.2:1b",
+        );
+        let request = AnalysisRequest {
+            file_path: "/home/invoice.pdf".to_string(),
+            file_name: "invoice.pdf".to_string(),
+            file_extension: "pdf".to_string(),
+            file_size: 1024,
+            mime_type: None,
+            existing_structure: None,
+            organization_scheme: "JOHNNY_DECIMAL".to_string(),
+            content_snippet: None,
+            examples: vec![],
+        };
+        let key = service.cache_key(&request, "abc123", None).unwrap();
+        assert_eq!(key, service.cache_key(&request, "abc123", None).unwrap());
+
+        let para = AnalysisRequest {
+            organization_scheme: "PARA".to_string(),
+            ..request.clone()
+        };
+        let snippet = AnalysisRequest {
+            content_snippet: Some("Invoice 2024-117".to_string()),
+            ..request.clone()
+        };
+        let examples = AnalysisRequest {
+            examples: vec![FewShotExample {
+                file_name: "invoice-2023.pdf".to_string(),
+                suggested: None,
+                chosen: "20-29 Finance/21 Invoices".to_string(),
+            }],
+            ..request.clone()
+        };
+        let para_key = service.cache_key(&para, "abc123", None).unwrap();
+        assert_ne!(para_key.organization_scheme, key.organization_scheme);
+        for changed in [&snippet, &examples] {
+            let other = service.cache_key(changed, "abc123", None).unwrap();
+            assert_ne!(other.prompt_hash, key.prompt_hash);
+        }
+
+        // Only identical prompt inputs hit; the provider has just two replies
+        for request in [&request, &request, &snippet] {
+            service
+                .analyze_file_cached(&db, request.clone(), "abc123", None)
+                .await
+                .unwrap();
+        }
+        let stats = db.analysis_cache_stats().await.unwrap();
+        assert_eq!((stats.entries, stats.hits, stats.misses), (2, 1, 2));
+    }
+
+    #[tokio::test]
+    async fn test_corrections_guide_cached_analysis() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db = DatabaseManager::new(temp_dir.path().join("test.db").to_str().unwrap()).unwrap();
+        let structure = crate::templates::builtin_template("small_business")
+            .unwrap()
+            .instantiate("/biz");
+        for (name, days_ago) in [("0301", 3), ("0315", 2), ("0329", 1)] {
+            let mut correction = Correction::new(
+                &format!("/dl/invoice_acme_{}.pdf", name),
+                None,
+                "21.02".parse().unwrap(),
+                "21.02 Purchase Invoices",
+                Some(&structure.id),
+            );
+            correction.created_at -= chrono::Duration::days(days_ago);
+            db.save_correction(&correction).await.unwrap();
+        }
+
+        // The model files the invoice under reports
+        let provider = scripted(vec![reply("24.02", None)]);
+        let prompts = provider.prompts.clone();
+        let service = OllamaService::from_provider(Box::new(provider), "llama3.2:1b");
+        let request = AnalysisRequest {
+            file_path: "/dl/invoice_acme_0412.pdf".to_string(),
+            file_name: "invoice_acme_0412.pdf".to_string(),
+            file_extension: "pdf".to_string(),
+            file_size: 1024,
+            mime_type: None,
+            existing_structure: Some(serde_json::to_value(&structure).unwrap()),
+            organization_scheme: "JOHNNY_DECIMAL".to_string(),
+            content_snippet: None,
+            examples: vec![],
+        };
+
+        let result = service
+            .analyze_file_cached(&db, request.clone(), "abc123", Some(1))
+            .await
+            .unwrap();
+        assert!(
+            prompts.lock().unwrap()[0].contains("invoice_acme_0329.pdf: 21.02 Purchase Invoices")
+        );
+        assert_eq!(result.assignment.unwrap().item_number, "21.02");
+        assert!(result.suggested_category.ends_with("/21 Invoices"));
+        assert!(result.reasoning.contains("Rule 'invoice'"));
+
+        // The cache keeps the model's answer; the corrections apply on top
+        let request = prepare_request(request, &db.recent_corrections(None, 10).await.unwrap());
+        assert_eq!(request.examples.len(), 3);
+        let key = service.cache_key(&request, "abc123", Some(1)).unwrap();
+        let cached = db.get_cached_analysis(&key).await.unwrap().unwrap();
+        assert_eq!(cached.assignment.unwrap().item_number, "24.02");
+    }
+
+    #[tokio::test]
+    async fn test_switch_model_persists_choice() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db = DatabaseManager::new(temp_dir.path().join("test.db").to_str().unwrap()).unwrap();
+        let mut service = OllamaService::from_provider(Box::new(scripted(vec![])), "llama3.2:1b");
+
+        service.switch_model("qwen2.5", &db).await.unwrap();
+        assert_eq!(service.current_model(), Some("qwen2.5"));
+        assert_eq!(db.load_settings().await.unwrap().ai_model, "qwen2.5");
+
+        assert!(service.switch_model("mistral", &db).await.is_err());
+        assert_eq!(service.current_model(), Some("qwen2.5"));
+    }
+
+    #[tokio::test]
+    async fn test_start_only_checks_other_backends() {
+        let mut service = OllamaService::from_provider(Box::new(scripted(vec![])), "llama3.2:1b");
+        service.is_running = false;
+        service.current_model = None;
+        service.default_model = "qwen2.5:latest".to_string();
+
+        assert_eq!(service.start().await.unwrap(), "scripted is running");
+        assert!(service.is_running);
+        assert!(service.supervisor.is_none());
+        assert_eq!(service.current_model(), Some("qwen2.5:latest"));
+    }
+
+    #[tokio::test]
+    async fn test_missing_model_is_reported_not_pulled() {
+        let mut service = OllamaService::from_provider(Box::new(scripted(vec![])), "llama3.2:1b");
+        service.current_model = None;
+        service.default_model = "mistral".to_string();
+
+        assert!(!service.load_installed_model("mistral").await.unwrap());
+        assert_eq!(service.missing_model(), Some("mistral"));
+        let status = service.get_status().await.unwrap();
+        assert_eq!(status["missing_model"], "mistral");
+
+        assert!(service.load_installed_model("qwen2.5").await.unwrap());
+        assert_eq!(service.current_model(), Some("qwen2.5"));
+        assert_eq!(service.missing_model(), None);
     }
 }
@@ -0,0 +1,475 @@
+use crate::ai_service::{AnalysisRequest, AnalysisResult, OllamaService};
+use crate::database::{AnalysisCacheKey, DatabaseManager};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::{mpsc, watch, RwLock, Semaphore};
+use tokio::task::JoinSet;
+use tokio::time::{sleep, timeout, Duration, Instant};
+
+/// Parallel requests when `OLLAMA_NUM_PARALLEL` is not set.
+const DEFAULT_CONCURRENCY: usize = 2;
+
+#[derive(Debug, Clone)]
+pub struct BatchOptions {
+    pub concurrency: usize, // Match the server's parallel request slots
+    pub file_timeout: Duration,
+    pub max_retries: u32,
+    pub initial_backoff: Duration,  // Doubled after every retry
+    pub deadline: Option<Duration>, // Files not done by then use the rules
+    pub cache: Option<BatchCache>,
+}
+
+/// Where a batch looks up and stores the model's replies, as
+/// `analyze_file_cached` does. Files without a hash are not cached.
+#[derive(Debug, Clone)]
+pub struct BatchCache {
+    pub db: DatabaseManager,
+    pub file_hashes: HashMap<String, String>, // Content hash by file path
+    pub structure_revision: Option<u32>,
+}
+
+impl Default for BatchOptions {
+    fn default() -> Self {
+        Self {
+            concurrency: std::env::var("OLLAMA_NUM_PARALLEL")
+                .ok()
+                .and_then(|v| v.parse().ok())
+                .filter(|n| *n > 0)
+                .unwrap_or(DEFAULT_CONCURRENCY),
+            file_timeout: Duration::from_secs(30),
+            max_retries: 2,
+            initial_backoff: Duration::from_millis(500),
+            deadline: None,
+            cache: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BatchSource {
+    Ai,
+    Cached,
+    RuleBased,
+}
+
+/// One finished file, sent as soon as it completes.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchItem {
+    pub index: usize, // Position in the submitted requests
+    pub file_path: String,
+    pub source: BatchSource,
+    pub attempts: u32,
+    pub result: Option<AnalysisResult>,
+    pub error: Option<String>, // Last AI error, also set when the rules took over
+}
+
+/// Results arrive on `results` in completion order. The channel closes
+/// when every file is done or the batch was cancelled.
+pub struct BatchHandle {
+    pub results: mpsc::Receiver<BatchItem>,
+    cancel: BatchCanceller,
+}
+
+impl BatchHandle {
+    /// Stops the batch from elsewhere while `results` is being read.
+    pub fn canceller(&self) -> BatchCanceller {
+        self.cancel.clone()
+    }
+}
+
+#[derive(Clone)]
+pub struct BatchCanceller(Arc<watch::Sender<bool>>);
+
+impl BatchCanceller {
+    /// Stops the batch. Files in flight are abandoned and nothing more is sent.
+    pub fn cancel(&self) {
+        let _ = self.0.send(true);
+    }
+}
+
+/// Analyzes `requests` with at most `options.concurrency` in flight. The
+/// service is only read-locked per attempt, so it can be stopped meanwhile.
+pub fn analyze_batch(
+    service: Arc<RwLock<OllamaService>>,
+    requests: Vec<AnalysisRequest>,
+    options: BatchOptions,
+) -> BatchHandle {
+    let concurrency = options.concurrency.max(1);
+    let (sender, results) = mpsc::channel(concurrency * 2);
+    let (cancel, mut cancelled) = watch::channel(false);
+    let deadline = options.deadline.map(|d| Instant::now() + d);
+    let options = Arc::new(options);
+
+    tokio::spawn(async move {
+        let semaphore = Arc::new(Semaphore::new(concurrency));
+        let mut tasks = JoinSet::new();
+
+        for (index, request) in requests.into_iter().enumerate() {
+            let permit = tokio::select! {
+                permit = semaphore.clone().acquire_owned() => match permit {
+                    Ok(permit) => permit,
+                    Err(_) => break,
+                },
+                _ = wait_cancelled(&mut cancelled) => break,
+            };
+
+            let service = service.clone();
+            let sender = sender.clone();
+            let options = options.clone();
+            let cancelled = cancelled.clone();
+            tasks.spawn(async move {
+                let item =
+                    analyze_one(&service, index, request, &options, deadline, cancelled).await;
+                drop(permit);
+                if let Some(item) = item {
+                    let _ = sender.send(item).await;
+                }
+            });
+        }
+
+        while tasks.join_next().await.is_some() {}
+    });
+
+    BatchHandle {
+        results,
+        cancel: BatchCanceller(Arc::new(cancel)),
+    }
+}
+
+/// `None` when the batch was cancelled before the file finished.
+async fn analyze_one(
+    service: &RwLock<OllamaService>,
+    index: usize,
+    request: AnalysisRequest,
+    options: &BatchOptions,
+    deadline: Option<Instant>,
+    mut cancelled: watch::Receiver<bool>,
+) -> Option<BatchItem> {
+    let mut attempts = 0;
+    let mut backoff = options.initial_backoff;
+    let mut last_error = None;
+
+    let cached = match &options.cache {
+        Some(cache) => cache_entry(service, cache, &request).await,
+        None => None,
+    };
+    if let Some((cache, key)) = &cached {
+        match cache.db.get_cached_analysis(key).await {
+            Ok(Some(result)) => {
+                return Some(BatchItem {
+                    index,
+                    file_path: request.file_path,
+                    source: BatchSource::Cached,
+                    attempts,
+                    result: Some(result),
+                    error: None,
+                })
+            }
+            Ok(None) => {}
+            Err(e) => log::warn!("Analysis cache lookup failed: {}", e),
+        }
+    }
+
+    loop {
+        let remaining = deadline.map(|d| d.saturating_duration_since(Instant::now()));
+        if remaining == Some(Duration::ZERO) {
+            last_error.get_or_insert_with(|| "Batch deadline expired".to_string());
+            break;
+        }
+        let limit = remaining.map_or(options.file_timeout, |r| r.min(options.file_timeout));
+
+        attempts += 1;
+        let ai = service.read().await;
+        let outcome = tokio::select! {
+            outcome = timeout(limit, ai.ai_analyze_file(&request)) => outcome,
+            _ = wait_cancelled(&mut cancelled) => return None,
+        };
+        let retry = match outcome {
+            Ok(Ok(result)) => {
+                if let Some((cache, key)) = &cached {
+                    if let Err(e) = cache.db.cache_analysis(key, &result).await {
+                        log::warn!("Could not cache analysis: {}", e);
+                    }
+                }
+                return Some(BatchItem {
+                    index,
+                    file_path: request.file_path,
+                    source: BatchSource::Ai,
+                    attempts,
+                    result: Some(result),
+                    error: None,
+                });
+            }
+            Ok(Err(e)) => {
+                let transient = e.is_transient();
+                last_error = Some(e.to_string());
+                transient
+            }
+            Err(_) => {
+                last_error = Some(format!("Analysis timed out after {:?}", limit));
+                true
+            }
+        };
+        drop(ai);
+        if !retry || attempts > options.max_retries {
+            break;
+        }
+
+        let pause = deadline.map_or(backoff, |d| {
+            backoff.min(d.saturating_duration_since(Instant::now()))
+        });
+        tokio::select! {
+            _ = sleep(pause) => {},
+            _ = wait_cancelled(&mut cancelled) => return None,
+        }
+        backoff *= 2;
+    }
+
+    log::info!(
+        "Using rules for {} after {} attempts: {:?}",
+        request.file_path,
+        attempts,
+        last_error
+    );
+    let (result, error) = match service.read().await.rule_based_analysis(&request) {
+        Ok(result) => (Some(result), last_error),
+        Err(e) => (None, Some(e.to_string())),
+    };
+    Some(BatchItem {
+        index,
+        file_path: request.file_path,
+        source: BatchSource::RuleBased,
+        attempts,
+        result,
+        error,
+    })
+}
+
+/// The cache and the key a file's reply is stored under, if it has a hash
+/// and a model is loaded.
+async fn cache_entry<'a>(
+    service: &RwLock<OllamaService>,
+    cache: &'a BatchCache,
+    request: &AnalysisRequest,
+) -> Option<(&'a BatchCache, AnalysisCacheKey)> {
+    let file_hash = cache.file_hashes.get(&request.file_path)?;
+    match service
+        .read()
+        .await
+        .cache_key(request, file_hash, cache.structure_revision)
+    {
+        Ok(key) => Some((cache, key)),
+        Err(e) => {
+            log::debug!("Not caching {}: {}", request.file_path, e);
+            None
+        }
+    }
+}
+
+async fn wait_cancelled(cancelled: &mut watch::Receiver<bool>) {
+    while !*cancelled.borrow() {
+        if cancelled.changed().await.is_err() {
+            // The handle is gone, so nobody can cancel any more
+            std::future::pending::<()>().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai_provider::{AiBackendSettings, AiProvider, ProviderConfig, ProviderHealth};
+    use crate::error::Result;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Answers after `delay`; the first `slow_calls` calls take ten times longer.
+    struct SlowProvider {
+        config: ProviderConfig,
+        delay: Duration,
+        slow_calls: usize,
+        calls: AtomicUsize,
+        in_flight: AtomicUsize,
+        max_in_flight: Arc<AtomicUsize>,
+    }
+
+    /// Counts a request as finished even when a timeout drops it.
+    struct InFlight<'a>(&'a AtomicUsize);
+
+    impl Drop for InFlight<'_> {
+        fn drop(&mut self) {
+            self.0.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl AiProvider for SlowProvider {
+        fn id(&self) -> &'static str {
+            "slow"
+        }
+
+        fn config(&self) -> &ProviderConfig {
+            &self.config
+        }
+
+        async fn health(&self) -> ProviderHealth {
+            ProviderHealth {
+                provider: self.id().to_string(),
+                available: true,
+                detail: None,
+            }
+        }
+
+        async fn list_models(&self) -> Result<Vec<String>> {
+            Ok(vec![])
+        }
+
+        async fn generate(
+            &self,
+            _model: &str,
+            _prompt: &str,
+            _schema: Option<&serde_json::Value>,
+        ) -> Result<String> {
+            let call = self.calls.fetch_add(1, Ordering::SeqCst);
+            let now = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_in_flight.fetch_max(now, Ordering::SeqCst);
+            let _guard = InFlight(&self.in_flight);
+            let delay = if call < self.slow_calls {
+                self.delay * 10
+            } else {
+                self.delay
+            };
+            sleep(delay).await;
+
+            Ok(serde_json::json!({
+                "category": "20-29 Documents/21 Reports and Documents",
+                "confidence": 0.9,
+                "reasoning": "A report",
+                "alternatives": [],
+                "tags": []
+            })
+            .to_string())
+        }
+    }
+
+    /// The service and the highest number of requests seen in flight.
+    fn service(delay_ms: u64, slow_calls: usize) -> (Arc<RwLock<OllamaService>>, Arc<AtomicUsize>) {
+        let max_in_flight = Arc::new(AtomicUsize::new(0));
+        let provider = SlowProvider {
+            config: AiBackendSettings::default().ollama,
+            delay: Duration::from_millis(delay_ms),
+            slow_calls,
+            calls: AtomicUsize::new(0),
+            in_flight: AtomicUsize::new(0),
+            max_in_flight: max_in_flight.clone(),
+        };
+        let service = OllamaService::from_provider(Box::new(provider), "model");
+        (Arc::new(RwLock::new(service)), max_in_flight)
+    }
+
+    fn requests(count: usize) -> Vec<AnalysisRequest> {
+        (0..count)
+            .map(|i| AnalysisRequest {
+                file_path: format!("/home/report{}.pdf", i),
+                file_name: format!("report{}.pdf", i),
+                file_extension: "pdf".to_string(),
+                file_size: 1024,
+                mime_type: None,
+                existing_structure: None,
+                organization_scheme: "JOHNNY_DECIMAL".to_string(),
+                content_snippet: None,
+                examples: vec![],
+            })
+            .collect()
+    }
+
+    async fn collect(mut handle: BatchHandle) -> Vec<BatchItem> {
+        let mut items = Vec::new();
+        while let Some(item) = handle.results.recv().await {
+            items.push(item);
+        }
+        items
+    }
+
+    #[tokio::test]
+    async fn test_batch_limits_concurrency_and_retries_timeouts() {
+        let (service, max_in_flight) = service(20, 1);
+        let options = BatchOptions {
+            concurrency: 3,
+            file_timeout: Duration::from_millis(100),
+            initial_backoff: Duration::from_millis(10),
+            ..BatchOptions::default()
+        };
+
+        let mut items = collect(analyze_batch(service, requests(10), options)).await;
+        items.sort_by_key(|item| item.index);
+        assert_eq!(items.len(), 10);
+        assert!(items.iter().all(|item| item.source == BatchSource::Ai));
+        assert_eq!(items.iter().filter(|item| item.attempts == 2).count(), 1);
+        assert_eq!(max_in_flight.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_batch_uses_the_analysis_cache() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let db = DatabaseManager::new(temp_dir.path().join("test.db").to_str().unwrap()).unwrap();
+        let requests = requests(3);
+        let options = BatchOptions {
+            cache: Some(BatchCache {
+                db,
+                file_hashes: requests
+                    .iter()
+                    .take(2)
+                    .map(|r| (r.file_path.clone(), format!("hash-{}", r.file_name)))
+                    .collect(),
+                structure_revision: None,
+            }),
+            ..BatchOptions::default()
+        };
+        let (service, _) = service(5, 0);
+
+        let first = collect(analyze_batch(
+            service.clone(),
+            requests.clone(),
+            options.clone(),
+        ))
+        .await;
+        assert!(first.iter().all(|item| item.source == BatchSource::Ai));
+
+        let mut again = collect(analyze_batch(service, requests, options)).await;
+        again.sort_by_key(|item| item.index);
+        let sources: Vec<_> = again.iter().map(|item| item.source).collect();
+        assert_eq!(
+            sources,
+            vec![BatchSource::Cached, BatchSource::Cached, BatchSource::Ai]
+        );
+        assert_eq!(
+            again[0].result.as_ref().unwrap().suggested_category,
+            "20-29 Documents/21 Reports and Documents"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_batch_deadline_and_cancel() {
+        let options = BatchOptions {
+            concurrency: 2,
+            deadline: Some(Duration::from_millis(120)),
+            ..BatchOptions::default()
+        };
+        let items = collect(analyze_batch(service(50, 0).0, requests(8), options)).await;
+        assert_eq!(items.len(), 8);
+        let by_rules: Vec<_> = items
+            .iter()
+            .filter(|item| item.source == BatchSource::RuleBased)
+            .collect();
+        assert!(!by_rules.is_empty() && by_rules.len() < 8);
+        assert!(by_rules.iter().all(|item| item.result.is_some()));
+
+        let mut handle = analyze_batch(service(50, 0).0, requests(8), BatchOptions::default());
+        let first = handle.results.recv().await.unwrap();
+        assert_eq!(first.source, BatchSource::Ai);
+        handle.canceller().cancel();
+        assert!(collect(handle).await.len() < 7);
+    }
+}
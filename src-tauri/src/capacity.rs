@@ -0,0 +1,555 @@
+use crate::clustering::MAX_ITEMS_PER_CATEGORY;
+use crate::error::{AppError, Result};
+use crate::jd_id::FolderLevel;
+use crate::johnny_decimal::{
+    free_category_number, place_in_overflow_area, push_category, JDCategory, JDItem, JDStructure,
+};
+use crate::organization_plan::{relocate_files, OrganizationPlan};
+use crate::taxonomy::category_for_extension;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+
+/// Category numbers x0-x9.
+pub const MAX_CATEGORIES_PER_AREA: usize = 10;
+/// Share of the available IDs above which an area or category is reported.
+pub const PRESSURE_RATIO: f64 = 0.8;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CapacityUsage {
+    pub level: FolderLevel,
+    pub id: String,
+    pub name: String,
+    pub used: usize,
+    pub capacity: usize,
+}
+
+impl CapacityUsage {
+    pub fn ratio(&self) -> f64 {
+        self.used as f64 / self.capacity as f64
+    }
+
+    pub fn is_over_capacity(&self) -> bool {
+        self.used > self.capacity
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SplitStrategy {
+    ByYear,
+    ByLanguage,
+    ByFileType,
+    OverflowArea,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProposedCategory {
+    pub number: u8,
+    pub name: String,
+    pub item_count: usize,
+}
+
+/// A suggested split together with the structure it produces and the plan
+/// that moves the files there.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SplitProposal {
+    pub source_id: String,
+    pub strategy: SplitStrategy,
+    pub categories: Vec<ProposedCategory>,
+    pub structure: JDStructure,
+    pub plan: OrganizationPlan,
+}
+
+/// What is running out of IDs in a structure and how to make room.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CapacityReport {
+    pub pressure: Vec<CapacityUsage>,
+    pub over_capacity: bool, // Something already holds more than its IDs allow
+    pub proposals: Vec<SplitProposal>,
+}
+
+pub fn capacity_report(structure: &JDStructure) -> CapacityReport {
+    let pressure = capacity_pressure(structure);
+    CapacityReport {
+        over_capacity: pressure.iter().any(CapacityUsage::is_over_capacity),
+        proposals: propose_splits(structure),
+        pressure,
+    }
+}
+
+/// Areas and categories that use at least `PRESSURE_RATIO` of their IDs,
+/// fullest first.
+pub fn capacity_pressure(structure: &JDStructure) -> Vec<CapacityUsage> {
+    let mut usages = Vec::new();
+
+    for area in &structure.areas {
+        usages.push(CapacityUsage {
+            level: FolderLevel::Area,
+            id: format!("{}-{}", area.number, area.number + 9),
+            name: area.label().to_string(),
+            used: area.categories.len(),
+            capacity: MAX_CATEGORIES_PER_AREA,
+        });
+        for category in &area.categories {
+            usages.push(CapacityUsage {
+                level: FolderLevel::Category,
+                id: format!("{:02}", category.number),
+                name: category.name.clone(),
+                used: category.items.len(),
+                capacity: MAX_ITEMS_PER_CATEGORY,
+            });
+        }
+    }
+
+    usages.retain(|u| u.ratio() >= PRESSURE_RATIO);
+    usages.sort_by(|a, b| {
+        b.ratio()
+            .partial_cmp(&a.ratio())
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    usages
+}
+
+/// One proposal per area or category under pressure. Categories are split
+/// by language for source code, otherwise by year or file type; full areas
+/// move their upper half into an overflow area.
+pub fn propose_splits(structure: &JDStructure) -> Vec<SplitProposal> {
+    let mut proposals = Vec::new();
+
+    for usage in capacity_pressure(structure) {
+        let proposal = match usage.level {
+            FolderLevel::Area => {
+                let area_number = usage.id[..2].parse().unwrap_or_default();
+                overflow_area(structure, area_number)
+            }
+            _ => {
+                let number = usage.id.parse().unwrap_or_default();
+                [
+                    SplitStrategy::ByLanguage,
+                    SplitStrategy::ByYear,
+                    SplitStrategy::ByFileType,
+                ]
+                .into_iter()
+                .find_map(|strategy| split_category(structure, number, strategy).ok())
+                .ok_or_else(|| AppError::Organization("no way to split".to_string()))
+            }
+        };
+
+        match proposal {
+            Ok(proposal) => proposals.push(proposal),
+            Err(e) => log::info!("No split for {} {}: {}", usage.id, usage.name, e),
+        }
+    }
+
+    proposals
+}
+
+/// Splits a category into one category per group of `strategy`. Items stay
+/// whole and follow the group most of their files belong to; the largest
+/// group keeps the original number.
+pub fn split_category(
+    structure: &JDStructure,
+    category_number: u8,
+    strategy: SplitStrategy,
+) -> Result<SplitProposal> {
+    let (area_number, source) = structure
+        .areas
+        .iter()
+        .find_map(|a| {
+            a.categories
+                .iter()
+                .find(|c| c.number == category_number)
+                .map(|c| (a.number, c.clone()))
+        })
+        .ok_or_else(|| {
+            AppError::InvalidInput(format!("No category {:02} in structure", category_number))
+        })?;
+
+    if strategy == SplitStrategy::ByLanguage && !is_source_code(&source) {
+        return Err(AppError::Organization(format!(
+            "Category {:02} does not hold source code",
+            category_number
+        )));
+    }
+    let key = |item: &JDItem| match strategy {
+        SplitStrategy::ByYear => majority(item.files.iter().map(|f| file_year(f))),
+        SplitStrategy::ByLanguage => majority(item.files.iter().map(|f| language(f))),
+        SplitStrategy::ByFileType => majority(item.files.iter().map(|f| extension_label(f))),
+        SplitStrategy::OverflowArea => None,
+    };
+
+    let mut groups: BTreeMap<String, Vec<JDItem>> = BTreeMap::new();
+    for item in &source.items {
+        let group = key(item).unwrap_or_else(|| "Other".to_string());
+        groups.entry(group).or_default().push(item.clone());
+    }
+    if groups.len() < 2 {
+        return Err(AppError::Organization(format!(
+            "Category {:02} cannot be split by {:?}",
+            category_number, strategy
+        )));
+    }
+
+    let mut groups: Vec<(String, Vec<JDItem>)> = groups.into_iter().collect();
+    groups.sort_by_key(|(_, items)| std::cmp::Reverse(items.len()));
+
+    let mut proposed = structure.clone();
+    let mut used_areas: HashSet<u8> = proposed.areas.iter().map(|a| a.number).collect();
+    let mut used_categories: HashSet<u8> = proposed
+        .areas
+        .iter()
+        .flat_map(|a| a.categories.iter().map(|c| c.number))
+        .collect();
+    let mut overflow_areas = HashMap::new();
+    let mut categories = Vec::new();
+
+    for (index, (group, items)) in groups.into_iter().enumerate() {
+        let name = format!("{} {}", source.name, group);
+        let number = if index == 0 {
+            category_number
+        } else {
+            match free_category_number(area_number, &used_categories) {
+                Some(number) => {
+                    used_categories.insert(number);
+                    number
+                }
+                None => place_in_overflow_area(
+                    &mut proposed,
+                    area_number,
+                    &mut overflow_areas,
+                    &mut used_areas,
+                    &mut used_categories,
+                )?,
+            }
+        };
+
+        categories.push(ProposedCategory {
+            number,
+            name: name.clone(),
+            item_count: items.len(),
+        });
+        let category = JDCategory {
+            number,
+            name,
+            description: source.description.clone(),
+            items: renumber(number, items),
+        };
+
+        if index == 0 {
+            if let Some(existing) = proposed
+                .areas
+                .iter_mut()
+                .flat_map(|a| &mut a.categories)
+                .find(|c| c.number == category_number)
+            {
+                *existing = category;
+            }
+        } else {
+            push_category(&mut proposed, number, category);
+        }
+    }
+
+    Ok(finish(
+        structure,
+        proposed,
+        category_number.to_string(),
+        strategy,
+        categories,
+    ))
+}
+
+/// Moves the upper half of an area's categories into a new overflow area.
+pub fn overflow_area(structure: &JDStructure, area_number: u8) -> Result<SplitProposal> {
+    let mut proposed = structure.clone();
+    let area = proposed
+        .areas
+        .iter_mut()
+        .find(|a| a.number == area_number)
+        .ok_or_else(|| AppError::InvalidInput(format!("No area {} in structure", area_number)))?;
+
+    area.categories.sort_by_key(|c| c.number);
+    let keep = area.categories.len() / 2;
+    if keep == 0 {
+        return Err(AppError::Organization(format!(
+            "Area {} has too few categories to split",
+            area_number
+        )));
+    }
+    let moved = area.categories.split_off(keep);
+
+    let mut used_areas: HashSet<u8> = proposed.areas.iter().map(|a| a.number).collect();
+    let mut used_categories: HashSet<u8> = proposed
+        .areas
+        .iter()
+        .flat_map(|a| a.categories.iter().map(|c| c.number))
+        .collect();
+    let mut overflow_areas = HashMap::new();
+    let mut categories = Vec::new();
+
+    for category in moved {
+        let number = place_in_overflow_area(
+            &mut proposed,
+            area_number,
+            &mut overflow_areas,
+            &mut used_areas,
+            &mut used_categories,
+        )?;
+        categories.push(ProposedCategory {
+            number,
+            name: category.name.clone(),
+            item_count: category.items.len(),
+        });
+        push_category(
+            &mut proposed,
+            number,
+            JDCategory {
+                number,
+                items: renumber(number, category.items),
+                ..category
+            },
+        );
+    }
+
+    Ok(finish(
+        structure,
+        proposed,
+        format!("{}-{}", area_number, area_number + 9),
+        SplitStrategy::OverflowArea,
+        categories,
+    ))
+}
+
+fn finish(
+    original: &JDStructure,
+    mut proposed: JDStructure,
+    source_id: String,
+    strategy: SplitStrategy,
+    categories: Vec<ProposedCategory>,
+) -> SplitProposal {
+    proposed.areas.sort_by_key(|a| a.number);
+    for area in &mut proposed.areas {
+        area.categories.sort_by_key(|c| c.number);
+    }
+    proposed.modified_at = chrono::Utc::now();
+
+    let plan = OrganizationPlan::between(original, &proposed);
+    relocate_files(&mut proposed);
+
+    SplitProposal {
+        source_id,
+        strategy,
+        categories,
+        structure: proposed,
+        plan,
+    }
+}
+
+fn renumber(category_number: u8, items: Vec<JDItem>) -> Vec<JDItem> {
+    items
+        .into_iter()
+        .enumerate()
+        .map(|(i, item)| JDItem {
+            number: format!("{}.{:02}", category_number, i + 1),
+            ..item
+        })
+        .collect()
+}
+
+fn majority(keys: impl Iterator<Item = String>) -> Option<String> {
+    let mut counts: BTreeMap<String, usize> = BTreeMap::new();
+    for key in keys {
+        *counts.entry(key).or_default() += 1;
+    }
+    counts
+        .into_iter()
+        .max_by_key(|(_, count)| *count)
+        .map(|(key, _)| key)
+}
+
+fn is_source_code(category: &JDCategory) -> bool {
+    let files: Vec<&String> = category.items.iter().flat_map(|i| &i.files).collect();
+    let code = files
+        .iter()
+        .filter(|f| category_for_extension(&extension(f)).map(|c| c.name) == Some("Source Code"))
+        .count();
+    !files.is_empty() && code * 2 > files.len()
+}
+
+/// Year the file was last modified, or a year in its path when it is not on disk.
+fn file_year(file: &str) -> String {
+    if let Ok(modified) = std::fs::metadata(file).and_then(|m| m.modified()) {
+        let modified: chrono::DateTime<chrono::Utc> = modified.into();
+        return modified.format("%Y").to_string();
+    }
+
+    file.as_bytes()
+        .windows(4)
+        .enumerate()
+        .rev()
+        .find(|(i, w)| {
+            w.iter().all(u8::is_ascii_digit)
+                && (w.starts_with(b"19") || w.starts_with(b"20"))
+                && !file.as_bytes().get(i + 4).is_some_and(u8::is_ascii_digit)
+                && !(*i > 0 && file.as_bytes()[i - 1].is_ascii_digit())
+        })
+        .map(|(_, w)| String::from_utf8_lossy(w).to_string())
+        .unwrap_or_else(|| "Undated".to_string())
+}
+
+fn language(file: &str) -> String {
+    match extension(file).as_str() {
+        "rs" => "Rust",
+        "py" => "Python",
+        "js" => "JavaScript",
+        "ts" => "TypeScript",
+        "java" => "Java",
+        "c" | "h" => "C",
+        "cpp" | "hpp" | "cc" => "C++",
+        "go" => "Go",
+        "rb" => "Ruby",
+        _ => "Other",
+    }
+    .to_string()
+}
+
+fn extension_label(file: &str) -> String {
+    match extension(file) {
+        ext if ext.is_empty() => "Other".to_string(),
+        ext => ext.to_uppercase(),
+    }
+}
+
+fn extension(file: &str) -> String {
+    Path::new(file)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::johnny_decimal::JohnnyDecimalEngine;
+
+    fn structure_with(category: &str, files: impl Fn(usize) -> String) -> JDStructure {
+        let engine = JohnnyDecimalEngine::new().unwrap();
+        let mut structure = engine.build_structure(&[], "/home");
+        structure.areas.push(crate::johnny_decimal::JDArea {
+            number: 30,
+            name: "30-39 Media".to_string(),
+            description: None,
+            categories: vec![JDCategory {
+                number: 31,
+                name: category.to_string(),
+                description: None,
+                items: (0..85)
+                    .map(|i| JDItem {
+                        number: format!("31.{:02}", i + 1),
+                        name: format!("Item {}", i),
+                        description: None,
+                        files: vec![files(i)],
+                    })
+                    .collect(),
+            }],
+        });
+        structure
+    }
+
+    #[tokio::test]
+    async fn test_split_by_year() {
+        let structure = structure_with("Images", |i| {
+            format!("/home/Photos {}/IMG_{:04}.jpg", 2021 + i % 2, i)
+        });
+
+        let report = capacity_report(&structure);
+        assert_eq!(report.pressure[0].id, "31");
+        assert_eq!(report.pressure[0].used, 85);
+        assert!(!report.over_capacity);
+
+        let proposals = &report.proposals;
+        assert_eq!(proposals.len(), 1);
+        let proposal = &proposals[0];
+        assert_eq!(proposal.strategy, SplitStrategy::ByYear);
+        assert_eq!(
+            proposal
+                .categories
+                .iter()
+                .map(|c| (c.number, c.name.as_str(), c.item_count))
+                .collect::<Vec<_>>(),
+            vec![(31, "Images 2021", 43), (32, "Images 2022", 42)]
+        );
+        assert!(!proposal.plan.is_empty());
+
+        let engine = JohnnyDecimalEngine::new().unwrap();
+        let result = engine
+            .validate_structure(&proposal.structure)
+            .await
+            .unwrap();
+        assert!(result.is_valid && result.warnings.is_empty());
+    }
+
+    #[test]
+    fn test_split_by_language_and_overflow() {
+        let extensions = ["rs", "py", "ts"];
+        let structure = structure_with("Source Code", |i| {
+            format!("/home/src/file{}.{}", i, extensions[i % 3])
+        });
+
+        let proposal = split_category(&structure, 31, SplitStrategy::ByLanguage).unwrap();
+        let names: Vec<_> = proposal
+            .categories
+            .iter()
+            .map(|c| c.name.as_str())
+            .collect();
+        assert_eq!(
+            names,
+            vec![
+                "Source Code Rust",
+                "Source Code Python",
+                "Source Code TypeScript"
+            ]
+        );
+        assert!(split_category(&structure, 31, SplitStrategy::ByYear).is_err());
+
+        let mut full = proposal.structure.clone();
+        let media = full.areas.iter_mut().find(|a| a.number == 30).unwrap();
+        media.categories = (31..=39)
+            .map(|number| JDCategory {
+                number,
+                name: format!("Kind {}", number),
+                description: None,
+                items: Vec::new(),
+            })
+            .collect();
+        let proposal = overflow_area(&full, 30).unwrap();
+        assert_eq!(proposal.strategy, SplitStrategy::OverflowArea);
+        assert_eq!(proposal.categories.len(), 5);
+        let overflow = proposal.categories[0].number / 10 * 10;
+        let area = proposal
+            .structure
+            .areas
+            .iter()
+            .find(|a| a.number == overflow);
+        assert!(area.unwrap().name.ends_with("Media (continued)"));
+    }
+
+    #[test]
+    fn test_report_flags_overfull_category() {
+        let mut structure = structure_with("Images", |i| format!("/home/IMG_{:04}.jpg", i));
+        let items = &mut structure.areas.last_mut().unwrap().categories[0].items;
+        for i in items.len()..=MAX_ITEMS_PER_CATEGORY {
+            items.push(JDItem {
+                number: format!("31.{}", i + 1),
+                name: format!("Item {}", i),
+                description: None,
+                files: vec![format!("/home/IMG_{:04}.png", i)],
+            });
+        }
+
+        let report = capacity_report(&structure);
+        assert!(report.over_capacity);
+        assert_eq!(report.pressure[0].used, MAX_ITEMS_PER_CATEGORY + 1);
+        assert!(report.pressure[0].ratio() > 1.0);
+    }
+}
@@ -0,0 +1,413 @@
+use crate::jd_id::{JDId, JDNumber};
+use crate::johnny_decimal::{
+    CategoryAssignment, JDStructure, JohnnyDecimalEngine, EXTENSION_MATCH_CONFIDENCE,
+};
+use crate::semantic_index::SemanticMatch;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Strength of user-made signals, which come without a confidence of their
+/// own. Like every signal they are scaled by how well their target matched.
+const RULE_STRENGTH: f64 = 0.9;
+const CORRECTION_STRENGTH: f64 = 0.95;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SignalSource {
+    ExtensionMap,
+    Fallback,
+    Rule,
+    Ai,
+    Semantic,
+    History,
+    UserCorrection,
+}
+
+/// One piece of evidence for placing a file under `id`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DecisionSignal {
+    pub source: SignalSource,
+    pub id: JDId,
+    pub strength: f64, // 0.0-1.0
+    pub detail: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RankedCandidate {
+    pub id: JDId,
+    pub assignment: CategoryAssignment,
+    pub score: f64,
+    pub sources: Vec<SignalSource>,
+}
+
+/// Candidates best first, with every signal that fired.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Categorization {
+    pub candidates: Vec<RankedCandidate>,
+    pub trace: Vec<DecisionSignal>,
+}
+
+#[allow(dead_code)]
+impl Categorization {
+    pub fn best(&self) -> Option<&RankedCandidate> {
+        self.candidates.first()
+    }
+
+    pub fn runner_up(&self) -> Option<&RankedCandidate> {
+        self.candidates.get(1)
+    }
+
+    /// The signals behind one candidate, for "why here?".
+    pub fn explain(&self, id: &JDId) -> Vec<&DecisionSignal> {
+        self.trace.iter().filter(|s| &s.id == id).collect()
+    }
+}
+
+/// Sends files whose name matches `pattern` to `target`. Patterns are
+/// `*.ext` or a case-insensitive part of the file name.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlacementRule {
+    pub pattern: String,
+    pub target: JDId,
+}
+
+impl PlacementRule {
+    pub fn matches(&self, filename: &str) -> bool {
+        let filename = filename.to_lowercase();
+        let pattern = self.pattern.to_lowercase();
+        match pattern.strip_prefix("*.") {
+            Some(extension) => filename.ends_with(&format!(".{}", extension)),
+            None => !pattern.is_empty() && filename.contains(&pattern),
+        }
+    }
+}
+
+/// Signals known about a file besides its extension.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CategorizationContext {
+    pub rules: Vec<PlacementRule>,
+    pub ai_suggestion: Option<CategoryAssignment>,
+    #[serde(default)]
+    pub semantic: Vec<SemanticMatch>, // Nearest structure entries by embedding
+    pub history: Vec<JDId>,       // Where earlier files of the same kind went
+    pub correction: Option<JDId>, // Where the user moved this file before
+}
+
+/// Scores every ID any signal points at. Signals for the same ID combine
+/// as independent evidence: `1 - (1 - a)(1 - b)...`. Scores order the
+/// candidates; they are not calibrated probabilities.
+pub fn rank_candidates(
+    engine: &JohnnyDecimalEngine,
+    file_info: &serde_json::Value,
+    structure: &JDStructure,
+    context: &CategorizationContext,
+) -> Categorization {
+    let filename = file_info["name"]
+        .as_str()
+        .or_else(|| {
+            file_info["path"]
+                .as_str()
+                .and_then(|p| Path::new(p).file_name()?.to_str())
+        })
+        .unwrap_or_default();
+    let mut trace = Vec::new();
+
+    let by_extension = engine.assign_file(file_info, structure);
+    if let Ok(id) = by_extension.item_number.parse::<JDId>() {
+        let source = if by_extension.confidence < EXTENSION_MATCH_CONFIDENCE {
+            SignalSource::Fallback
+        } else {
+            SignalSource::ExtensionMap
+        };
+        trace.push(DecisionSignal {
+            source,
+            id,
+            strength: by_extension.confidence,
+            detail: by_extension.reasoning.clone(),
+        });
+    }
+
+    for rule in context.rules.iter().filter(|r| r.matches(filename)) {
+        if let Some((id, quality)) = resolve(structure, &rule.target) {
+            trace.push(DecisionSignal {
+                source: SignalSource::Rule,
+                id,
+                strength: RULE_STRENGTH * quality,
+                detail: format!("Rule '{}' sends it to {}", rule.pattern, rule.target),
+            });
+        }
+    }
+
+    if let Some(ai) = &context.ai_suggestion {
+        if let Some((id, quality)) = ai
+            .item_number
+            .parse()
+            .ok()
+            .and_then(|id| resolve(structure, &id))
+        {
+            trace.push(DecisionSignal {
+                source: SignalSource::Ai,
+                id,
+                strength: (ai.confidence * quality).clamp(0.0, 1.0),
+                detail: ai.reasoning.clone(),
+            });
+        }
+    }
+
+    for neighbour in &context.semantic {
+        if let Some((id, quality)) = neighbour
+            .key
+            .parse()
+            .ok()
+            .and_then(|id| resolve(structure, &id))
+        {
+            trace.push(DecisionSignal {
+                source: SignalSource::Semantic,
+                id,
+                strength: (neighbour.score * quality).clamp(0.0, 1.0),
+                detail: format!(
+                    "Similar in meaning to {} ({:.2})",
+                    neighbour.key, neighbour.score
+                ),
+            });
+        }
+    }
+
+    // Files filed in IDs that no longer exist still count towards the total
+    let mut counts: HashMap<JDId, f64> = HashMap::new();
+    for (id, quality) in context
+        .history
+        .iter()
+        .filter_map(|id| resolve(structure, id))
+    {
+        *counts.entry(id).or_default() += quality;
+    }
+    let total = context.history.len();
+    for (id, weight) in counts {
+        trace.push(DecisionSignal {
+            source: SignalSource::History,
+            detail: format!(
+                "{:.0} of {} similar files were filed in {}",
+                weight, total, id
+            ),
+            id,
+            strength: weight / total as f64,
+        });
+    }
+
+    if let Some((id, quality)) = context
+        .correction
+        .as_ref()
+        .and_then(|id| resolve(structure, id))
+    {
+        trace.push(DecisionSignal {
+            source: SignalSource::UserCorrection,
+            detail: format!("You moved this file to {} before", id),
+            id,
+            strength: CORRECTION_STRENGTH * quality,
+        });
+    }
+
+    let mut candidates: Vec<RankedCandidate> = Vec::new();
+    for signal in &trace {
+        match candidates.iter_mut().find(|c| c.id == signal.id) {
+            Some(candidate) => {
+                candidate.score = 1.0 - (1.0 - candidate.score) * (1.0 - signal.strength);
+                if !candidate.sources.contains(&signal.source) {
+                    candidate.sources.push(signal.source);
+                }
+            }
+            None => candidates.push(RankedCandidate {
+                id: signal.id.clone(),
+                assignment: CategoryAssignment {
+                    area_number: signal.id.area_number(),
+                    category_number: signal.id.category_number().unwrap_or_default(),
+                    item_number: signal.id.local_id(),
+                    confidence: 0.0,
+                    reasoning: String::new(),
+                },
+                score: signal.strength,
+                sources: vec![signal.source],
+            }),
+        }
+    }
+
+    for candidate in &mut candidates {
+        candidate.assignment.confidence = candidate.score;
+        candidate.assignment.reasoning = trace
+            .iter()
+            .filter(|s| s.id == candidate.id)
+            .map(|s| s.detail.as_str())
+            .collect::<Vec<_>>()
+            .join("; ");
+    }
+    candidates.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    Categorization { candidates, trace }
+}
+
+/// The item a signal points at, if its category is in the structure, and
+/// how precisely it matched: 1.0 for an existing item. Category IDs and items
+/// the category does not have resolve to the category's first item, with
+/// the share of the category that item stands for.
+fn resolve(structure: &JDStructure, id: &JDId) -> Option<(JDId, f64)> {
+    let category_number = id.category_number()?;
+    let category = structure
+        .areas
+        .iter()
+        .flat_map(|a| &a.categories)
+        .find(|c| c.number == category_number)?;
+
+    if let JDNumber::Item { .. } = id.number {
+        let local = id.local_id();
+        if category.items.iter().any(|i| i.number == local) {
+            return Some((
+                JDId {
+                    system: None,
+                    number: id.number,
+                },
+                1.0,
+            ));
+        }
+    }
+
+    let quality = 1.0 / category.items.len().max(1) as f64;
+    match category.items.first() {
+        Some(item) => Some((item.number.parse().ok()?, quality)),
+        None => Some((JDId::item(category_number, 1).ok()?, quality)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn id(value: &str) -> JDId {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn test_rank_candidates() {
+        let engine = JohnnyDecimalEngine::new().unwrap();
+        let files = vec![
+            serde_json::json!({"path": "/home/invoice-2024.pdf", "extension": "pdf"}),
+            serde_json::json!({"path": "/home/photo.jpg", "extension": "jpg"}),
+        ];
+        let structure = engine.build_structure(&files, "/home");
+        let pdf = &files[0];
+
+        let plain = rank_candidates(&engine, pdf, &structure, &Default::default());
+        assert_eq!(plain.candidates.len(), 1);
+        assert_eq!(plain.best().unwrap().score, 0.85);
+        assert_eq!(plain.trace[0].source, SignalSource::ExtensionMap);
+
+        let images = structure.areas[1].categories[0].number;
+        let context = CategorizationContext {
+            rules: vec![PlacementRule {
+                pattern: "invoice".to_string(),
+                target: JDId::category(images).unwrap(),
+            }],
+            history: vec![id("21.01"), id("21.01"), id("99.01")],
+            ..Default::default()
+        };
+        let ranked = rank_candidates(&engine, pdf, &structure, &context);
+        let best = ranked.best().unwrap();
+        assert_eq!(best.id, id("21.01"));
+        assert_eq!(
+            best.sources,
+            vec![SignalSource::ExtensionMap, SignalSource::History]
+        );
+        assert!(best.score > 0.85 && best.score < 1.0);
+        assert_eq!(best.assignment.confidence, best.score);
+
+        let runner_up = ranked.runner_up().unwrap();
+        assert_eq!(runner_up.sources, vec![SignalSource::Rule]);
+        assert_eq!(runner_up.assignment.category_number, images);
+        assert_eq!(ranked.explain(&runner_up.id).len(), 1);
+        assert_eq!(ranked.candidates.len(), 2); // 99.01 is not in the structure
+    }
+
+    #[test]
+    fn test_correction_outranks_extension() {
+        let engine = JohnnyDecimalEngine::new().unwrap();
+        let files = vec![
+            serde_json::json!({"path": "/home/scan.pdf", "extension": "pdf"}),
+            serde_json::json!({"path": "/home/scan.jpg", "extension": "jpg"}),
+        ];
+        let structure = engine.build_structure(&files, "/home");
+        let images = structure.areas[1].categories[0].items[0].number.clone();
+
+        let context = CategorizationContext {
+            correction: Some(id(&images)),
+            semantic: vec![SemanticMatch {
+                key: format!("{:02}", structure.areas[1].categories[0].number),
+                score: 0.6,
+            }],
+            ..Default::default()
+        };
+        let ranked = rank_candidates(&engine, &files[0], &structure, &context);
+        assert_eq!(ranked.best().unwrap().id, id(&images));
+        assert_eq!(
+            ranked.best().unwrap().sources,
+            vec![SignalSource::Semantic, SignalSource::UserCorrection]
+        );
+        assert_eq!(ranked.runner_up().unwrap().id, id("21.01"));
+    }
+
+    #[test]
+    fn test_unknown_items_fall_back_to_their_category() {
+        let engine = JohnnyDecimalEngine::new().unwrap();
+        let files = vec![serde_json::json!({"path": "/home/scan.jpg", "extension": "jpg"})];
+        let mut structure = engine.build_structure(&files, "/home");
+        let images = &mut structure.areas[0].categories[0];
+        let number = images.number;
+        let first = images.items[0].number.clone();
+        images.items.push(crate::johnny_decimal::JDItem {
+            number: format!("{}.02", number),
+            name: "Screenshots".to_string(),
+            description: None,
+            files: vec![],
+        });
+
+        let suggest = |item: &str| CategorizationContext {
+            ai_suggestion: Some(CategoryAssignment {
+                area_number: 30,
+                category_number: number,
+                item_number: item.to_string(),
+                confidence: 0.9,
+                reasoning: "Looks like a photo".to_string(),
+            }),
+            ..Default::default()
+        };
+        let strength = |ranked: &Categorization| {
+            ranked
+                .trace
+                .iter()
+                .find(|s| s.source == SignalSource::Ai)
+                .map(|s| (s.id.to_string(), s.strength))
+        };
+
+        // An existing item keeps the model's own confidence
+        let exact = rank_candidates(&engine, &files[0], &structure, &suggest(&first));
+        assert_eq!(strength(&exact), Some((first.clone(), 0.9)));
+
+        // A made-up item lands on the category's first item, weakened by the
+        // number of items it could have meant
+        let made_up = format!("{}.17", number);
+        let ranked = rank_candidates(&engine, &files[0], &structure, &suggest(&made_up));
+        assert_eq!(strength(&ranked), Some((first, 0.45)));
+        assert!(ranked
+            .candidates
+            .iter()
+            .all(|c| c.id.to_string() != made_up));
+
+        // A category the structure does not have gives no signal at all
+        let ranked = rank_candidates(&engine, &files[0], &structure, &suggest("88.01"));
+        assert_eq!(strength(&ranked), None);
+    }
+}
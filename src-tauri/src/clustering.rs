@@ -0,0 +1,303 @@
+use chrono::Datelike;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Johnny Decimal allows IDs 01-99 inside a category.
+pub const MAX_ITEMS_PER_CATEGORY: usize = 99;
+
+/// A group needs at least this many files before it earns its own item.
+const MIN_CLUSTER_SIZE: usize = 2;
+
+/// Files whose presence marks their folder as the root of a project.
+const PROJECT_MARKERS: &[&str] = &[
+    "Cargo.toml",
+    "package.json",
+    "pyproject.toml",
+    "setup.py",
+    "go.mod",
+    "pom.xml",
+    "build.gradle",
+    "CMakeLists.txt",
+    "Gemfile",
+    "composer.json",
+];
+
+/// The signal that pulled a set of files together, in priority order.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum ClusterSignal {
+    Project(String),
+    Stem(String),
+    Folder(String),
+    Date { year: i32, month: u32 },
+}
+
+#[derive(Debug, Clone)]
+pub struct FileCluster {
+    pub name: String,
+    pub description: String,
+    pub signal: Option<ClusterSignal>,
+    pub files: Vec<String>,
+}
+
+/// Splits the files of one category into meaningful JD items.
+pub struct FileClusterer {
+    root_path: PathBuf,
+    project_roots: HashSet<PathBuf>,
+}
+
+impl FileClusterer {
+    /// Builds a clusterer for a scan, detecting project roots across all
+    /// scanned files so that markers in one category affect the others.
+    pub fn new(root_path: &str, all_files: &[serde_json::Value]) -> Self {
+        let project_roots = all_files
+            .iter()
+            .filter_map(|file| file["path"].as_str())
+            .map(Path::new)
+            .filter(|path| {
+                path.file_name()
+                    .and_then(|n| n.to_str())
+                    .map(|n| PROJECT_MARKERS.contains(&n))
+                    .unwrap_or(false)
+            })
+            .filter_map(|path| path.parent().map(Path::to_path_buf))
+            .collect();
+
+        Self {
+            root_path: PathBuf::from(root_path),
+            project_roots,
+        }
+    }
+
+    pub fn cluster(&self, category_name: &str, files: &[serde_json::Value]) -> Vec<FileCluster> {
+        let candidates: Vec<(String, Vec<ClusterSignal>)> = files
+            .iter()
+            .filter_map(|file| {
+                let path = file["path"].as_str()?;
+                Some((path.to_string(), self.signals_for(path, file)))
+            })
+            .collect();
+
+        let mut signal_counts: HashMap<&ClusterSignal, usize> = HashMap::new();
+        for (_, signals) in &candidates {
+            for signal in signals {
+                *signal_counts.entry(signal).or_insert(0) += 1;
+            }
+        }
+
+        // Each file joins the strongest signal it shares with enough others
+        let mut grouped: BTreeMap<ClusterSignal, Vec<String>> = BTreeMap::new();
+        let mut general = Vec::new();
+        for (path, signals) in &candidates {
+            match signals
+                .iter()
+                .find(|s| signal_counts.get(s).copied().unwrap_or(0) >= MIN_CLUSTER_SIZE)
+            {
+                Some(signal) => grouped
+                    .entry(signal.clone())
+                    .or_default()
+                    .push(path.clone()),
+                None => general.push(path.clone()),
+            }
+        }
+
+        let mut clusters: Vec<(ClusterSignal, Vec<String>)> = grouped.into_iter().collect();
+        clusters.sort_by(|a, b| b.1.len().cmp(&a.1.len()).then_with(|| a.0.cmp(&b.0)));
+
+        // Keep one slot for the general item when clusters have to be folded
+        let reserved = usize::from(!general.is_empty() || clusters.len() > MAX_ITEMS_PER_CATEGORY);
+        if clusters.len() + reserved > MAX_ITEMS_PER_CATEGORY {
+            for (_, overflow) in clusters.split_off(MAX_ITEMS_PER_CATEGORY - 1) {
+                general.extend(overflow);
+            }
+        }
+
+        let mut used_names = HashSet::new();
+        let mut result: Vec<FileCluster> = clusters
+            .into_iter()
+            .map(|(signal, files)| {
+                let name = unique_name(cluster_name(&signal), &mut used_names);
+                FileCluster {
+                    name,
+                    description: cluster_description(&signal, category_name),
+                    signal: Some(signal),
+                    files,
+                }
+            })
+            .collect();
+
+        if !general.is_empty() {
+            result.push(FileCluster {
+                name: unique_name(format!("{} Files", category_name), &mut used_names),
+                description: format!("Collection of {} files", category_name.to_lowercase()),
+                signal: None,
+                files: general,
+            });
+        }
+
+        result
+    }
+
+    fn signals_for(&self, path: &str, file: &serde_json::Value) -> Vec<ClusterSignal> {
+        let path = Path::new(path);
+        let mut signals = Vec::new();
+
+        if let Some(project) = path
+            .ancestors()
+            .skip(1)
+            .find(|ancestor| self.project_roots.contains(*ancestor))
+            .and_then(|root| root.file_name())
+            .and_then(|name| name.to_str())
+        {
+            signals.push(ClusterSignal::Project(project.to_string()));
+        }
+
+        if let Some(stem) = path.file_stem().and_then(|s| s.to_str()).and_then(stem_key) {
+            signals.push(ClusterSignal::Stem(stem));
+        }
+
+        if let Some(parent) = path.parent() {
+            if parent != self.root_path {
+                if let Some(folder) = parent.file_name().and_then(|n| n.to_str()) {
+                    signals.push(ClusterSignal::Folder(folder.to_string()));
+                }
+            }
+        }
+
+        let modified = file["modified"]
+            .as_str()
+            .or_else(|| file["modified_at"].as_str())
+            .and_then(|s| chrono::DateTime::parse_from_rfc3339(s).ok());
+        if let Some(modified) = modified {
+            signals.push(ClusterSignal::Date {
+                year: modified.year(),
+                month: modified.month(),
+            });
+        }
+
+        signals
+    }
+}
+
+/// Reduces a file stem to its leading word, e.g. `Invoice_2023-04` -> `invoice`.
+fn stem_key(stem: &str) -> Option<String> {
+    let word: String = stem
+        .chars()
+        .take_while(|c| c.is_alphabetic())
+        .collect::<String>()
+        .to_lowercase();
+
+    if word.chars().count() >= 3 {
+        Some(word)
+    } else {
+        None
+    }
+}
+
+fn cluster_name(signal: &ClusterSignal) -> String {
+    match signal {
+        ClusterSignal::Project(project) => format!("{} Project", project),
+        ClusterSignal::Stem(stem) => {
+            let mut chars = stem.chars();
+            match chars.next() {
+                Some(first) => first.to_uppercase().chain(chars).collect(),
+                None => stem.clone(),
+            }
+        }
+        ClusterSignal::Folder(folder) => folder.clone(),
+        ClusterSignal::Date { year, month } => format!("{}-{:02}", year, month),
+    }
+}
+
+fn cluster_description(signal: &ClusterSignal, category_name: &str) -> String {
+    let category = category_name.to_lowercase();
+    match signal {
+        ClusterSignal::Project(project) => {
+            format!(
+                "{} files belonging to the {} project",
+                category_name, project
+            )
+        }
+        ClusterSignal::Stem(stem) => format!("{} files named like '{}*'", category_name, stem),
+        ClusterSignal::Folder(folder) => {
+            format!("{} files originally stored in '{}'", category_name, folder)
+        }
+        ClusterSignal::Date { year, month } => {
+            format!("{} last modified in {}-{:02}", category, year, month)
+        }
+    }
+}
+
+fn unique_name(name: String, used: &mut HashSet<String>) -> String {
+    let mut candidate = name.clone();
+    let mut suffix = 2;
+    while !used.insert(candidate.to_lowercase()) {
+        candidate = format!("{} ({})", name, suffix);
+        suffix += 1;
+    }
+    candidate
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(path: &str) -> serde_json::Value {
+        serde_json::json!({ "path": path, "extension": "pdf" })
+    }
+
+    #[test]
+    fn test_cluster_by_stem_and_folder() {
+        let files = vec![
+            file("/root/invoice_2023_01.pdf"),
+            file("/root/Invoice-2023-02.pdf"),
+            file("/root/taxes/w2.pdf"),
+            file("/root/taxes/1099.pdf"),
+            file("/root/lonely.pdf"),
+        ];
+        let clusterer = FileClusterer::new("/root", &files);
+        let clusters = clusterer.cluster("Reports and Documents", &files);
+
+        assert_eq!(clusters.len(), 3);
+        assert_eq!(clusters[0].name, "Invoice");
+        assert_eq!(clusters[0].files.len(), 2);
+        assert_eq!(clusters[1].name, "taxes");
+        assert_eq!(clusters[2].name, "Reports and Documents Files");
+        assert_eq!(clusters[2].files, vec!["/root/lonely.pdf".to_string()]);
+    }
+
+    #[test]
+    fn test_project_marker_takes_priority() {
+        let all = vec![
+            file("/root/app/package.json"),
+            file("/root/app/src/index.js"),
+            file("/root/app/src/util.js"),
+        ];
+        let clusterer = FileClusterer::new("/root", &all);
+        let clusters = clusterer.cluster("Source Code", &all[1..]);
+
+        assert_eq!(clusters.len(), 1);
+        assert_eq!(clusters[0].name, "app Project");
+        assert_eq!(
+            clusters[0].signal,
+            Some(ClusterSignal::Project("app".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_item_limit_is_respected() {
+        let files: Vec<_> = (0..150)
+            .flat_map(|i| {
+                vec![
+                    file(&format!("/root/folder{}/a.pdf", i)),
+                    file(&format!("/root/folder{}/b.pdf", i)),
+                ]
+            })
+            .collect();
+        let clusterer = FileClusterer::new("/root", &files);
+        let clusters = clusterer.cluster("Documents", &files);
+
+        assert_eq!(clusters.len(), MAX_ITEMS_PER_CATEGORY);
+        assert_eq!(clusters.iter().map(|c| c.files.len()).sum::<usize>(), 300);
+        assert!(clusters.last().unwrap().signal.is_none());
+    }
+}
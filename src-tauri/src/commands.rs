@@ -1,3 +1,59 @@
+use crate::ai_provider::OLLAMA;
+use crate::ai_service::{apply_corrections, AnalysisRequest, OllamaService};
+use crate::batch_analysis::{self, BatchCache, BatchCanceller, BatchItem, BatchOptions};
+use crate::capacity::{self, CapacityReport, SplitProposal, SplitStrategy};
+use crate::categorization::Categorization;
+use crate::content_extraction::{snippet_for_request, ExtractionLimits};
+use crate::corrections::{
+    attach_examples, context_from_corrections, Correction, MAX_CORRECTIONS_CONSIDERED,
+};
+use crate::database::{AnalysisCacheStats, CachePurge, DatabaseManager, StructureRevision};
+use crate::drift::{self, DriftReport};
+use crate::error::Result as AppResult;
+use crate::exchange::{self, ImportResult};
+use crate::file_operations::{FileOperations, FileScanner, PlanExecutionReport};
+use crate::jd_id::{JDId, JDLookup};
+use crate::jdex_export::{self, JdexFormat};
+use crate::johnny_decimal::{
+    CategoryAssignment, JDRepairResult, JDStructure, JDValidationResult, JohnnyDecimalEngine,
+};
+use crate::link_farm::{LinkFarm, LinkFarmReport};
+use crate::lint::{self, LintRuleStatus};
+use crate::model_manager::{same_model, ModelDetails, ModelManager};
+use crate::organization_plan::OrganizationPlan;
+use crate::organization_scheme::scheme_from_id;
+use crate::semantic_index::{SemanticIndex, SemanticMatch, DEFAULT_EMBEDDING_MODEL};
+use crate::structure_diff::StructureDiff;
+use crate::templates::StructureTemplate;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter, State};
+use tokio::sync::RwLock;
+use uuid::Uuid;
+
+/// Services shared by the commands, managed by Tauri.
+pub struct AppState {
+    pub db: DatabaseManager,
+    pub ai: Arc<RwLock<OllamaService>>,
+    pub batches: Arc<std::sync::Mutex<HashMap<String, BatchCanceller>>>, // Running, by batch id
+    pub undo: std::sync::Mutex<Vec<OrganizationPlan>>, // Reverts executed plans, newest last
+}
+
+impl AppState {
+    pub async fn new(db_path: &str) -> AppResult<Self> {
+        let db = DatabaseManager::new(db_path)?;
+        let settings = db.load_settings().await?;
+        let ai = OllamaService::new(&settings).await?;
+        Ok(Self {
+            db,
+            ai: Arc::new(RwLock::new(ai)),
+            batches: Arc::default(),
+            undo: std::sync::Mutex::new(Vec::new()),
+        })
+    }
+}
+
 /// A simple greeting command for testing Tauri backend communication
 #[tauri::command]
 pub fn greet(name: &str) -> String {
@@ -56,3 +112,702 @@ pub async fn test_ai_analysis(file_count: u32) -> Result<serde_json::Value, Stri
         "status": "completed"
     }))
 }
+
+/// Starts the configured AI backend, launching Ollama when needed
+#[tauri::command]
+pub async fn start_ai_service(state: State<'_, AppState>) -> Result<String, String> {
+    Ok(state.ai.write().await.start().await?)
+}
+
+/// Stops Ollama if this app started it
+#[tauri::command]
+pub async fn stop_ai_service(state: State<'_, AppState>) -> Result<(), String> {
+    Ok(state.ai.write().await.stop().await?)
+}
+
+#[tauri::command]
+pub async fn ai_service_status(state: State<'_, AppState>) -> Result<serde_json::Value, String> {
+    Ok(state.ai.read().await.get_status().await?)
+}
+
+/// Event carrying `{ model, status, fraction }` while a model downloads.
+pub const MODEL_PULL_EVENT: &str = "model-pull-progress";
+
+/// Downloads a model into Ollama, emitting `MODEL_PULL_EVENT` as it goes.
+/// Pulling the configured model the service is missing loads it.
+#[tauri::command]
+pub async fn pull_model(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    model: &str,
+) -> Result<(), String> {
+    model_manager(&state)
+        .await?
+        .pull(model, |progress| {
+            let payload = serde_json::json!({
+                "model": model,
+                "status": progress.status,
+                "fraction": progress.fraction(),
+            });
+            if let Err(e) = app.emit(MODEL_PULL_EVENT, payload) {
+                log::debug!("Could not report pull progress: {}", e);
+            }
+        })
+        .await?;
+
+    // A service started without its model can use it now
+    let mut ai = state.ai.write().await;
+    if ai
+        .missing_model()
+        .is_some_and(|missing| same_model(missing, model))
+    {
+        ai.load_installed_model(model).await?;
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn delete_model(state: State<'_, AppState>, model: &str) -> Result<(), String> {
+    Ok(model_manager(&state).await?.delete(model).await?)
+}
+
+#[tauri::command]
+pub async fn show_model(state: State<'_, AppState>, model: &str) -> Result<ModelDetails, String> {
+    Ok(model_manager(&state).await?.show(model).await?)
+}
+
+/// Makes an installed model the active one and remembers it in the settings
+#[tauri::command]
+pub async fn switch_model(state: State<'_, AppState>, model: &str) -> Result<(), String> {
+    Ok(state
+        .ai
+        .write()
+        .await
+        .switch_model(model, &state.db)
+        .await?)
+}
+
+/// Event carrying `{ batch_id, item }` for every file a batch finishes.
+pub const BATCH_ITEM_EVENT: &str = "batch-analysis-item";
+/// Event carrying `{ batch_id }` once a batch is done or cancelled.
+pub const BATCH_DONE_EVENT: &str = "batch-analysis-done";
+
+/// Starts analyzing files in parallel, falling back to the rules for files
+/// the AI cannot finish. Returns the batch id; each finished file is sent
+/// as `BATCH_ITEM_EVENT`, in completion order, after the stored corrections
+/// have been applied to it.
+#[tauri::command]
+pub async fn analyze_files(
+    app: AppHandle,
+    state: State<'_, AppState>,
+    paths: Vec<String>,
+    scheme: &str,
+    structure_id: Option<String>,
+    deadline_secs: Option<u64>,
+) -> Result<String, String> {
+    let structure = match structure_id {
+        Some(id) => Some(stored_structure(&state, &id).await?),
+        None => None,
+    };
+    let corrections = state
+        .db
+        .recent_corrections(
+            structure.as_ref().map(|s| s.id.as_str()),
+            MAX_CORRECTIONS_CONSIDERED,
+        )
+        .await?;
+    let structure_revision = match &structure {
+        Some(structure) => state.db.latest_revision(&structure.id).await?,
+        None => None,
+    };
+    let scanner = FileScanner::new()?;
+    let mut requests = Vec::with_capacity(paths.len());
+    let mut file_hashes = HashMap::new();
+    for path in &paths {
+        let metadata = scanner.get_file_metadata(path).await?;
+        let mut request = AnalysisRequest::from_metadata(&metadata, scheme, structure.as_ref())?;
+        attach_examples(&mut request, &corrections);
+        requests.push(request);
+        match scanner.compute_checksum(path).await {
+            Ok(hash) => {
+                file_hashes.insert(path.clone(), hash);
+            }
+            Err(e) => log::debug!("Not caching {}: {}", path, e),
+        }
+    }
+
+    let options = BatchOptions {
+        deadline: deadline_secs.map(std::time::Duration::from_secs),
+        cache: Some(BatchCache {
+            db: state.db.clone(),
+            file_hashes,
+            structure_revision,
+        }),
+        ..BatchOptions::default()
+    };
+    let mut handle = batch_analysis::analyze_batch(state.ai.clone(), requests.clone(), options);
+    let batch_id = Uuid::new_v4().to_string();
+    state
+        .batches
+        .lock()
+        .map_err(|e| e.to_string())?
+        .insert(batch_id.clone(), handle.canceller());
+
+    let batches = state.batches.clone();
+    let id = batch_id.clone();
+    tokio::spawn(async move {
+        while let Some(mut item) = handle.results.recv().await {
+            if let Some(result) = item.result.take() {
+                let request = &requests[item.index];
+                item.result = Some(
+                    match apply_corrections(request, result.clone(), &corrections) {
+                        Ok(corrected) => corrected,
+                        Err(e) => {
+                            log::warn!("Could not apply corrections to {}: {}", item.file_path, e);
+                            result
+                        }
+                    },
+                );
+            }
+            emit_batch_event(&app, BATCH_ITEM_EVENT, &id, Some(item));
+        }
+        if let Ok(mut batches) = batches.lock() {
+            batches.remove(&id);
+        }
+        emit_batch_event(&app, BATCH_DONE_EVENT, &id, None);
+    });
+    Ok(batch_id)
+}
+
+fn emit_batch_event(app: &AppHandle, event: &str, batch_id: &str, item: Option<BatchItem>) {
+    let mut payload = serde_json::json!({ "batch_id": batch_id });
+    if let Some(item) = item {
+        payload["item"] = serde_json::json!(item);
+    }
+    if let Err(e) = app.emit(event, payload) {
+        log::debug!("Could not report batch progress: {}", e);
+    }
+}
+
+/// Candidate IDs for a file in a saved structure, best first, with the
+/// signals behind each, so the UI can explain a placement and offer the
+/// runner-up. Rules and earlier moves come from the stored corrections.
+#[tauri::command]
+pub async fn categorize_file(
+    state: State<'_, AppState>,
+    path: &str,
+    structure_id: &str,
+) -> Result<Categorization, String> {
+    let structure = stored_structure(&state, structure_id).await?;
+    let metadata = FileScanner::new()?.get_file_metadata(path).await?;
+    let corrections = state
+        .db
+        .recent_corrections(Some(structure_id), MAX_CORRECTIONS_CONSIDERED)
+        .await?;
+    let context = context_from_corrections(&corrections, path);
+    let file_info = serde_json::json!({
+        "path": metadata.path,
+        "name": metadata.name,
+        "extension": metadata.file_type,
+        "size": metadata.size,
+        "mime_type": metadata.mime_type,
+    });
+
+    Ok(settings_engine(&state)
+        .await?
+        .categorize_file(file_info, &structure, &context)
+        .await?)
+}
+
+/// Records that the user filed `path` under `chosen` rather than where the
+/// organizer suggested, so later analyses learn from it.
+#[tauri::command]
+pub async fn record_correction(
+    state: State<'_, AppState>,
+    path: &str,
+    suggested: Option<CategoryAssignment>,
+    chosen: &str,
+    structure_id: Option<String>,
+) -> Result<Correction, String> {
+    let chosen: JDId = chosen.parse()?;
+    let chosen_label = match &structure_id {
+        Some(id) => {
+            let lookup = stored_structure(&state, id)
+                .await?
+                .lookup_id(&chosen)
+                .ok_or_else(|| format!("{} is not in structure {}", chosen, id))?;
+            format!("{} {}", chosen.local_id(), lookup.name)
+        }
+        None => chosen.to_string(),
+    };
+    let correction = Correction::new(
+        path,
+        suggested.as_ref(),
+        chosen,
+        &chosen_label,
+        structure_id.as_deref(),
+    );
+    state.db.save_correction(&correction).await?;
+    Ok(correction)
+}
+
+/// Stops a running batch analysis; files already done have been sent.
+#[tauri::command]
+pub fn cancel_batch_analysis(state: State<'_, AppState>, batch_id: &str) -> Result<(), String> {
+    if let Some(canceller) = state
+        .batches
+        .lock()
+        .map_err(|e| e.to_string())?
+        .remove(batch_id)
+    {
+        canceller.cancel();
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn analysis_cache_stats(
+    state: State<'_, AppState>,
+) -> Result<AnalysisCacheStats, String> {
+    Ok(state.db.analysis_cache_stats().await?)
+}
+
+/// Deletes cached AI results and returns how many were removed
+#[tauri::command]
+pub async fn purge_analysis_cache(
+    state: State<'_, AppState>,
+    scope: CachePurge,
+) -> Result<usize, String> {
+    Ok(state.db.purge_analysis_cache(scope).await?)
+}
+
+/// Compares a saved structure with the folders and files on disk
+#[tauri::command]
+pub async fn detect_structure_drift(
+    state: State<'_, AppState>,
+    structure_id: &str,
+) -> Result<DriftReport, String> {
+    let structure = stored_structure(&state, structure_id).await?;
+    Ok(drift::detect_drift(&structure).await?)
+}
+
+/// Adopts what is on disk into a saved structure, stored as a new revision
+#[tauri::command]
+pub async fn reconcile_structure_with_disk(
+    state: State<'_, AppState>,
+    structure_id: &str,
+) -> Result<JDStructure, String> {
+    let structure = stored_structure(&state, structure_id).await?;
+    let report = drift::detect_drift(&structure).await?;
+    let reconciled = drift::reconcile_structure_from_disk(&structure, &report);
+    state.db.save_structure(&reconciled).await?;
+    Ok(reconciled)
+}
+
+/// Plans the moves that put the disk back in line with a saved structure
+#[tauri::command]
+pub async fn plan_drift_reconciliation(
+    state: State<'_, AppState>,
+    structure_id: &str,
+) -> Result<OrganizationPlan, String> {
+    let structure = stored_structure(&state, structure_id).await?;
+    let report = drift::detect_drift(&structure).await?;
+    Ok(drift::plan_disk_reconciliation(&structure, &report))
+}
+
+/// Fixes numbering problems in a saved structure and stores the result as
+/// a new revision. Executing the returned plan moves the files to match.
+#[tauri::command]
+pub async fn repair_structure(
+    state: State<'_, AppState>,
+    structure_id: &str,
+) -> Result<JDRepairResult, String> {
+    let structure = stored_structure(&state, structure_id).await?;
+    let repair = settings_engine(&state)
+        .await?
+        .repair_structure(&structure)
+        .await?;
+    state.db.save_structure(&repair.structure).await?;
+    Ok(repair)
+}
+
+/// Runs a plan on disk. Its undo plan is kept for `undo_last_plan`.
+#[tauri::command]
+pub async fn execute_plan(
+    state: State<'_, AppState>,
+    plan: OrganizationPlan,
+) -> Result<PlanExecutionReport, String> {
+    let report = FileOperations::new()?.execute_plan(&plan).await?;
+    if !report.undo.is_empty() {
+        state
+            .undo
+            .lock()
+            .map_err(|e| e.to_string())?
+            .push(report.undo.clone());
+    }
+    Ok(report)
+}
+
+/// Reverts the most recently executed plan. `None` when there is nothing
+/// left to undo.
+#[tauri::command]
+pub async fn undo_last_plan(
+    state: State<'_, AppState>,
+) -> Result<Option<PlanExecutionReport>, String> {
+    let plan = match state.undo.lock().map_err(|e| e.to_string())?.pop() {
+        Some(plan) => plan,
+        None => return Ok(None),
+    };
+    match FileOperations::new()?.execute_plan(&plan).await {
+        Ok(report) => Ok(Some(report)),
+        Err(e) => {
+            // A failed plan is rolled back, so the undo can be tried again
+            state.undo.lock().map_err(|e| e.to_string())?.push(plan);
+            Err(e.into())
+        }
+    }
+}
+
+/// Checks the folders under a root against an organization scheme
+#[tauri::command]
+pub async fn validate_scheme_layout(
+    scheme: &str,
+    root_path: &str,
+) -> Result<JDValidationResult, String> {
+    Ok(scheme_from_id(scheme, None)?.validate_root(root_path)?)
+}
+
+/// Plans the moves that sort the files under a root by a scheme. Johnny
+/// Decimal places files into the saved structure when one is given.
+#[tauri::command]
+pub async fn plan_scheme_organization(
+    state: State<'_, AppState>,
+    scheme: &str,
+    root_path: &str,
+    structure_id: Option<String>,
+) -> Result<OrganizationPlan, String> {
+    let structure = match structure_id {
+        Some(id) => Some(stored_structure(&state, &id).await?),
+        None => None,
+    };
+    let scan = FileScanner::new()?.scan_directory(root_path).await?;
+    let files = scan
+        .files
+        .iter()
+        .map(serde_json::to_value)
+        .collect::<serde_json::Result<Vec<_>>>()
+        .map_err(|e| e.to_string())?;
+
+    Ok(scheme_from_id(scheme, structure)?.plan(root_path, &files)?)
+}
+
+/// Writes a JDex index of a saved structure. Returns the files written; a
+/// JSON export comes with its schema.
+#[tauri::command]
+pub async fn export_jdex(
+    state: State<'_, AppState>,
+    structure_id: &str,
+    format: JdexFormat,
+    destination: &str,
+) -> Result<Vec<String>, String> {
+    let structure = stored_structure(&state, structure_id).await?;
+    let written = jdex_export::write_jdex(&structure, format, Path::new(destination))?;
+    Ok(written
+        .iter()
+        .map(|p| p.to_string_lossy().to_string())
+        .collect())
+}
+
+/// Writes a saved structure to a `.jdstructure` file for sharing. Returns
+/// the path written, which gets the extension when it has none.
+#[tauri::command]
+pub async fn export_structure_file(
+    state: State<'_, AppState>,
+    structure_id: &str,
+    include_files: bool,
+    destination: &str,
+) -> Result<String, String> {
+    let structure = stored_structure(&state, structure_id).await?;
+    let data = exchange::export_structure(&structure, include_files)?;
+
+    let mut path = PathBuf::from(destination);
+    if path.extension().is_none() {
+        path.set_extension(exchange::EXCHANGE_FILE_EXTENSION);
+    }
+    std::fs::write(&path, data).map_err(|e| e.to_string())?;
+    Ok(path.to_string_lossy().to_string())
+}
+
+/// Imports a `.jdstructure` file as a new saved structure under `root_path`.
+/// The result's plan moves files to any renumbered folders.
+#[tauri::command]
+pub async fn import_structure_file(
+    state: State<'_, AppState>,
+    path: &str,
+    root_path: &str,
+) -> Result<ImportResult, String> {
+    let data = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let engine = settings_engine(&state).await?;
+    let imported = exchange::import_structure(&engine, &data, root_path).await?;
+    state.db.save_structure(&imported.structure).await?;
+    Ok(imported)
+}
+
+/// Merges a `.jdstructure` file into a saved structure
+#[tauri::command]
+pub async fn merge_structure_file(
+    state: State<'_, AppState>,
+    structure_id: &str,
+    path: &str,
+) -> Result<ImportResult, String> {
+    let local = stored_structure(&state, structure_id).await?;
+    let data = std::fs::read_to_string(path).map_err(|e| e.to_string())?;
+    let engine = settings_engine(&state).await?;
+    let merged = exchange::merge_structure(&engine, &local, &data).await?;
+    state.db.save_structure(&merged.structure).await?;
+    Ok(merged)
+}
+
+/// Built-in and saved user templates
+#[tauri::command]
+pub async fn list_structure_templates(
+    state: State<'_, AppState>,
+) -> Result<Vec<StructureTemplate>, String> {
+    Ok(state.db.list_templates().await?)
+}
+
+/// Saves the areas, categories and items of a structure as a user template
+#[tauri::command]
+pub async fn save_structure_as_template(
+    state: State<'_, AppState>,
+    structure_id: &str,
+    name: &str,
+) -> Result<StructureTemplate, String> {
+    let structure = stored_structure(&state, structure_id).await?;
+    let template = StructureTemplate::from_structure(
+        &Uuid::new_v4().to_string(),
+        name,
+        &format!("Saved from {}", structure.name),
+        &structure,
+    );
+    Ok(state.db.save_template(&template).await?)
+}
+
+/// Creates and saves a new structure under `root_path` from a template,
+/// named in `locale` or the configured language.
+#[tauri::command]
+pub async fn instantiate_template(
+    state: State<'_, AppState>,
+    template_id: &str,
+    root_path: &str,
+    locale: Option<String>,
+) -> Result<JDStructure, String> {
+    let template = state
+        .db
+        .load_template(template_id)
+        .await?
+        .ok_or_else(|| format!("Template {} not found", template_id))?;
+    let locale = match locale {
+        Some(locale) => locale,
+        None => state.db.load_settings().await?.locale,
+    };
+    let structure = template.instantiate_in(root_path, &locale);
+    state.db.save_structure(&structure).await?;
+    Ok(structure)
+}
+
+/// Deletes a user template. Built-in templates cannot be deleted.
+#[tauri::command]
+pub async fn delete_structure_template(
+    state: State<'_, AppState>,
+    template_id: &str,
+) -> Result<(), String> {
+    Ok(state.db.delete_template(template_id).await?)
+}
+
+/// Saved revisions of a structure, newest first
+#[tauri::command]
+pub async fn list_structure_revisions(
+    state: State<'_, AppState>,
+    structure_id: &str,
+) -> Result<Vec<StructureRevision>, String> {
+    Ok(state.db.list_revisions(structure_id).await?)
+}
+
+/// What changed between two revisions of a structure
+#[tauri::command]
+pub async fn diff_structure_revisions(
+    state: State<'_, AppState>,
+    structure_id: &str,
+    from: u32,
+    to: u32,
+) -> Result<StructureDiff, String> {
+    Ok(state.db.diff_revisions(structure_id, from, to).await?)
+}
+
+/// Makes an old revision current again, saved as a new revision
+#[tauri::command]
+pub async fn restore_structure_revision(
+    state: State<'_, AppState>,
+    structure_id: &str,
+    revision: u32,
+) -> Result<JDStructure, String> {
+    Ok(state.db.restore_revision(structure_id, revision).await?)
+}
+
+/// Resolves a Johnny Decimal ID such as `21.03`, or failing that the
+/// closest folder name, to its folder in a saved structure
+#[tauri::command]
+pub async fn lookup_jd_id(
+    state: State<'_, AppState>,
+    structure_id: &str,
+    id: &str,
+) -> Result<Option<JDLookup>, String> {
+    let structure = stored_structure(&state, structure_id).await?;
+    Ok(structure.lookup(id))
+}
+
+/// Areas and categories running out of IDs, with split proposals
+#[tauri::command]
+pub async fn structure_capacity_report(
+    state: State<'_, AppState>,
+    structure_id: &str,
+) -> Result<CapacityReport, String> {
+    let structure = stored_structure(&state, structure_id).await?;
+    Ok(capacity::capacity_report(&structure))
+}
+
+/// Proposes splitting one category of a saved structure a given way
+#[tauri::command]
+pub async fn propose_category_split(
+    state: State<'_, AppState>,
+    structure_id: &str,
+    category_number: u8,
+    strategy: SplitStrategy,
+) -> Result<SplitProposal, String> {
+    let structure = stored_structure(&state, structure_id).await?;
+    Ok(capacity::split_category(
+        &structure,
+        category_number,
+        strategy,
+    )?)
+}
+
+/// Rebuilds the link tree of a saved structure in symlink or hardlink mode
+#[tauri::command]
+pub async fn sync_link_farm(
+    state: State<'_, AppState>,
+    structure_id: &str,
+) -> Result<LinkFarmReport, String> {
+    let structure = stored_structure(&state, structure_id).await?;
+    let settings = state.db.load_settings().await?;
+    let farm = LinkFarm::from_settings(
+        settings.organization_mode,
+        settings.link_farm_path.as_deref(),
+    )?
+    .ok_or("Files are moved, not linked, in the current organization mode")?;
+    Ok(farm.sync(&structure)?)
+}
+
+/// Embeds the categories and items of a saved structure that changed since
+/// the last run. Returns how many were embedded.
+#[tauri::command]
+pub async fn index_structure_semantics(
+    state: State<'_, AppState>,
+    structure_id: &str,
+) -> Result<usize, String> {
+    let structure = stored_structure(&state, structure_id).await?;
+    let ai = state.ai.read().await;
+    let index = SemanticIndex::new(ai.provider(), &state.db, DEFAULT_EMBEDDING_MODEL);
+    Ok(index.index_structure(&structure).await?)
+}
+
+/// Places a file at the item of an indexed structure closest in meaning
+#[tauri::command]
+pub async fn assign_file_semantically(
+    state: State<'_, AppState>,
+    structure_id: &str,
+    path: &str,
+) -> Result<Option<CategoryAssignment>, String> {
+    let structure = stored_structure(&state, structure_id).await?;
+    let (file_name, snippet) = embedding_input(path);
+    let ai = state.ai.read().await;
+    let index = SemanticIndex::new(ai.provider(), &state.db, DEFAULT_EMBEDDING_MODEL);
+    Ok(index
+        .assign(&structure, path, &file_name, snippet.as_deref())
+        .await?)
+}
+
+/// Files indexed earlier that are closest in meaning to the file at `path`
+#[tauri::command]
+pub async fn find_similar_files(
+    state: State<'_, AppState>,
+    path: &str,
+    limit: Option<usize>,
+) -> Result<Vec<SemanticMatch>, String> {
+    let (file_name, snippet) = embedding_input(path);
+    let ai = state.ai.read().await;
+    let index = SemanticIndex::new(ai.provider(), &state.db, DEFAULT_EMBEDDING_MODEL);
+    index
+        .index_file(path, &file_name, snippet.as_deref())
+        .await?;
+    Ok(index.similar_files(path, limit.unwrap_or(10)).await?)
+}
+
+/// Validates a saved structure and runs the lint rules from the settings
+#[tauri::command]
+pub async fn validate_saved_structure(
+    state: State<'_, AppState>,
+    structure_id: &str,
+) -> Result<JDValidationResult, String> {
+    let structure = stored_structure(&state, structure_id).await?;
+    let settings = state.db.load_settings().await?;
+    let engine = JohnnyDecimalEngine::with_locale(&settings.locale)?;
+    Ok(engine.validate_and_lint(&structure, &settings.lint).await?)
+}
+
+/// All lint rules with their state under the current settings
+#[tauri::command]
+pub async fn list_lint_rules(state: State<'_, AppState>) -> Result<Vec<LintRuleStatus>, String> {
+    let settings = state.db.load_settings().await?;
+    Ok(lint::rule_statuses(&settings.lint))
+}
+
+/// Models are only managed on Ollama; other servers install their own.
+async fn model_manager(state: &AppState) -> Result<ModelManager, String> {
+    let ai = state.ai.read().await;
+    let provider = ai.provider();
+    if provider.id() != OLLAMA {
+        return Err(format!(
+            "Models on {} are managed by the server",
+            provider.id()
+        ));
+    }
+    Ok(ModelManager::new(provider.config().clone())?)
+}
+
+/// A JD engine that names new folders in the configured language.
+async fn settings_engine(state: &AppState) -> AppResult<JohnnyDecimalEngine> {
+    let settings = state.db.load_settings().await?;
+    JohnnyDecimalEngine::with_locale(&settings.locale)
+}
+
+async fn stored_structure(state: &AppState, structure_id: &str) -> Result<JDStructure, String> {
+    state
+        .db
+        .load_structure(structure_id)
+        .await?
+        .ok_or_else(|| format!("Structure {} not found", structure_id))
+}
+
+/// The file name and content snippet a file is embedded from.
+fn embedding_input(path: &str) -> (String, Option<String>) {
+    let file_name = Path::new(path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.to_string());
+    (
+        file_name,
+        snippet_for_request(path, &ExtractionLimits::default()),
+    )
+}
@@ -0,0 +1,458 @@
+use crate::error::{AppError, Result};
+use flate2::read::ZlibDecoder;
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+/// Bytes inspected when deciding whether a file is binary.
+const SNIFF_BYTES: usize = 8 * 1024;
+/// Share of control bytes above which a file counts as binary.
+const BINARY_RATIO: f64 = 0.1;
+/// Data rows kept after a CSV header.
+const CSV_SAMPLE_ROWS: usize = 3;
+
+const SOURCE_EXTENSIONS: &[&str] = &[
+    "rs", "py", "js", "ts", "jsx", "tsx", "java", "kt", "c", "h", "cpp", "hpp", "cs", "go", "rb",
+    "php", "swift", "scala", "sh", "sql", "html", "css", "scss", "json", "yaml", "yml", "toml",
+    "xml",
+];
+const TEXT_EXTENSIONS: &[&str] = &["txt", "text", "log", "ini", "cfg", "conf", "rtf", "tex"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ContentKind {
+    PlainText,
+    Markdown,
+    SourceCode,
+    Csv,
+    Docx,
+    Odt,
+    Pdf,
+}
+
+impl ContentKind {
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        let extension = extension.to_lowercase();
+        match extension.as_str() {
+            "md" | "markdown" => Some(ContentKind::Markdown),
+            "csv" | "tsv" => Some(ContentKind::Csv),
+            "docx" => Some(ContentKind::Docx),
+            "odt" => Some(ContentKind::Odt),
+            "pdf" => Some(ContentKind::Pdf),
+            ext if SOURCE_EXTENSIONS.contains(&ext) => Some(ContentKind::SourceCode),
+            ext if TEXT_EXTENSIONS.contains(&ext) => Some(ContentKind::PlainText),
+            _ => None,
+        }
+    }
+
+    fn is_container(self) -> bool {
+        matches!(
+            self,
+            ContentKind::Docx | ContentKind::Odt | ContentKind::Pdf
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExtractionLimits {
+    pub max_chars: usize,         // Length of the snippet handed to the prompt
+    pub max_text_bytes: u64,      // Read from plain text files
+    pub max_container_bytes: u64, // DOCX, ODT and PDF files larger than this are skipped
+    pub max_inflated_bytes: u64,  // Unpacked from a document's XML or each PDF stream
+}
+
+impl Default for ExtractionLimits {
+    fn default() -> Self {
+        Self {
+            max_chars: 2000,
+            max_text_bytes: 64 * 1024,
+            max_container_bytes: 20 * 1024 * 1024,
+            max_inflated_bytes: 4 * 1024 * 1024,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContentSnippet {
+    pub kind: ContentKind,
+    pub text: String,
+    pub truncated: bool,
+}
+
+/// Normalized text from the start of a file, or `None` when the format is
+/// unsupported, the file is binary or too large, or it holds no text.
+pub fn extract_snippet(path: &Path, limits: &ExtractionLimits) -> Result<Option<ContentSnippet>> {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default();
+    let kind = match ContentKind::from_extension(extension) {
+        Some(kind) => kind,
+        None => return Ok(None),
+    };
+
+    if kind.is_container() && std::fs::metadata(path)?.len() > limits.max_container_bytes {
+        return Ok(None);
+    }
+
+    let raw = match kind {
+        ContentKind::Docx => zip_entry_text(path, "word/document.xml", "w:p", limits)?,
+        ContentKind::Odt => zip_entry_text(path, "content.xml", "text:p", limits)?,
+        ContentKind::Pdf => pdf_text(&std::fs::read(path)?, limits),
+        _ => {
+            let mut bytes = Vec::new();
+            File::open(path)?
+                .take(limits.max_text_bytes)
+                .read_to_end(&mut bytes)?;
+            if is_binary(&bytes) {
+                return Ok(None);
+            }
+            let text = String::from_utf8_lossy(&bytes).to_string();
+            if kind == ContentKind::Csv {
+                text.lines()
+                    .take(CSV_SAMPLE_ROWS + 1)
+                    .collect::<Vec<_>>()
+                    .join("\n")
+            } else {
+                text
+            }
+        }
+    };
+
+    let text = normalize(&raw);
+    if text.is_empty() {
+        return Ok(None);
+    }
+    let truncated = text.chars().count() > limits.max_chars;
+    Ok(Some(ContentSnippet {
+        kind,
+        text: text.chars().take(limits.max_chars).collect(),
+        truncated,
+    }))
+}
+
+/// The snippet text for `AnalysisRequest::content_snippet`. Failures are
+/// logged and leave the request without content.
+pub fn snippet_for_request(path: &str, limits: &ExtractionLimits) -> Option<String> {
+    match extract_snippet(Path::new(path), limits) {
+        Ok(snippet) => snippet.map(|s| s.text),
+        Err(e) => {
+            log::debug!("No content snippet for {}: {}", path, e);
+            None
+        }
+    }
+}
+
+/// NUL bytes or many control characters in the first few KB.
+pub fn is_binary(bytes: &[u8]) -> bool {
+    let sample = &bytes[..bytes.len().min(SNIFF_BYTES)];
+    if sample.contains(&0) {
+        return true;
+    }
+    let control = sample
+        .iter()
+        .filter(|b| b.is_ascii_control() && !b"\n\r\t\x0c".contains(b))
+        .count();
+    !sample.is_empty() && control as f64 / sample.len() as f64 > BINARY_RATIO
+}
+
+/// Collapses runs of spaces, drops control characters and blank lines.
+pub fn normalize(text: &str) -> String {
+    text.lines()
+        .map(|line| {
+            line.split(|c: char| c.is_whitespace() || c.is_control())
+                .filter(|word| !word.is_empty())
+                .collect::<Vec<_>>()
+                .join(" ")
+        })
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Text of an XML part inside a DOCX or ODT archive, one line per paragraph.
+fn zip_entry_text(
+    path: &Path,
+    entry: &str,
+    paragraph: &str,
+    limits: &ExtractionLimits,
+) -> Result<String> {
+    let mut archive = zip::ZipArchive::new(File::open(path)?)
+        .map_err(|e| AppError::FileSystem(format!("Not a valid document archive: {}", e)))?;
+    let mut xml = Vec::new();
+    archive
+        .by_name(entry)
+        .map_err(|e| AppError::FileSystem(format!("Document has no {}: {}", entry, e)))?
+        .take(limits.max_inflated_bytes)
+        .read_to_end(&mut xml)?;
+    Ok(xml_text(&String::from_utf8_lossy(&xml), paragraph, limits))
+}
+
+/// Whether `text` already fills a snippet, so reading can stop.
+fn has_enough_text(text: &str, limits: &ExtractionLimits) -> bool {
+    normalize(text).chars().count() > limits.max_chars
+}
+
+fn xml_text(xml: &str, paragraph: &str, limits: &ExtractionLimits) -> String {
+    let closing = format!("</{}>", paragraph);
+    let mut text = String::new();
+    let mut rest = xml;
+    while let Some(start) = rest.find('<') {
+        text.push_str(&decode_entities(&rest[..start]));
+        let end = match rest[start..].find('>') {
+            Some(end) => start + end + 1,
+            None => break,
+        };
+        let tag = &rest[start..end];
+        if tag == closing {
+            text.push('\n');
+            if has_enough_text(&text, limits) {
+                break;
+            }
+        } else if ["<w:tab", "<text:tab", "<text:s/", "<text:s "]
+            .iter()
+            .any(|t| tag.starts_with(t))
+        {
+            text.push(' ');
+        }
+        rest = &rest[end..];
+    }
+    text
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Strings shown by the text operators of every content stream, inflating
+/// `FlateDecode` streams. Fonts with custom encodings come out garbled, and
+/// scanned PDFs have no text at all.
+fn pdf_text(pdf: &[u8], limits: &ExtractionLimits) -> String {
+    let mut text = String::new();
+    let mut rest = pdf;
+    while let Some(start) = find(rest, b"stream") {
+        let dictionary = &rest[..start];
+        let mut body = &rest[start + b"stream".len()..];
+        body = body.strip_prefix(b"\r").unwrap_or(body);
+        body = body.strip_prefix(b"\n").unwrap_or(body);
+        let end = match find(body, b"endstream") {
+            Some(end) => end,
+            None => break,
+        };
+
+        let data = &body[..end];
+        let dictionary_start = dictionary.windows(2).rposition(|w| w == b"<<").unwrap_or(0);
+        if find(&dictionary[dictionary_start..], b"/FlateDecode").is_some() {
+            let mut inflated = Vec::new();
+            if ZlibDecoder::new(data)
+                .take(limits.max_inflated_bytes)
+                .read_to_end(&mut inflated)
+                .is_ok()
+            {
+                text.push_str(&pdf_text_operators(&inflated));
+            }
+        } else {
+            text.push_str(&pdf_text_operators(data));
+        }
+        if has_enough_text(&text, limits) {
+            break;
+        }
+        rest = &body[end + b"endstream".len()..];
+    }
+    text
+}
+
+/// Literal strings inside `BT`/`ET` blocks, a line per block.
+fn pdf_text_operators(content: &[u8]) -> String {
+    let mut text = String::new();
+    let mut in_text = false;
+    let mut i = 0;
+    while i < content.len() {
+        match content[i] {
+            b'(' if in_text => {
+                let (string, next) = pdf_string(content, i + 1);
+                text.push_str(&string);
+                i = next;
+                continue;
+            }
+            b'B' if content[i..].starts_with(b"BT") && is_token(content, i, 2) => in_text = true,
+            b'E' if content[i..].starts_with(b"ET") && is_token(content, i, 2) => {
+                in_text = false;
+                text.push('\n');
+            }
+            b'T' if in_text && content[i..].starts_with(b"T*") => text.push('\n'),
+            b'T' if in_text && content[i..].starts_with(b"Td") => text.push(' '),
+            _ => {}
+        }
+        i += 1;
+    }
+    text
+}
+
+fn is_token(content: &[u8], at: usize, len: usize) -> bool {
+    let boundary = |b: Option<&u8>| !matches!(b, Some(b) if !b.is_ascii_whitespace());
+    (at == 0 || boundary(content.get(at - 1))) && boundary(content.get(at + len))
+}
+
+/// A `(...)` string starting after the open paren, with the index after it.
+fn pdf_string(content: &[u8], mut i: usize) -> (String, usize) {
+    let mut bytes = Vec::new();
+    let mut depth = 1;
+    while i < content.len() {
+        let byte = content[i];
+        i += 1;
+        match byte {
+            b'\\' if i < content.len() => {
+                let escaped = content[i];
+                i += 1;
+                match escaped {
+                    b'n' => bytes.push(b'\n'),
+                    b't' => bytes.push(b' '),
+                    b'0'..=b'7' => {
+                        let digits = content[i - 1..]
+                            .iter()
+                            .take(3)
+                            .take_while(|b| (b'0'..=b'7').contains(b))
+                            .count();
+                        let octal = std::str::from_utf8(&content[i - 1..i - 1 + digits])
+                            .ok()
+                            .and_then(|s| u8::from_str_radix(s, 8).ok());
+                        bytes.extend(octal);
+                        i += digits - 1;
+                    }
+                    other => bytes.push(other),
+                }
+            }
+            b'(' => {
+                depth += 1;
+                bytes.push(byte);
+            }
+            b')' => {
+                depth -= 1;
+                if depth == 0 {
+                    break;
+                }
+                bytes.push(byte);
+            }
+            _ => bytes.push(byte),
+        }
+    }
+    (String::from_utf8_lossy(&bytes).to_string(), i)
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|w| w == needle)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_text_csv_and_binary_files() {
+        let dir = TempDir::new().unwrap();
+        let limits = ExtractionLimits {
+            max_chars: 40,
+            ..ExtractionLimits::default()
+        };
+
+        let notes = dir.path().join("notes.md");
+        std::fs::write(&notes, "# Invoice   March\n\n\nTotal due:\t 120 EUR\n").unwrap();
+        let snippet = extract_snippet(&notes, &limits).unwrap().unwrap();
+        assert_eq!(snippet.kind, ContentKind::Markdown);
+        assert_eq!(snippet.text, "# Invoice March\nTotal due: 120 EUR");
+        assert!(!snippet.truncated);
+
+        let csv = dir.path().join("export.csv");
+        std::fs::write(&csv, "date,amount,payee\n1,2,3\n4,5,6\n7,8,9\n10,11,12\n").unwrap();
+        let snippet = extract_snippet(&csv, &limits).unwrap().unwrap();
+        assert_eq!(snippet.text.lines().count(), 4);
+        assert!(snippet.text.starts_with("date,amount,payee"));
+
+        let long = dir.path().join("main.rs");
+        std::fs::write(&long, "fn main() {}\n".repeat(10)).unwrap();
+        assert!(extract_snippet(&long, &limits).unwrap().unwrap().truncated);
+
+        let binary = dir.path().join("data.txt");
+        std::fs::write(&binary, [0x89, b'P', b'N', b'G', 0, 0, 1]).unwrap();
+        assert!(extract_snippet(&binary, &limits).unwrap().is_none());
+        assert!(extract_snippet(&dir.path().join("photo.jpg"), &limits)
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    fn test_docx_and_pdf_text() {
+        let dir = TempDir::new().unwrap();
+        let limits = ExtractionLimits::default();
+
+        let docx = dir.path().join("scan_0042.docx");
+        let mut zip = zip::ZipWriter::new(File::create(&docx).unwrap());
+        zip.start_file("word/document.xml", zip::write::FileOptions::default())
+            .unwrap();
+        zip.write_all(
+            b"<w:document><w:body><w:p><w:r><w:t>Lease &amp; rental</w:t></w:r></w:p>\
+              <w:p><w:r><w:t>agreement</w:t></w:r></w:p></w:body></w:document>",
+        )
+        .unwrap();
+        zip.finish().unwrap();
+        let snippet = extract_snippet(&docx, &limits).unwrap().unwrap();
+        assert_eq!(snippet.text, "Lease & rental\nagreement");
+
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder
+            .write_all(b"BT /F1 12 Tf (Tax \\(2024\\)) Tj T* [(Assess) -20 (ment)] TJ ET")
+            .unwrap();
+        let stream = encoder.finish().unwrap();
+        let mut pdf = b"%PDF-1.4\n4 0 obj << /Length 60 /Filter /FlateDecode >>\nstream\n".to_vec();
+        pdf.extend(&stream);
+        pdf.extend(b"\nendstream\nendobj\n%%EOF");
+        let path = dir.path().join("scan_0042.pdf");
+        std::fs::write(&path, pdf).unwrap();
+        let snippet = extract_snippet(&path, &limits).unwrap().unwrap();
+        assert_eq!(snippet.kind, ContentKind::Pdf);
+        assert_eq!(snippet.text, "Tax (2024)\nAssessment");
+    }
+
+    #[test]
+    fn test_inflated_streams_are_bounded() {
+        let limits = ExtractionLimits {
+            max_chars: 20,
+            max_inflated_bytes: 1024,
+            ..ExtractionLimits::default()
+        };
+        let stream = |content: &[u8]| {
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(content).unwrap();
+            let mut pdf = b"<< /Filter /FlateDecode >>\nstream\n".to_vec();
+            pdf.extend(encoder.finish().unwrap());
+            pdf.extend(b"\nendstream\n");
+            pdf
+        };
+
+        // A bomb inflates only as far as the cap and keeps its first text
+        let mut bomb = b"BT (Budget) Tj ET".to_vec();
+        bomb.resize(8 * 1024 * 1024, b' ');
+        assert_eq!(pdf_text(&stream(&bomb), &limits), "Budget\n");
+
+        // Streams after the snippet is full are not read
+        let mut pdf = stream(b"BT (First quarter report 2024) Tj ET");
+        pdf.extend(stream(b"BT (Second) Tj ET"));
+        assert_eq!(pdf_text(&pdf, &limits), "First quarter report 2024\n");
+
+        let xml = "<w:p>Lease agreement for the flat</w:p><w:p>Signed</w:p>";
+        assert_eq!(
+            xml_text(xml, "w:p", &limits),
+            "Lease agreement for the flat\n"
+        );
+    }
+}
@@ -0,0 +1,230 @@
+use crate::ai_service::AnalysisRequest;
+use crate::categorization::{CategorizationContext, PlacementRule};
+use crate::jd_id::JDId;
+use crate::johnny_decimal::CategoryAssignment;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+/// Times the same correction has to repeat before it becomes a rule.
+pub const MIN_REPEATS_FOR_RULE: usize = 3;
+/// Few-shot examples added to a prompt.
+pub const MAX_PROMPT_EXAMPLES: usize = 5;
+/// Newest corrections examples and rules are drawn from.
+pub const MAX_CORRECTIONS_CONSIDERED: usize = 500;
+
+/// A user overriding where the organizer wanted to put a file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Correction {
+    pub id: String,
+    pub file_path: String,
+    pub file_name: String,
+    pub extension: String,       // Lower case, without the dot
+    pub suggested: Option<JDId>, // What the organizer proposed
+    pub chosen: JDId,
+    pub chosen_label: String, // e.g. "21.03 Invoices"
+    pub structure_id: Option<String>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+/// A correction as shown to the model.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FewShotExample {
+    pub file_name: String,
+    pub suggested: Option<String>,
+    pub chosen: String, // The ID with its name
+}
+
+#[allow(dead_code)]
+impl Correction {
+    pub fn new(
+        file_path: &str,
+        suggested: Option<&CategoryAssignment>,
+        chosen: JDId,
+        chosen_label: &str,
+        structure_id: Option<&str>,
+    ) -> Self {
+        let path = Path::new(file_path);
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            file_path: file_path.to_string(),
+            file_name: path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_else(|| file_path.to_string()),
+            extension: path
+                .extension()
+                .map(|e| e.to_string_lossy().to_lowercase())
+                .unwrap_or_default(),
+            suggested: suggested.and_then(|a| a.item_number.parse().ok()),
+            chosen,
+            chosen_label: chosen_label.to_string(),
+            structure_id: structure_id.map(str::to_string),
+            created_at: chrono::Utc::now(),
+        }
+    }
+
+    /// Lower-case words of the file name, without numbers and short noise.
+    pub fn name_tokens(&self) -> HashSet<String> {
+        name_tokens(&self.file_name)
+    }
+
+    pub fn example(&self) -> FewShotExample {
+        FewShotExample {
+            file_name: self.file_name.clone(),
+            suggested: self.suggested.as_ref().map(|id| id.to_string()),
+            chosen: self.chosen_label.clone(),
+        }
+    }
+}
+
+pub fn name_tokens(file_name: &str) -> HashSet<String> {
+    let stem = Path::new(file_name)
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    stem.split(|c: char| !c.is_alphanumeric())
+        .filter(|t| t.chars().count() >= 3 && !t.chars().all(|c| c.is_ascii_digit()))
+        .map(str::to_string)
+        .collect()
+}
+
+/// The corrections most like `file_name`, as prompt examples. Shared name
+/// words count most, then a matching extension; ties go to the newest.
+/// Corrections sharing neither are left out.
+pub fn select_examples(
+    corrections: &[Correction],
+    file_name: &str,
+    limit: usize,
+) -> Vec<FewShotExample> {
+    let tokens = name_tokens(file_name);
+    let extension = Path::new(file_name)
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    let mut scored: Vec<(usize, &Correction)> = corrections
+        .iter()
+        .map(|c| {
+            let shared = c.name_tokens().intersection(&tokens).count();
+            (shared * 2 + usize::from(c.extension == extension), c)
+        })
+        .filter(|(score, _)| *score > 0)
+        .collect();
+    scored.sort_by(|(a, x), (b, y)| b.cmp(a).then(y.created_at.cmp(&x.created_at)));
+
+    scored
+        .into_iter()
+        .take(limit)
+        .map(|(_, c)| c.example())
+        .collect()
+}
+
+/// Fills `request.examples` with up to `MAX_PROMPT_EXAMPLES` corrections.
+pub fn attach_examples(request: &mut AnalysisRequest, corrections: &[Correction]) {
+    request.examples = select_examples(corrections, &request.file_name, MAX_PROMPT_EXAMPLES);
+}
+
+/// Rules for habits the user has shown at least `MIN_REPEATS_FOR_RULE`
+/// times: an extension or name word that always went to the same ID. A
+/// habit that was ever broken gives no rule.
+pub fn learned_rules(corrections: &[Correction]) -> Vec<PlacementRule> {
+    let mut by_pattern: HashMap<String, Vec<&JDId>> = HashMap::new();
+    for correction in corrections {
+        if !correction.extension.is_empty() {
+            by_pattern
+                .entry(format!("*.{}", correction.extension))
+                .or_default()
+                .push(&correction.chosen);
+        }
+        for token in correction.name_tokens() {
+            by_pattern
+                .entry(token)
+                .or_default()
+                .push(&correction.chosen);
+        }
+    }
+
+    let mut rules: Vec<PlacementRule> = by_pattern
+        .into_iter()
+        .filter(|(_, targets)| {
+            targets.len() >= MIN_REPEATS_FOR_RULE && targets.iter().all(|t| *t == targets[0])
+        })
+        .map(|(pattern, targets)| PlacementRule {
+            pattern,
+            target: targets[0].clone(),
+        })
+        .collect();
+    rules.sort_by(|a, b| a.pattern.cmp(&b.pattern));
+    rules
+}
+
+/// Learned rules plus the user's last choice for this exact file.
+pub fn context_from_corrections(
+    corrections: &[Correction],
+    file_path: &str,
+) -> CategorizationContext {
+    CategorizationContext {
+        rules: learned_rules(corrections),
+        correction: corrections
+            .iter()
+            .filter(|c| c.file_path == file_path)
+            .max_by_key(|c| c.created_at)
+            .map(|c| c.chosen.clone()),
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn correction(path: &str, chosen: &str, minutes_ago: i64) -> Correction {
+        let mut correction = Correction::new(
+            path,
+            None,
+            chosen.parse().unwrap(),
+            &format!("{} Chosen", chosen),
+            None,
+        );
+        correction.created_at = chrono::Utc::now() - chrono::Duration::minutes(minutes_ago);
+        correction
+    }
+
+    #[test]
+    fn test_select_examples() {
+        let corrections = vec![
+            correction("/dl/invoice_acme_2023.pdf", "21.03", 30),
+            correction("/dl/holiday.jpg", "31.01", 20),
+            correction("/dl/manual.pdf", "22.01", 10),
+            correction("/dl/acme-invoice-04.pdf", "21.03", 5),
+        ];
+
+        let examples = select_examples(&corrections, "Invoice ACME 2024-05.pdf", 3);
+        assert_eq!(examples.len(), 3);
+        assert_eq!(examples[0].file_name, "acme-invoice-04.pdf");
+        assert_eq!(examples[1].file_name, "invoice_acme_2023.pdf");
+        assert_eq!(examples[2].chosen, "22.01 Chosen");
+        assert!(select_examples(&corrections, "song.mp3", 3).is_empty());
+    }
+
+    #[test]
+    fn test_learned_rules_need_consistent_repeats() {
+        let mut corrections = vec![
+            correction("/dl/scan_001.png", "23.02", 3),
+            correction("/dl/scan_002.png", "23.02", 2),
+            correction("/dl/receipt_scan.png", "23.02", 1),
+            correction("/dl/wallpaper.png", "31.01", 0),
+        ];
+        let rules = learned_rules(&corrections);
+        assert_eq!(rules.len(), 1);
+        assert_eq!(rules[0].pattern, "scan");
+        assert!(rules[0].matches("SCAN_003.png"));
+
+        corrections.push(correction("/dl/scan_004.png", "24.01", 0));
+        assert!(learned_rules(&corrections).is_empty());
+
+        let context = context_from_corrections(&corrections, "/dl/scan_002.png");
+        assert_eq!(context.correction, Some("23.02".parse().unwrap()));
+    }
+}
@@ -1,5 +1,13 @@
-use crate::error::Result;
+use crate::ai_provider::AiBackendSettings;
+use crate::ai_service::AnalysisResult;
+use crate::corrections::Correction;
+use crate::error::{AppError, Result};
 use crate::johnny_decimal::JDStructure;
+use crate::labels::DEFAULT_LOCALE;
+use crate::link_farm::OrganizationMode;
+use crate::lint::LintSettings;
+use crate::structure_diff::{diff_structures, StructureDiff};
+use crate::templates::{builtin_templates, StructureTemplate};
 use rusqlite::{params, Connection};
 use serde::{Deserialize, Serialize};
 
@@ -25,6 +33,55 @@ pub struct FileMetadata {
     pub notes: Option<String>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StructureRevision {
+    pub structure_id: String,
+    pub revision: u32,
+    pub name: String,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredEmbedding {
+    pub key: String,
+    pub model: String,
+    pub text_hash: String,
+    pub vector: Vec<f32>,
+}
+
+/// Everything an AI result depends on. A change to any part is a cache miss.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AnalysisCacheKey {
+    pub file_hash: String,
+    pub model: String,
+    pub prompt_version: u32,
+    pub organization_scheme: String,
+    pub prompt_hash: String, // Hash of the rendered prompt: snippet, examples, file name
+    pub structure_id: Option<String>,
+    pub structure_revision: Option<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AnalysisCacheStats {
+    pub entries: u64,
+    pub hits: u64,
+    pub misses: u64,
+    pub hit_rate: f64, // hits / (hits + misses), 0.0 before the first lookup
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CachePurge {
+    All,
+    /// Results no current lookup can hit: other prompt versions, other
+    /// models (when given) and superseded structure revisions
+    Outdated {
+        prompt_version: u32,
+        model: Option<String>,
+    },
+    OlderThan(chrono::DateTime<chrono::Utc>),
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OrganizationSession {
     pub id: String,
@@ -55,11 +112,51 @@ pub struct AppSettings {
     pub backup_location: Option<String>,
     pub ai_provider: String,
     pub ai_model: String,
+    #[serde(default)]
+    pub ai_backends: AiBackendSettings, // Base URL, timeout and auth per provider
     pub preview_mode: bool,
     pub confirm_moves: bool,
     pub max_file_size_mb: u64,
     pub excluded_extensions: Vec<String>,
     pub excluded_paths: Vec<String>,
+    #[serde(default)]
+    pub lint: LintSettings,
+    #[serde(default = "default_locale")]
+    pub locale: String, // Language of generated folder names
+    #[serde(default)]
+    pub organization_mode: OrganizationMode,
+    #[serde(default)]
+    pub link_farm_path: Option<String>, // Root of the link tree in symlink/hardlink mode
+}
+
+fn default_locale() -> String {
+    DEFAULT_LOCALE.to_string()
+}
+
+impl Default for AppSettings {
+    fn default() -> Self {
+        Self {
+            theme: "system".to_string(),
+            auto_backup: true,
+            backup_location: None,
+            ai_provider: "ollama".to_string(),
+            ai_model: "llama3.2:1b".to_string(),
+            ai_backends: AiBackendSettings::default(),
+            preview_mode: true,
+            confirm_moves: true,
+            max_file_size_mb: 1000,
+            excluded_extensions: vec!["tmp".to_string(), "cache".to_string(), "log".to_string()],
+            excluded_paths: vec![
+                ".git".to_string(),
+                "node_modules".to_string(),
+                ".DS_Store".to_string(),
+            ],
+            lint: LintSettings::default(),
+            locale: default_locale(),
+            organization_mode: OrganizationMode::default(),
+            link_farm_path: None,
+        }
+    }
 }
 
 #[allow(dead_code)]
@@ -89,6 +186,27 @@ impl DatabaseManager {
             [],
         )?;
 
+        // Every saved version of a structure, oldest revision first
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS structure_revisions (
+                structure_id TEXT NOT NULL,
+                revision INTEGER NOT NULL,
+                name TEXT NOT NULL,
+                data TEXT NOT NULL, -- JSON serialized JDStructure
+                created_at TEXT NOT NULL,
+                PRIMARY KEY (structure_id, revision)
+            )",
+            [],
+        )?;
+
+        // Structures saved before revisions existed start with revision 1
+        conn.execute(
+            "INSERT INTO structure_revisions (structure_id, revision, name, data, created_at)
+             SELECT id, 1, name, data, modified_at FROM jd_structures
+             WHERE id NOT IN (SELECT structure_id FROM structure_revisions)",
+            [],
+        )?;
+
         conn.execute(
             "CREATE TABLE IF NOT EXISTS file_metadata (
                 id TEXT PRIMARY KEY,
@@ -123,6 +241,18 @@ impl DatabaseManager {
             [],
         )?;
 
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS structure_templates (
+                id TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                version INTEGER NOT NULL,
+                data TEXT NOT NULL, -- JSON serialized StructureTemplate
+                created_at TEXT NOT NULL,
+                modified_at TEXT NOT NULL
+            )",
+            [],
+        )?;
+
         conn.execute(
             "CREATE TABLE IF NOT EXISTS app_settings (
                 key TEXT PRIMARY KEY,
@@ -132,6 +262,78 @@ impl DatabaseManager {
             [],
         )?;
 
+        // AI results, valid only for the exact file content, model, prompt,
+        // scheme and structure revision they were produced with. Results are
+        // disposable, so a table keyed without the prompt hash is dropped.
+        let keyed_by_prompt: bool = conn.query_row(
+            "SELECT COUNT(*) > 0 FROM pragma_table_info('analysis_cache') WHERE name = 'prompt_hash'",
+            [],
+            |row| row.get(0),
+        )?;
+        if !keyed_by_prompt {
+            conn.execute("DROP TABLE IF EXISTS analysis_cache", [])?;
+        }
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS analysis_cache (
+                file_hash TEXT NOT NULL,
+                model TEXT NOT NULL,
+                prompt_version INTEGER NOT NULL,
+                organization_scheme TEXT NOT NULL,
+                prompt_hash TEXT NOT NULL,
+                structure_id TEXT NOT NULL, -- Empty when analyzed without a structure
+                structure_revision INTEGER NOT NULL,
+                result TEXT NOT NULL, -- JSON serialized AnalysisResult
+                created_at TEXT NOT NULL,
+                PRIMARY KEY (file_hash, model, prompt_version, organization_scheme, prompt_hash,
+                             structure_id, structure_revision)
+            )",
+            [],
+        )?;
+
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS analysis_cache_stats (
+                id INTEGER PRIMARY KEY CHECK (id = 1),
+                hits INTEGER NOT NULL,
+                misses INTEGER NOT NULL
+            )",
+            [],
+        )?;
+        conn.execute(
+            "INSERT OR IGNORE INTO analysis_cache_stats (id, hits, misses) VALUES (1, 0, 0)",
+            [],
+        )?;
+
+        // Embedding vectors as little-endian f32 blobs. Files use their path
+        // as key (matching file_metadata.path), structure entries their JD ID.
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS embeddings (
+                scope TEXT NOT NULL, -- Structure ID, or 'files'
+                key TEXT NOT NULL,
+                model TEXT NOT NULL,
+                text_hash TEXT NOT NULL, -- Hash of the embedded text
+                vector BLOB NOT NULL,
+                updated_at TEXT NOT NULL,
+                PRIMARY KEY (scope, key, model)
+            )",
+            [],
+        )?;
+
+        // Placements the user overrode, for few-shot examples and learned rules
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS corrections (
+                id TEXT PRIMARY KEY,
+                file_path TEXT NOT NULL,
+                file_name TEXT NOT NULL,
+                extension TEXT NOT NULL,
+                suggested_id TEXT,
+                chosen_id TEXT NOT NULL,
+                chosen_label TEXT NOT NULL,
+                structure_id TEXT,
+                created_at TEXT NOT NULL
+            )",
+            [],
+        )?;
+
         // Create indexes for better performance
         conn.execute(
             "CREATE INDEX IF NOT EXISTS idx_file_metadata_path ON file_metadata(path)",
@@ -155,22 +357,7 @@ impl DatabaseManager {
     }
 
     fn initialize_default_settings(&self, conn: &Connection) -> Result<()> {
-        let default_settings = AppSettings {
-            theme: "system".to_string(),
-            auto_backup: true,
-            backup_location: None,
-            ai_provider: "ollama".to_string(),
-            ai_model: "llama3.2:1b".to_string(),
-            preview_mode: true,
-            confirm_moves: true,
-            max_file_size_mb: 1000,
-            excluded_extensions: vec!["tmp".to_string(), "cache".to_string(), "log".to_string()],
-            excluded_paths: vec![
-                ".git".to_string(),
-                "node_modules".to_string(),
-                ".DS_Store".to_string(),
-            ],
-        };
+        let default_settings = AppSettings::default();
 
         let settings_json = serde_json::to_string(&default_settings)?;
 
@@ -187,12 +374,15 @@ impl DatabaseManager {
     }
 
     // JD Structure operations
-    pub async fn save_structure(&self, structure: &JDStructure) -> Result<()> {
-        let conn = Connection::open(&self.db_path)?;
+    /// Saves the structure and records it as a new revision, unless it is
+    /// unchanged since the latest one. Returns the current revision number.
+    pub async fn save_structure(&self, structure: &JDStructure) -> Result<u32> {
+        let mut conn = Connection::open(&self.db_path)?;
+        let tx = conn.transaction()?;
 
         let structure_json = serde_json::to_string(structure)?;
 
-        conn.execute(
+        tx.execute(
             "INSERT OR REPLACE INTO jd_structures (id, name, root_path, data, created_at, modified_at) 
              VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
             params![
@@ -205,7 +395,40 @@ impl DatabaseManager {
             ],
         )?;
 
-        Ok(())
+        let latest: Option<(u32, String)> = tx
+            .query_row(
+                "SELECT revision, data FROM structure_revisions
+                 WHERE structure_id = ?1 ORDER BY revision DESC LIMIT 1",
+                params![structure.id],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+            .map(Some)
+            .or_else(|e| match e {
+                rusqlite::Error::QueryReturnedNoRows => Ok(None),
+                e => Err(e),
+            })?;
+
+        let revision = match latest {
+            Some((revision, data)) if data == structure_json => revision,
+            latest => {
+                let revision = latest.map(|(r, _)| r + 1).unwrap_or(1);
+                tx.execute(
+                    "INSERT INTO structure_revisions (structure_id, revision, name, data, created_at)
+                     VALUES (?1, ?2, ?3, ?4, ?5)",
+                    params![
+                        structure.id,
+                        revision,
+                        structure.name,
+                        structure_json,
+                        chrono::Utc::now().to_rfc3339()
+                    ],
+                )?;
+                revision
+            }
+        };
+
+        tx.commit()?;
+        Ok(revision)
     }
 
     pub async fn load_structure(&self, structure_id: &str) -> Result<Option<JDStructure>> {
@@ -258,6 +481,188 @@ impl DatabaseManager {
             "DELETE FROM jd_structures WHERE id = ?1",
             params![structure_id],
         )?;
+        conn.execute(
+            "DELETE FROM structure_revisions WHERE structure_id = ?1",
+            params![structure_id],
+        )?;
+
+        Ok(())
+    }
+
+    // Structure revision operations
+    /// Newest revision first.
+    pub async fn list_revisions(&self, structure_id: &str) -> Result<Vec<StructureRevision>> {
+        let conn = Connection::open(&self.db_path)?;
+
+        let mut stmt = conn.prepare(
+            "SELECT revision, name, created_at FROM structure_revisions
+             WHERE structure_id = ?1 ORDER BY revision DESC",
+        )?;
+        let rows = stmt.query_map(params![structure_id], |row| {
+            Ok((
+                row.get::<_, u32>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })?;
+
+        let mut revisions = Vec::new();
+        for row in rows {
+            let (revision, name, created_at) = row?;
+            revisions.push(StructureRevision {
+                structure_id: structure_id.to_string(),
+                revision,
+                name,
+                created_at: chrono::DateTime::parse_from_rfc3339(&created_at)
+                    .map_err(|e| AppError::Unknown(format!("Date parse error: {}", e)))?
+                    .with_timezone(&chrono::Utc),
+            });
+        }
+
+        Ok(revisions)
+    }
+
+    pub async fn load_revision(
+        &self,
+        structure_id: &str,
+        revision: u32,
+    ) -> Result<Option<JDStructure>> {
+        let conn = Connection::open(&self.db_path)?;
+
+        let result = conn.query_row(
+            "SELECT data FROM structure_revisions WHERE structure_id = ?1 AND revision = ?2",
+            params![structure_id, revision],
+            |row| row.get::<_, String>(0),
+        );
+
+        match result {
+            Ok(data) => Ok(Some(serde_json::from_str(&data)?)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// What changed from revision `from` to revision `to`.
+    pub async fn diff_revisions(
+        &self,
+        structure_id: &str,
+        from: u32,
+        to: u32,
+    ) -> Result<StructureDiff> {
+        let before = self.require_revision(structure_id, from).await?;
+        let after = self.require_revision(structure_id, to).await?;
+        Ok(diff_structures(&before, &after))
+    }
+
+    /// Makes an old revision current again. The restore is saved as a new
+    /// revision, so history is never rewritten.
+    pub async fn restore_revision(&self, structure_id: &str, revision: u32) -> Result<JDStructure> {
+        let mut structure = self.require_revision(structure_id, revision).await?;
+        structure.modified_at = chrono::Utc::now();
+        self.save_structure(&structure).await?;
+        Ok(structure)
+    }
+
+    async fn require_revision(&self, structure_id: &str, revision: u32) -> Result<JDStructure> {
+        self.load_revision(structure_id, revision)
+            .await?
+            .ok_or_else(|| {
+                AppError::InvalidInput(format!(
+                    "Structure {} has no revision {}",
+                    structure_id, revision
+                ))
+            })
+    }
+
+    // Structure template operations
+    /// Saves a user template. Saving over an existing id bumps its version.
+    pub async fn save_template(&self, template: &StructureTemplate) -> Result<StructureTemplate> {
+        if builtin_templates()?.iter().any(|t| t.id == template.id) {
+            return Err(AppError::InvalidInput(format!(
+                "Template id '{}' is reserved for a built-in template",
+                template.id
+            )));
+        }
+
+        let conn = Connection::open(&self.db_path)?;
+        let now = chrono::Utc::now().to_rfc3339();
+
+        let previous_version: Option<u32> = conn
+            .query_row(
+                "SELECT version FROM structure_templates WHERE id = ?1",
+                params![template.id],
+                |row| row.get(0),
+            )
+            .map(Some)
+            .or_else(|e| match e {
+                rusqlite::Error::QueryReturnedNoRows => Ok(None),
+                e => Err(e),
+            })?;
+
+        let mut saved = template.clone();
+        saved.builtin = false;
+        saved.version = previous_version.map(|v| v + 1).unwrap_or(1);
+        let template_json = serde_json::to_string(&saved)?;
+
+        conn.execute(
+            "INSERT INTO structure_templates (id, name, version, data, created_at, modified_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?5)
+             ON CONFLICT(id) DO UPDATE SET
+                name = excluded.name,
+                version = excluded.version,
+                data = excluded.data,
+                modified_at = excluded.modified_at",
+            params![saved.id, saved.name, saved.version, template_json, now],
+        )?;
+
+        Ok(saved)
+    }
+
+    pub async fn load_template(&self, template_id: &str) -> Result<Option<StructureTemplate>> {
+        if let Some(template) = builtin_templates()?
+            .into_iter()
+            .find(|t| t.id == template_id)
+        {
+            return Ok(Some(template));
+        }
+
+        let conn = Connection::open(&self.db_path)?;
+
+        let result = conn.query_row(
+            "SELECT data FROM structure_templates WHERE id = ?1",
+            params![template_id],
+            |row| row.get::<_, String>(0),
+        );
+
+        match result {
+            Ok(data) => Ok(Some(serde_json::from_str(&data)?)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    /// Built-in templates first, then user templates by name.
+    pub async fn list_templates(&self) -> Result<Vec<StructureTemplate>> {
+        let conn = Connection::open(&self.db_path)?;
+
+        let mut stmt = conn.prepare("SELECT data FROM structure_templates ORDER BY name")?;
+        let rows = stmt.query_map([], |row| row.get::<_, String>(0))?;
+
+        let mut templates = builtin_templates()?;
+        for data in rows {
+            templates.push(serde_json::from_str(&data?)?);
+        }
+
+        Ok(templates)
+    }
+
+    pub async fn delete_template(&self, template_id: &str) -> Result<()> {
+        let conn = Connection::open(&self.db_path)?;
+
+        conn.execute(
+            "DELETE FROM structure_templates WHERE id = ?1",
+            params![template_id],
+        )?;
 
         Ok(())
     }
@@ -396,6 +801,322 @@ impl DatabaseManager {
         Ok(())
     }
 
+    // Analysis cache operations
+    /// A cached result for exactly this key. Counts a hit or a miss.
+    pub async fn get_cached_analysis(
+        &self,
+        key: &AnalysisCacheKey,
+    ) -> Result<Option<AnalysisResult>> {
+        let conn = Connection::open(&self.db_path)?;
+
+        let result = conn.query_row(
+            "SELECT result FROM analysis_cache
+             WHERE file_hash = ?1 AND model = ?2 AND prompt_version = ?3
+               AND organization_scheme = ?4 AND prompt_hash = ?5
+               AND structure_id = ?6 AND structure_revision = ?7",
+            params![
+                key.file_hash,
+                key.model,
+                key.prompt_version,
+                key.organization_scheme,
+                key.prompt_hash,
+                key.structure_id.as_deref().unwrap_or(""),
+                key.structure_revision.unwrap_or(0)
+            ],
+            |row| row.get::<_, String>(0),
+        );
+        let cached = match result {
+            Ok(data) => Some(serde_json::from_str(&data)?),
+            Err(rusqlite::Error::QueryReturnedNoRows) => None,
+            Err(e) => return Err(e.into()),
+        };
+
+        let counter = if cached.is_some() { "hits" } else { "misses" };
+        conn.execute(
+            &format!(
+                "UPDATE analysis_cache_stats SET {0} = {0} + 1 WHERE id = 1",
+                counter
+            ),
+            [],
+        )?;
+
+        Ok(cached)
+    }
+
+    pub async fn cache_analysis(
+        &self,
+        key: &AnalysisCacheKey,
+        result: &AnalysisResult,
+    ) -> Result<()> {
+        let conn = Connection::open(&self.db_path)?;
+
+        conn.execute(
+            "INSERT OR REPLACE INTO analysis_cache
+             (file_hash, model, prompt_version, organization_scheme, prompt_hash,
+              structure_id, structure_revision, result, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                key.file_hash,
+                key.model,
+                key.prompt_version,
+                key.organization_scheme,
+                key.prompt_hash,
+                key.structure_id.as_deref().unwrap_or(""),
+                key.structure_revision.unwrap_or(0),
+                serde_json::to_string(result)?,
+                chrono::Utc::now().to_rfc3339()
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    pub async fn analysis_cache_stats(&self) -> Result<AnalysisCacheStats> {
+        let conn = Connection::open(&self.db_path)?;
+
+        let entries: u64 =
+            conn.query_row("SELECT COUNT(*) FROM analysis_cache", [], |row| row.get(0))?;
+        let (hits, misses): (u64, u64) = conn.query_row(
+            "SELECT hits, misses FROM analysis_cache_stats WHERE id = 1",
+            [],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        let lookups = hits + misses;
+
+        Ok(AnalysisCacheStats {
+            entries,
+            hits,
+            misses,
+            hit_rate: if lookups == 0 {
+                0.0
+            } else {
+                hits as f64 / lookups as f64
+            },
+        })
+    }
+
+    /// Deletes cached results and returns how many were removed. Purging
+    /// everything also resets the hit and miss counters.
+    pub async fn purge_analysis_cache(&self, scope: CachePurge) -> Result<usize> {
+        let conn = Connection::open(&self.db_path)?;
+
+        let removed = match &scope {
+            CachePurge::All => {
+                conn.execute(
+                    "UPDATE analysis_cache_stats SET hits = 0, misses = 0 WHERE id = 1",
+                    [],
+                )?;
+                conn.execute("DELETE FROM analysis_cache", [])?
+            }
+            CachePurge::Outdated {
+                prompt_version,
+                model,
+            } => conn.execute(
+                "DELETE FROM analysis_cache
+                 WHERE prompt_version != ?1 OR (?2 IS NOT NULL AND model != ?2)
+                    OR (structure_id != '' AND structure_revision < (
+                        SELECT MAX(revision) FROM structure_revisions
+                        WHERE structure_id = analysis_cache.structure_id))",
+                params![prompt_version, model],
+            )?,
+            CachePurge::OlderThan(cutoff) => conn.execute(
+                "DELETE FROM analysis_cache WHERE created_at < ?1",
+                params![cutoff.to_rfc3339()],
+            )?,
+        };
+
+        Ok(removed)
+    }
+
+    /// The newest revision of a structure, for building cache keys.
+    pub async fn latest_revision(&self, structure_id: &str) -> Result<Option<u32>> {
+        let conn = Connection::open(&self.db_path)?;
+
+        Ok(conn.query_row(
+            "SELECT MAX(revision) FROM structure_revisions WHERE structure_id = ?1",
+            params![structure_id],
+            |row| row.get(0),
+        )?)
+    }
+
+    // Embedding operations
+    pub async fn save_embedding(&self, scope: &str, embedding: &StoredEmbedding) -> Result<()> {
+        let conn = Connection::open(&self.db_path)?;
+
+        let blob: Vec<u8> = embedding
+            .vector
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        conn.execute(
+            "INSERT OR REPLACE INTO embeddings (scope, key, model, text_hash, vector, updated_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            params![
+                scope,
+                embedding.key,
+                embedding.model,
+                embedding.text_hash,
+                blob,
+                chrono::Utc::now().to_rfc3339()
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    /// Every vector in `scope` made by `model`.
+    pub async fn load_embeddings(&self, scope: &str, model: &str) -> Result<Vec<StoredEmbedding>> {
+        let conn = Connection::open(&self.db_path)?;
+
+        let mut stmt = conn.prepare(
+            "SELECT key, text_hash, vector FROM embeddings
+             WHERE scope = ?1 AND model = ?2 ORDER BY key",
+        )?;
+        let rows = stmt.query_map(params![scope, model], |row| {
+            Ok(StoredEmbedding {
+                key: row.get(0)?,
+                model: model.to_string(),
+                text_hash: row.get(1)?,
+                vector: vector_from_blob(&row.get::<_, Vec<u8>>(2)?),
+            })
+        })?;
+
+        let mut embeddings = Vec::new();
+        for embedding in rows {
+            embeddings.push(embedding?);
+        }
+        Ok(embeddings)
+    }
+
+    /// The vector stored for one key in `scope`, if `model` made it.
+    pub async fn load_embedding(
+        &self,
+        scope: &str,
+        key: &str,
+        model: &str,
+    ) -> Result<Option<StoredEmbedding>> {
+        let conn = Connection::open(&self.db_path)?;
+
+        let result = conn.query_row(
+            "SELECT text_hash, vector FROM embeddings
+             WHERE scope = ?1 AND key = ?2 AND model = ?3",
+            params![scope, key, model],
+            |row| {
+                Ok(StoredEmbedding {
+                    key: key.to_string(),
+                    model: model.to_string(),
+                    text_hash: row.get(0)?,
+                    vector: vector_from_blob(&row.get::<_, Vec<u8>>(1)?),
+                })
+            },
+        );
+
+        match result {
+            Ok(embedding) => Ok(Some(embedding)),
+            Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub async fn delete_embeddings(&self, scope: &str, keys: &[String]) -> Result<usize> {
+        let conn = Connection::open(&self.db_path)?;
+
+        let mut removed = 0;
+        for key in keys {
+            removed += conn.execute(
+                "DELETE FROM embeddings WHERE scope = ?1 AND key = ?2",
+                params![scope, key],
+            )?;
+        }
+        Ok(removed)
+    }
+
+    // Correction operations
+    pub async fn save_correction(&self, correction: &Correction) -> Result<()> {
+        let conn = Connection::open(&self.db_path)?;
+
+        conn.execute(
+            "INSERT OR REPLACE INTO corrections
+             (id, file_path, file_name, extension, suggested_id, chosen_id, chosen_label, structure_id, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+            params![
+                correction.id,
+                correction.file_path,
+                correction.file_name,
+                correction.extension,
+                correction.suggested.as_ref().map(|id| id.to_string()),
+                correction.chosen.to_string(),
+                correction.chosen_label,
+                correction.structure_id,
+                correction.created_at.to_rfc3339()
+            ],
+        )?;
+
+        Ok(())
+    }
+
+    /// The newest corrections first. With a structure ID, only corrections
+    /// made in that structure.
+    pub async fn recent_corrections(
+        &self,
+        structure_id: Option<&str>,
+        limit: usize,
+    ) -> Result<Vec<Correction>> {
+        let conn = Connection::open(&self.db_path)?;
+
+        let mut stmt = conn.prepare(
+            "SELECT id, file_path, file_name, extension, suggested_id, chosen_id, chosen_label, structure_id, created_at
+             FROM corrections WHERE ?1 IS NULL OR structure_id = ?1
+             ORDER BY created_at DESC LIMIT ?2",
+        )?;
+        let rows = stmt.query_map(params![structure_id, limit as i64], |row| {
+            Ok((
+                [
+                    row.get::<_, String>(0)?,
+                    row.get(1)?,
+                    row.get(2)?,
+                    row.get(3)?,
+                    row.get(5)?,
+                    row.get(6)?,
+                    row.get(8)?,
+                ],
+                row.get::<_, Option<String>>(4)?,
+                row.get::<_, Option<String>>(7)?,
+            ))
+        })?;
+
+        let parse = |row| -> Result<Correction> {
+            let (
+                [id, file_path, file_name, extension, chosen, chosen_label, created_at],
+                suggested,
+                structure_id,
+            ): ([String; 7], Option<String>, Option<String>) = row;
+            Ok(Correction {
+                id,
+                file_path,
+                file_name,
+                extension,
+                suggested: suggested.and_then(|id| id.parse().ok()),
+                chosen: chosen.parse()?,
+                chosen_label,
+                structure_id,
+                created_at: chrono::DateTime::parse_from_rfc3339(&created_at)
+                    .map_err(|e| AppError::Unknown(format!("Date parse error: {}", e)))?
+                    .with_timezone(&chrono::Utc),
+            })
+        };
+
+        // A damaged row must not hide every other correction
+        let mut corrections = Vec::new();
+        for row in rows {
+            match row.map_err(AppError::from).and_then(parse) {
+                Ok(correction) => corrections.push(correction),
+                Err(e) => log::warn!("Skipping unreadable correction: {}", e),
+            }
+        }
+        Ok(corrections)
+    }
+
     // Settings operations
     pub async fn load_settings(&self) -> Result<AppSettings> {
         let conn = Connection::open(&self.db_path)?;
@@ -414,26 +1135,7 @@ impl DatabaseManager {
             }
             Err(_) => {
                 // Return default settings if not found
-                Ok(AppSettings {
-                    theme: "system".to_string(),
-                    auto_backup: true,
-                    backup_location: None,
-                    ai_provider: "ollama".to_string(),
-                    ai_model: "llama3.2:1b".to_string(),
-                    preview_mode: true,
-                    confirm_moves: true,
-                    max_file_size_mb: 1000,
-                    excluded_extensions: vec![
-                        "tmp".to_string(),
-                        "cache".to_string(),
-                        "log".to_string(),
-                    ],
-                    excluded_paths: vec![
-                        ".git".to_string(),
-                        "node_modules".to_string(),
-                        ".DS_Store".to_string(),
-                    ],
-                })
+                Ok(AppSettings::default())
             }
         }
     }
@@ -456,9 +1158,17 @@ impl DatabaseManager {
     }
 }
 
+/// Vectors are stored as little-endian `f32`s.
+fn vector_from_blob(blob: &[u8]) -> Vec<f32> {
+    blob.chunks_exact(4)
+        .map(|b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::templates::builtin_template;
     use std::path::Path;
     use tempfile::tempdir;
 
@@ -486,6 +1196,7 @@ mod tests {
         let mut custom_settings = settings;
         custom_settings.theme = "dark".to_string();
         custom_settings.max_file_size_mb = 500;
+        custom_settings.lint.disabled_rules = vec!["JD103".to_string()];
 
         manager.save_settings(&custom_settings).await.unwrap();
 
@@ -493,6 +1204,229 @@ mod tests {
         let loaded_settings = manager.load_settings().await.unwrap();
         assert_eq!(loaded_settings.theme, "dark");
         assert_eq!(loaded_settings.max_file_size_mb, 500);
+        assert_eq!(loaded_settings.lint.disabled_rules, vec!["JD103"]);
+    }
+
+    #[tokio::test]
+    async fn test_template_operations() {
+        let temp_dir = tempdir().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        let manager = DatabaseManager::new(db_path.to_str().unwrap()).unwrap();
+
+        let downloads = manager.load_template("downloads").await.unwrap().unwrap();
+        let structure = downloads.instantiate("/home/me/Downloads");
+
+        let template = StructureTemplate::from_structure("my-downloads", "Mine", "", &structure);
+        assert_eq!(manager.save_template(&template).await.unwrap().version, 1);
+        assert_eq!(manager.save_template(&template).await.unwrap().version, 2);
+        assert!(manager.save_template(&downloads).await.is_err());
+
+        let templates = manager.list_templates().await.unwrap();
+        assert!(templates
+            .iter()
+            .any(|t| t.id == "my-downloads" && !t.builtin));
+
+        manager.delete_template("my-downloads").await.unwrap();
+        assert!(manager
+            .load_template("my-downloads")
+            .await
+            .unwrap()
+            .is_none());
+    }
+
+    #[tokio::test]
+    async fn test_structure_revisions() {
+        let temp_dir = tempdir().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        let manager = DatabaseManager::new(db_path.to_str().unwrap()).unwrap();
+
+        let mut structure = builtin_template("downloads").unwrap().instantiate("/dl");
+        assert_eq!(manager.save_structure(&structure).await.unwrap(), 1);
+        assert_eq!(manager.save_structure(&structure).await.unwrap(), 1);
+
+        structure.areas[0].name = "10-19 Photos".to_string();
+        assert_eq!(manager.save_structure(&structure).await.unwrap(), 2);
+        assert_eq!(
+            manager.list_revisions(&structure.id).await.unwrap()[0].revision,
+            2
+        );
+
+        let diff = manager.diff_revisions(&structure.id, 1, 2).await.unwrap();
+        assert_eq!(diff.renamed[0].to, "Photos");
+
+        let restored = manager.restore_revision(&structure.id, 1).await.unwrap();
+        assert_eq!(restored.areas[0].label(), "Photography & Creative");
+        let current = manager
+            .load_structure(&structure.id)
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(current.areas[0].label(), "Photography & Creative");
+        assert_eq!(
+            manager.list_revisions(&structure.id).await.unwrap().len(),
+            3
+        );
+        assert!(manager.diff_revisions(&structure.id, 1, 9).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_analysis_cache() {
+        let temp_dir = tempdir().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        let manager = DatabaseManager::new(db_path.to_str().unwrap()).unwrap();
+
+        let mut structure = builtin_template("downloads").unwrap().instantiate("/dl");
+        manager.save_structure(&structure).await.unwrap();
+        let key = AnalysisCacheKey {
+            file_hash: "abc123".to_string(),
+            model: "ollama/llama3.2".to_string(),
+            prompt_version: 1,
+            organization_scheme: "JOHNNY_DECIMAL".to_string(),
+            prompt_hash: "def456".to_string(),
+            structure_id: Some(structure.id.clone()),
+            structure_revision: Some(1),
+        };
+        let result = AnalysisResult {
+            suggested_category: "10-19 Photos".to_string(),
+            confidence: 0.9,
+            reasoning: "A photo".to_string(),
+            alternative_categories: vec![],
+            tags: vec![],
+            assignment: None,
+            proposed_item: None,
+        };
+
+        assert!(manager.get_cached_analysis(&key).await.unwrap().is_none());
+        manager.cache_analysis(&key, &result).await.unwrap();
+        let cached = manager.get_cached_analysis(&key).await.unwrap().unwrap();
+        assert_eq!(cached.suggested_category, "10-19 Photos");
+
+        // Any change to the key misses
+        let other_model = AnalysisCacheKey {
+            model: "ollama/qwen2.5".to_string(),
+            ..key.clone()
+        };
+        assert!(manager
+            .get_cached_analysis(&other_model)
+            .await
+            .unwrap()
+            .is_none());
+
+        let stats = manager.analysis_cache_stats().await.unwrap();
+        assert_eq!((stats.entries, stats.hits, stats.misses), (1, 1, 2));
+        assert!((stats.hit_rate - 1.0 / 3.0).abs() < 1e-9);
+
+        // A new revision makes the entry outdated
+        let outdated = CachePurge::Outdated {
+            prompt_version: 1,
+            model: None,
+        };
+        assert_eq!(
+            manager
+                .purge_analysis_cache(outdated.clone())
+                .await
+                .unwrap(),
+            0
+        );
+        structure.areas[0].name = "10-19 Pictures".to_string();
+        manager.save_structure(&structure).await.unwrap();
+        assert_eq!(manager.purge_analysis_cache(outdated).await.unwrap(), 1);
+
+        manager.cache_analysis(&key, &result).await.unwrap();
+        assert_eq!(
+            manager.purge_analysis_cache(CachePurge::All).await.unwrap(),
+            1
+        );
+        assert_eq!(manager.analysis_cache_stats().await.unwrap().hits, 0);
+    }
+
+    #[tokio::test]
+    async fn test_embeddings() {
+        let temp_dir = tempdir().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        let manager = DatabaseManager::new(db_path.to_str().unwrap()).unwrap();
+
+        let embedding = |key: &str, model: &str| StoredEmbedding {
+            key: key.to_string(),
+            model: model.to_string(),
+            text_hash: format!("hash-{}", key),
+            vector: vec![0.5, -1.0, 2.25],
+        };
+        for stored in [
+            embedding("/a", "m1"),
+            embedding("/b", "m1"),
+            embedding("/a", "m2"),
+        ] {
+            manager.save_embedding("files", &stored).await.unwrap();
+        }
+
+        let loaded = manager
+            .load_embedding("files", "/a", "m1")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(loaded.vector, vec![0.5, -1.0, 2.25]);
+        assert_eq!(loaded.text_hash, "hash-/a");
+        assert!(manager
+            .load_embedding("files", "/c", "m1")
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(
+            manager.load_embeddings("files", "m1").await.unwrap().len(),
+            2
+        );
+    }
+
+    #[tokio::test]
+    async fn test_corrections() {
+        let temp_dir = tempdir().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        let manager = DatabaseManager::new(db_path.to_str().unwrap()).unwrap();
+
+        let mut older = Correction::new(
+            "/dl/invoice.pdf",
+            None,
+            "21.03".parse().unwrap(),
+            "21.03 Invoices",
+            Some("home"),
+        );
+        older.created_at -= chrono::Duration::hours(1);
+        manager.save_correction(&older).await.unwrap();
+        let newer = Correction::new(
+            "/dl/photo.jpg",
+            None,
+            "31.01".parse().unwrap(),
+            "31.01 Photos",
+            Some("work"),
+        );
+        manager.save_correction(&newer).await.unwrap();
+
+        let all = manager.recent_corrections(None, 10).await.unwrap();
+        assert_eq!(all[0].file_name, "photo.jpg");
+        assert_eq!(all[1].chosen.to_string(), "21.03");
+        let home = manager.recent_corrections(Some("home"), 10).await.unwrap();
+        assert_eq!(home.len(), 1);
+
+        // A damaged row is skipped, the others still come back
+        Connection::open(&db_path)
+            .unwrap()
+            .execute(
+                "UPDATE corrections SET created_at = 'yesterday' WHERE structure_id = 'home'",
+                [],
+            )
+            .unwrap();
+        let readable = manager.recent_corrections(None, 10).await.unwrap();
+        assert_eq!(readable.len(), 1);
+        assert_eq!(readable[0].structure_id.as_deref(), Some("work"));
+        assert_eq!(
+            manager
+                .recent_corrections(Some("work"), 10)
+                .await
+                .unwrap()
+                .len(),
+            1
+        );
     }
 
     #[tokio::test]
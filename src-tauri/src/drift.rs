@@ -0,0 +1,635 @@
+use crate::error::{AppError, Result};
+use crate::jd_id::{FolderLevel, JDId, JDNumber};
+use crate::johnny_decimal::{JDArea, JDCategory, JDItem, JDStructure};
+use crate::organization_plan::{OrganizationPlan, PlannedOperation};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// A recorded file that turned up at a different path. The IDs differ when
+/// the file was moved to another item; they match when only a folder on the
+/// way was renamed.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MovedFile {
+    pub recorded_path: String,
+    pub actual_path: String,
+    pub recorded_id: String,
+    pub actual_id: String,
+}
+
+/// A file inside a JD item folder that the structure does not know about.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UntrackedFile {
+    pub path: String,
+    pub item_id: String,
+}
+
+/// A folder under the root that does not correspond to anything in the
+/// structure. `jd_id` is set when the folder name still follows JD numbering.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnknownFolder {
+    pub path: String,
+    pub level: Option<FolderLevel>,
+    pub jd_id: Option<String>,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RenamedFolder {
+    pub level: FolderLevel,
+    pub jd_id: String,
+    pub expected_path: String,
+    pub actual_path: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DriftReport {
+    pub structure_id: String,
+    pub root_path: String,
+    pub missing_files: Vec<String>,
+    pub moved_files: Vec<MovedFile>,
+    pub untracked_files: Vec<UntrackedFile>,
+    pub unknown_folders: Vec<UnknownFolder>,
+    pub renamed_folders: Vec<RenamedFolder>,
+    pub checked_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[allow(dead_code)]
+impl DriftReport {
+    pub fn has_drift(&self) -> bool {
+        !self.missing_files.is_empty()
+            || !self.moved_files.is_empty()
+            || !self.untracked_files.is_empty()
+            || !self.unknown_folders.is_empty()
+            || !self.renamed_folders.is_empty()
+    }
+}
+
+/// Compares a saved structure against the folders and files under its root.
+pub async fn detect_drift(structure: &JDStructure) -> Result<DriftReport> {
+    let root = Path::new(&structure.root_path);
+    if !root.is_dir() {
+        return Err(AppError::directory_not_found(&structure.root_path));
+    }
+
+    let mut report = DriftReport {
+        structure_id: structure.id.clone(),
+        root_path: structure.root_path.clone(),
+        missing_files: Vec::new(),
+        moved_files: Vec::new(),
+        untracked_files: Vec::new(),
+        unknown_folders: Vec::new(),
+        renamed_folders: Vec::new(),
+        checked_at: chrono::Utc::now(),
+    };
+
+    // Item ID of every file found inside a JD item folder
+    let mut disk_files: Vec<(String, String)> = Vec::new();
+
+    for (area_dir, area_name) in subdirectories(root)? {
+        let Some((area_number, _)) = parse_area_folder(&area_name) else {
+            report
+                .unknown_folders
+                .push(unknown(&area_dir, None, None, &area_name));
+            continue;
+        };
+
+        let area = structure.areas.iter().find(|a| a.number == area_number);
+        match area {
+            Some(area) if area.folder_name() != area_name => {
+                report.renamed_folders.push(RenamedFolder {
+                    level: FolderLevel::Area,
+                    jd_id: format!("{}-{}", area_number, area_number + 9),
+                    expected_path: path_string(&structure.area_path(area)),
+                    actual_path: path_string(&area_dir),
+                })
+            }
+            Some(_) => {}
+            None => report.unknown_folders.push(unknown(
+                &area_dir,
+                Some(FolderLevel::Area),
+                Some(format!("{}-{}", area_number, area_number + 9)),
+                &area_name,
+            )),
+        }
+
+        for (category_dir, category_name) in subdirectories(&area_dir)? {
+            let category_number = match parse_category_folder(&category_name) {
+                Some((number, _)) if number >= area_number && number - area_number < 10 => number,
+                _ => {
+                    report
+                        .unknown_folders
+                        .push(unknown(&category_dir, None, None, &category_name));
+                    continue;
+                }
+            };
+
+            let category = area.and_then(|a| {
+                a.categories
+                    .iter()
+                    .find(|c| c.number == category_number)
+                    .map(|c| (a, c))
+            });
+            match category {
+                Some((area, category)) if category.folder_name() != category_name => {
+                    report.renamed_folders.push(RenamedFolder {
+                        level: FolderLevel::Category,
+                        jd_id: format!("{:02}", category_number),
+                        expected_path: path_string(&structure.category_path(area, category)),
+                        actual_path: path_string(&category_dir),
+                    })
+                }
+                Some(_) => {}
+                None => report.unknown_folders.push(unknown(
+                    &category_dir,
+                    Some(FolderLevel::Category),
+                    Some(format!("{:02}", category_number)),
+                    &category_name,
+                )),
+            }
+
+            for (item_dir, item_name) in subdirectories(&category_dir)? {
+                let item_id = match parse_item_folder(&item_name) {
+                    Some((id, _)) if id.starts_with(&format!("{:02}.", category_number)) => id,
+                    _ => {
+                        report
+                            .unknown_folders
+                            .push(unknown(&item_dir, None, None, &item_name));
+                        continue;
+                    }
+                };
+
+                let item = category.and_then(|(area, c)| {
+                    c.items
+                        .iter()
+                        .find(|i| i.number == item_id)
+                        .map(|i| (area, c, i))
+                });
+                match item {
+                    Some((area, category, item)) if item.folder_name() != item_name => {
+                        report.renamed_folders.push(RenamedFolder {
+                            level: FolderLevel::Item,
+                            jd_id: item_id.clone(),
+                            expected_path: path_string(&structure.item_path(area, category, item)),
+                            actual_path: path_string(&item_dir),
+                        })
+                    }
+                    Some(_) => {}
+                    None => report.unknown_folders.push(unknown(
+                        &item_dir,
+                        Some(FolderLevel::Item),
+                        Some(item_id.clone()),
+                        &item_name,
+                    )),
+                }
+
+                for entry in WalkDir::new(&item_dir).into_iter().filter_map(|e| e.ok()) {
+                    if entry.file_type().is_file() {
+                        disk_files.push((path_string(entry.path()), item_id.clone()));
+                    }
+                }
+            }
+        }
+    }
+
+    // Recorded files that are no longer where the structure says they are
+    let mut recorded = HashSet::new();
+    let mut vanished: Vec<(String, String)> = Vec::new();
+    for item in structure
+        .areas
+        .iter()
+        .flat_map(|a| &a.categories)
+        .flat_map(|c| &c.items)
+    {
+        for file in &item.files {
+            recorded.insert(file.clone());
+            if !Path::new(file).exists() {
+                vanished.push((file.clone(), item.number.clone()));
+            }
+        }
+    }
+
+    for (path, item_id) in disk_files {
+        if recorded.contains(&path) {
+            continue;
+        }
+
+        // Prefer a vanished file from the same item, then any with the same name
+        let name = Path::new(&path).file_name();
+        let matched = vanished
+            .iter()
+            .position(|(v, id)| *id == item_id && Path::new(v).file_name() == name)
+            .or_else(|| {
+                vanished
+                    .iter()
+                    .position(|(v, _)| Path::new(v).file_name() == name)
+            });
+
+        match matched {
+            Some(index) => {
+                let (recorded_path, recorded_id) = vanished.remove(index);
+                report.moved_files.push(MovedFile {
+                    recorded_path,
+                    actual_path: path,
+                    recorded_id,
+                    actual_id: item_id,
+                });
+            }
+            None => report.untracked_files.push(UntrackedFile { path, item_id }),
+        }
+    }
+
+    report.missing_files = vanished.into_iter().map(|(path, _)| path).collect();
+
+    Ok(report)
+}
+
+/// Updates the structure to match what is on disk: folder names, file
+/// locations and hand-made JD folders are adopted, missing files dropped.
+pub fn reconcile_structure_from_disk(structure: &JDStructure, report: &DriftReport) -> JDStructure {
+    let mut reconciled = structure.clone();
+
+    for renamed in &report.renamed_folders {
+        let folder_name = folder_name_of(&renamed.actual_path);
+        match renamed.level {
+            FolderLevel::Area => {
+                if let Some((number, _)) = parse_area_folder(&folder_name) {
+                    if let Some(area) = reconciled.areas.iter_mut().find(|a| a.number == number) {
+                        area.name = folder_name;
+                    }
+                }
+            }
+            FolderLevel::Category => {
+                if let Some((number, label)) = parse_category_folder(&folder_name) {
+                    if let Some(category) = reconciled
+                        .areas
+                        .iter_mut()
+                        .flat_map(|a| &mut a.categories)
+                        .find(|c| c.number == number)
+                    {
+                        category.name = label.to_string();
+                    }
+                }
+            }
+            FolderLevel::Item => {
+                if let Some((id, label)) = parse_item_folder(&folder_name) {
+                    if let Some(item) = find_item_mut(&mut reconciled, &id) {
+                        item.name = label.to_string();
+                    }
+                }
+            }
+        }
+    }
+
+    // Hand-made JD folders become areas, categories and items
+    for folder in &report.unknown_folders {
+        let folder_name = folder_name_of(&folder.path);
+        match folder.level {
+            Some(FolderLevel::Area) => {
+                if let Some((number, _)) = parse_area_folder(&folder_name) {
+                    reconciled.areas.push(JDArea {
+                        number,
+                        name: folder_name,
+                        description: None,
+                        categories: Vec::new(),
+                    });
+                }
+            }
+            Some(FolderLevel::Category) => {
+                if let Some((number, label)) = parse_category_folder(&folder_name) {
+                    if let Some(area) = reconciled
+                        .areas
+                        .iter_mut()
+                        .find(|a| number >= a.number && number - a.number < 10)
+                    {
+                        area.categories.push(JDCategory {
+                            number,
+                            name: label.to_string(),
+                            description: None,
+                            items: Vec::new(),
+                        });
+                    }
+                }
+            }
+            Some(FolderLevel::Item) => {
+                if let Some((id, label)) = parse_item_folder(&folder_name) {
+                    if let Some(category) = reconciled
+                        .areas
+                        .iter_mut()
+                        .flat_map(|a| &mut a.categories)
+                        .find(|c| id.starts_with(&format!("{:02}.", c.number)))
+                    {
+                        category.items.push(JDItem {
+                            number: id,
+                            name: label.to_string(),
+                            description: None,
+                            files: Vec::new(),
+                        });
+                    }
+                }
+            }
+            None => {}
+        }
+    }
+
+    let missing: HashSet<&String> = report.missing_files.iter().collect();
+    let moved: HashSet<&String> = report
+        .moved_files
+        .iter()
+        .map(|m| &m.recorded_path)
+        .collect();
+    for item in reconciled
+        .areas
+        .iter_mut()
+        .flat_map(|a| &mut a.categories)
+        .flat_map(|c| &mut c.items)
+    {
+        item.files
+            .retain(|f| !missing.contains(f) && !moved.contains(f));
+    }
+
+    let arrivals = report
+        .moved_files
+        .iter()
+        .map(|m| (&m.actual_id, &m.actual_path))
+        .chain(report.untracked_files.iter().map(|u| (&u.item_id, &u.path)));
+    for (item_id, path) in arrivals {
+        if let Some(item) = find_item_mut(&mut reconciled, item_id) {
+            item.files.push(path.clone());
+        }
+    }
+
+    reconciled.areas.sort_by_key(|a| a.number);
+    for area in &mut reconciled.areas {
+        area.categories.sort_by_key(|c| c.number);
+        for category in &mut area.categories {
+            category.items.sort_by(|a, b| a.number.cmp(&b.number));
+        }
+    }
+    reconciled.modified_at = chrono::Utc::now();
+
+    reconciled
+}
+
+/// Plans the disk changes that put renamed folders and moved files back
+/// where the structure expects them. Missing files cannot be restored and
+/// untracked files and unknown folders are left alone.
+pub fn plan_disk_reconciliation(structure: &JDStructure, report: &DriftReport) -> OrganizationPlan {
+    let mut plan = OrganizationPlan::new(&structure.root_path);
+
+    // Parents first, so child paths can be rewritten under their new parent
+    let mut renamed: Vec<&RenamedFolder> = report.renamed_folders.iter().collect();
+    renamed.sort_by_key(|r| r.level);
+
+    let mut applied: Vec<(PathBuf, PathBuf)> = Vec::new();
+    for folder in renamed {
+        let source = rewrite_path(Path::new(&folder.actual_path), &applied);
+        let destination = PathBuf::from(&folder.expected_path);
+        if source != destination {
+            plan.operations.push(PlannedOperation::RenameDirectory {
+                source: path_string(&source),
+                destination: path_string(&destination),
+            });
+            applied.push((source, destination));
+        }
+    }
+
+    for moved in &report.moved_files {
+        let source = rewrite_path(Path::new(&moved.actual_path), &applied);
+        if path_string(&source) != moved.recorded_path {
+            plan.operations.push(PlannedOperation::MoveFile {
+                source: path_string(&source),
+                destination: moved.recorded_path.clone(),
+            });
+        }
+    }
+
+    plan
+}
+
+/// Parses `20-29 Documents` into `(20, "Documents")`.
+pub fn parse_area_folder(name: &str) -> Option<(u8, &str)> {
+    match parse_folder(name)? {
+        (JDNumber::Area(number), label) => Some((number, label)),
+        _ => None,
+    }
+}
+
+/// Parses `21 Reports` into `(21, "Reports")`.
+pub fn parse_category_folder(name: &str) -> Option<(u8, &str)> {
+    match parse_folder(name)? {
+        (JDNumber::Category(number), label) => Some((number, label)),
+        _ => None,
+    }
+}
+
+/// Parses `21.01 Invoices` into `("21.01", "Invoices")`.
+pub fn parse_item_folder(name: &str) -> Option<(String, &str)> {
+    match parse_folder(name)? {
+        (number @ JDNumber::Item { .. }, label) => Some((
+            JDId {
+                system: None,
+                number,
+            }
+            .to_string(),
+            label,
+        )),
+        _ => None,
+    }
+}
+
+/// Folder names never carry the system prefix.
+fn parse_folder(name: &str) -> Option<(JDNumber, &str)> {
+    let (id, label) = name.split_once(' ').unwrap_or((name, ""));
+    let id: JDId = id.parse().ok()?;
+    id.system.is_none().then_some((id.number, label))
+}
+
+fn subdirectories(dir: &Path) -> Result<Vec<(PathBuf, String)>> {
+    let mut dirs = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            dirs.push((
+                entry.path(),
+                entry.file_name().to_string_lossy().to_string(),
+            ));
+        }
+    }
+    dirs.sort();
+    Ok(dirs)
+}
+
+fn unknown(
+    path: &Path,
+    level: Option<FolderLevel>,
+    jd_id: Option<String>,
+    name: &str,
+) -> UnknownFolder {
+    UnknownFolder {
+        path: path_string(path),
+        level,
+        jd_id,
+        name: name.to_string(),
+    }
+}
+
+fn find_item_mut<'a>(structure: &'a mut JDStructure, item_id: &str) -> Option<&'a mut JDItem> {
+    structure
+        .areas
+        .iter_mut()
+        .flat_map(|a| &mut a.categories)
+        .flat_map(|c| &mut c.items)
+        .find(|i| i.number == item_id)
+}
+
+fn rewrite_path(path: &Path, renames: &[(PathBuf, PathBuf)]) -> PathBuf {
+    renames
+        .iter()
+        .fold(path.to_path_buf(), |current, (from, to)| {
+            match current.strip_prefix(from) {
+                Ok(rest) if rest.as_os_str().is_empty() => to.clone(),
+                Ok(rest) => to.join(rest),
+                Err(_) => current,
+            }
+        })
+}
+
+fn folder_name_of(path: &str) -> String {
+    Path::new(path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+fn path_string(path: &Path) -> String {
+    path.to_string_lossy().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_operations::FileOperations;
+    use tempfile::TempDir;
+
+    async fn materialized_structure(root: &Path) -> JDStructure {
+        let file = |name: &str| root.join(name).to_string_lossy().to_string();
+        for name in ["a.pdf", "b.pdf", "c.pdf"] {
+            std::fs::write(root.join(name), name).unwrap();
+        }
+
+        let mut structure = JDStructure {
+            id: "test".to_string(),
+            name: "Test".to_string(),
+            root_path: path_string(root),
+            system: None,
+            locale: None,
+            areas: vec![JDArea {
+                number: 20,
+                name: "20-29 Documents".to_string(),
+                description: None,
+                categories: vec![JDCategory {
+                    number: 21,
+                    name: "Reports".to_string(),
+                    description: None,
+                    items: vec![
+                        JDItem {
+                            number: "21.01".to_string(),
+                            name: "Invoices".to_string(),
+                            description: None,
+                            files: vec![file("a.pdf"), file("b.pdf")],
+                        },
+                        JDItem {
+                            number: "21.02".to_string(),
+                            name: "Receipts".to_string(),
+                            description: None,
+                            files: vec![file("c.pdf")],
+                        },
+                    ],
+                }],
+            }],
+            created_at: chrono::Utc::now(),
+            modified_at: chrono::Utc::now(),
+        };
+
+        let plan = OrganizationPlan::for_structure(&structure);
+        FileOperations::new()
+            .unwrap()
+            .execute_plan(&plan)
+            .await
+            .unwrap();
+        crate::organization_plan::relocate_files(&mut structure);
+        structure
+    }
+
+    #[tokio::test]
+    async fn test_detect_drift() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let structure = materialized_structure(root).await;
+        let category = root.join("20-29 Documents/21 Reports");
+
+        std::fs::rename(
+            category.join("21.01 Invoices/a.pdf"),
+            category.join("21.02 Receipts/a.pdf"),
+        )
+        .unwrap();
+        std::fs::remove_file(category.join("21.01 Invoices/b.pdf")).unwrap();
+        std::fs::write(category.join("21.02 Receipts/new.pdf"), "new").unwrap();
+        std::fs::create_dir(root.join("Random")).unwrap();
+        std::fs::rename(&category, root.join("20-29 Documents/21 Paperwork")).unwrap();
+
+        let report = detect_drift(&structure).await.unwrap();
+
+        assert_eq!(report.missing_files.len(), 1);
+        assert!(report.missing_files[0].ends_with("b.pdf"));
+        assert_eq!(report.renamed_folders.len(), 1);
+        assert_eq!(report.renamed_folders[0].level, FolderLevel::Category);
+        assert_eq!(report.untracked_files.len(), 1);
+        assert_eq!(report.unknown_folders.len(), 1);
+        assert_eq!(report.unknown_folders[0].name, "Random");
+
+        let moved: Vec<_> = report
+            .moved_files
+            .iter()
+            .filter(|m| m.recorded_id != m.actual_id)
+            .collect();
+        assert_eq!(moved.len(), 1);
+        assert_eq!(moved[0].recorded_id, "21.01");
+        assert_eq!(moved[0].actual_id, "21.02");
+
+        let reconciled = reconcile_structure_from_disk(&structure, &report);
+        let after = detect_drift(&reconciled).await.unwrap();
+        assert!(after.missing_files.is_empty());
+        assert!(after.moved_files.is_empty());
+        assert!(after.untracked_files.is_empty());
+        assert!(after.renamed_folders.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_plan_disk_reconciliation() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        let structure = materialized_structure(root).await;
+        let category = root.join("20-29 Documents/21 Reports");
+
+        std::fs::rename(
+            category.join("21.01 Invoices/a.pdf"),
+            category.join("21.02 Receipts/a.pdf"),
+        )
+        .unwrap();
+        std::fs::rename(&category, root.join("20-29 Documents/21 Paperwork")).unwrap();
+
+        let report = detect_drift(&structure).await.unwrap();
+        let plan = plan_disk_reconciliation(&structure, &report);
+        FileOperations::new()
+            .unwrap()
+            .execute_plan(&plan)
+            .await
+            .unwrap();
+
+        let after = detect_drift(&structure).await.unwrap();
+        assert!(!after.has_drift());
+    }
+}
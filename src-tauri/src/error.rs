@@ -19,8 +19,11 @@ pub enum AppError {
     #[error("AI service error: {0}")]
     AiService(String),
 
-    #[error("Ollama error: {0}")]
-    Ollama(String),
+    #[error("Ollama error: {message}")]
+    Ollama {
+        message: String,
+        raw_reply: Option<String>, // Model output that caused the error, for debugging
+    },
 
     #[error("HTTP request error: {0}")]
     Http(#[from] reqwest::Error),
@@ -74,6 +77,34 @@ impl AppError {
         AppError::AiService("AI service is not available".to_string())
     }
 
+    /// Failures worth retrying: timeouts, refused connections, overload and
+    /// server errors.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            AppError::Http(e) => {
+                e.is_timeout()
+                    || e.is_connect()
+                    || e.status().is_some_and(|s| {
+                        s.is_server_error() || s == reqwest::StatusCode::TOO_MANY_REQUESTS
+                    })
+            }
+            AppError::Io(e) => matches!(
+                e.kind(),
+                std::io::ErrorKind::TimedOut
+                    | std::io::ErrorKind::ConnectionRefused
+                    | std::io::ErrorKind::ConnectionReset
+            ),
+            _ => false,
+        }
+    }
+
+    pub fn invalid_reply(reason: impl std::fmt::Display, raw_reply: &str) -> Self {
+        AppError::Ollama {
+            message: format!("Reply does not match the schema: {}", reason),
+            raw_reply: Some(raw_reply.to_string()),
+        }
+    }
+
     pub fn invalid_johnny_decimal(reason: &str) -> Self {
         AppError::JohnnyDecimalValidation(format!("Invalid Johnny Decimal structure: {}", reason))
     }
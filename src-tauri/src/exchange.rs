@@ -0,0 +1,465 @@
+use crate::error::{AppError, Result};
+use crate::johnny_decimal::{
+    free_category_number, next_free_area, JDArea, JDCategory, JDItem, JDRepairAction, JDStructure,
+    JDValidationResult, JohnnyDecimalEngine,
+};
+use crate::organization_plan::OrganizationPlan;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::HashSet;
+use std::path::Path;
+use uuid::Uuid;
+
+/// Value of the `format` field of every `.jdstructure` file.
+pub const EXCHANGE_FORMAT: &str = "jdstructure";
+pub const EXCHANGE_FILE_EXTENSION: &str = "jdstructure";
+/// Bump together with a new entry in `MIGRATIONS`.
+pub const EXCHANGE_FORMAT_VERSION: u32 = 1;
+
+/// `MIGRATIONS[n]` upgrades a version `n` document to version `n + 1`.
+const MIGRATIONS: &[fn(Value) -> Result<Value>] = &[migrate_v0_to_v1];
+
+/// A `.jdstructure` document. See `docs/JDStructure_Exchange_Format.md`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ExchangeFile {
+    pub format: String,
+    pub version: u32,
+    pub exported_at: chrono::DateTime<chrono::Utc>,
+    pub structure: JDStructure, // File paths are relative to root_path
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportResult {
+    pub structure: JDStructure,
+    pub migrated_from: u32,
+    pub remapped: Vec<JDRepairAction>,
+    pub validation: JDValidationResult,
+    pub plan: OrganizationPlan, // Moves that follow the remapping on disk
+}
+
+/// Serializes a structure as a `.jdstructure` document. Without
+/// `include_files` only the taxonomy is shared.
+pub fn export_structure(structure: &JDStructure, include_files: bool) -> Result<String> {
+    let root = Path::new(&structure.root_path);
+    let mut shared = structure.clone();
+
+    for item in items_mut(&mut shared) {
+        if include_files {
+            for file in &mut item.files {
+                if let Ok(relative) = Path::new(file.as_str()).strip_prefix(root) {
+                    *file = relative.to_string_lossy().replace('\\', "/");
+                }
+            }
+        } else {
+            item.files.clear();
+        }
+    }
+
+    let document = ExchangeFile {
+        format: EXCHANGE_FORMAT.to_string(),
+        version: EXCHANGE_FORMAT_VERSION,
+        exported_at: chrono::Utc::now(),
+        structure: shared,
+    };
+    Ok(serde_json::to_string_pretty(&document)?)
+}
+
+/// Reads a `.jdstructure` document of any known version, upgrading it to the
+/// current one.
+pub fn parse_exchange_file(data: &str) -> Result<(ExchangeFile, u32)> {
+    let mut value: Value = serde_json::from_str(data)?;
+    let version = document_version(&value)?;
+
+    if version > EXCHANGE_FORMAT_VERSION {
+        return Err(AppError::InvalidInput(format!(
+            "Structure file version {} is newer than the supported version {}",
+            version, EXCHANGE_FORMAT_VERSION
+        )));
+    }
+    for migration in &MIGRATIONS[version as usize..] {
+        value = migration(value)?;
+    }
+
+    Ok((serde_json::from_value(value)?, version))
+}
+
+/// Imports a shared structure as a new structure rooted at `root_path`.
+/// Invalid numbering is repaired and reported in `remapped`; `plan` moves
+/// the files to the renumbered folders.
+pub async fn import_structure(
+    engine: &JohnnyDecimalEngine,
+    data: &str,
+    root_path: &str,
+) -> Result<ImportResult> {
+    let (document, migrated_from) = parse_exchange_file(data)?;
+    let mut structure = rebase(document.structure, root_path);
+    let mut remapped = Vec::new();
+    let mut plan = OrganizationPlan::new(root_path);
+
+    // Repairing also moves the recorded files into the renumbered folders
+    let validation = engine.validate_structure(&structure).await?;
+    if !validation.is_valid {
+        let repair = engine.repair_structure(&structure).await?;
+        remapped = repair.actions;
+        structure = repair.structure;
+        plan = repair.plan;
+    }
+
+    finish(engine, structure, migrated_from, remapped, plan).await
+}
+
+/// Merges a shared taxonomy into `local`. Areas, categories and items are
+/// matched by name; everything new gets a free number when its own is taken.
+/// Local items keep their files.
+pub async fn merge_structure(
+    engine: &JohnnyDecimalEngine,
+    local: &JDStructure,
+    data: &str,
+) -> Result<ImportResult> {
+    let (document, migrated_from) = parse_exchange_file(data)?;
+    let incoming = rebase(document.structure, &local.root_path);
+
+    let mut merged = local.clone();
+    let mut remapped = Vec::new();
+    let mut used_areas: HashSet<u8> = merged.areas.iter().map(|a| a.number).collect();
+    let mut used_categories: HashSet<u8> = merged
+        .areas
+        .iter()
+        .flat_map(|a| a.categories.iter().map(|c| c.number))
+        .collect();
+
+    for area in incoming.areas {
+        let area_index = match merged
+            .areas
+            .iter()
+            .position(|a| same_name(a.label(), area.label()))
+        {
+            Some(index) => index,
+            None => {
+                let number = if used_areas.contains(&area.number) {
+                    let number = next_free_area(&used_areas)?;
+                    remapped.push(JDRepairAction::AreaRenumbered {
+                        from: area.number,
+                        to: number,
+                    });
+                    number
+                } else {
+                    area.number
+                };
+                used_areas.insert(number);
+                merged.areas.push(JDArea {
+                    number,
+                    name: format!("{}-{} {}", number, number + 9, area.label()),
+                    description: area.description.clone(),
+                    categories: Vec::new(),
+                });
+                merged.areas.len() - 1
+            }
+        };
+
+        for category in area.categories {
+            let target = &mut merged.areas[area_index];
+            let category_index = match target
+                .categories
+                .iter()
+                .position(|c| same_name(&c.name, &category.name))
+            {
+                Some(index) => index,
+                None => {
+                    let wanted = target.number + category.number.saturating_sub(area.number) % 10;
+                    let number = if used_categories.contains(&wanted) {
+                        free_category_number(target.number, &used_categories).ok_or_else(|| {
+                            AppError::invalid_johnny_decimal(&format!(
+                                "area {} has no free category for {}",
+                                target.number, category.name
+                            ))
+                        })?
+                    } else {
+                        wanted
+                    };
+                    if number != category.number {
+                        remapped.push(JDRepairAction::CategoryRenumbered {
+                            area_number: target.number,
+                            from: category.number,
+                            to: number,
+                        });
+                    }
+                    used_categories.insert(number);
+                    target.categories.push(JDCategory {
+                        number,
+                        name: category.name.clone(),
+                        description: category.description.clone(),
+                        items: Vec::new(),
+                    });
+                    target.categories.len() - 1
+                }
+            };
+
+            let target = &mut target.categories[category_index];
+            for item in category.items {
+                if let Some(existing) = target
+                    .items
+                    .iter_mut()
+                    .find(|i| same_name(&i.name, &item.name))
+                {
+                    for file in item.files {
+                        if !existing.files.contains(&file) {
+                            existing.files.push(file);
+                        }
+                    }
+                    continue;
+                }
+
+                let number = format!("{}.{:02}", target.number, next_item_index(target));
+                if number != item.number {
+                    remapped.push(JDRepairAction::ItemRenumbered {
+                        from: item.number.clone(),
+                        to: number.clone(),
+                    });
+                }
+                target.items.push(JDItem { number, ..item });
+            }
+        }
+    }
+
+    merged.areas.sort_by_key(|a| a.number);
+    for area in &mut merged.areas {
+        area.categories.sort_by_key(|c| c.number);
+    }
+    merged.modified_at = chrono::Utc::now();
+
+    let plan = OrganizationPlan::new(&merged.root_path);
+    finish(engine, merged, migrated_from, remapped, plan).await
+}
+
+async fn finish(
+    engine: &JohnnyDecimalEngine,
+    structure: JDStructure,
+    migrated_from: u32,
+    remapped: Vec<JDRepairAction>,
+    plan: OrganizationPlan,
+) -> Result<ImportResult> {
+    let validation = engine.validate_structure(&structure).await?;
+    if !validation.is_valid {
+        let reasons: Vec<_> = validation
+            .errors
+            .iter()
+            .map(|e| e.message.as_str())
+            .collect();
+        return Err(AppError::invalid_johnny_decimal(&reasons.join("; ")));
+    }
+
+    Ok(ImportResult {
+        structure,
+        migrated_from,
+        remapped,
+        validation,
+        plan,
+    })
+}
+
+/// Documents without a `format` field are bare `JDStructure` JSON, the
+/// unversioned format saved before `.jdstructure` existed.
+fn document_version(value: &Value) -> Result<u32> {
+    match value.get("format").and_then(Value::as_str) {
+        Some(EXCHANGE_FORMAT) => value
+            .get("version")
+            .and_then(Value::as_u64)
+            .map(|v| v as u32)
+            .ok_or_else(|| AppError::InvalidInput("Structure file has no version".to_string())),
+        Some(other) => Err(AppError::InvalidInput(format!(
+            "Unsupported file format: {}",
+            other
+        ))),
+        None if value.get("areas").is_some() => Ok(0),
+        None => Err(AppError::InvalidInput(
+            "Not a Johnny Decimal structure file".to_string(),
+        )),
+    }
+}
+
+/// Wraps a bare structure in the versioned envelope and makes its absolute
+/// file paths relative to `root_path`.
+fn migrate_v0_to_v1(mut structure: Value) -> Result<Value> {
+    let root = structure
+        .get("root_path")
+        .and_then(Value::as_str)
+        .unwrap_or_default()
+        .to_string();
+
+    let areas = structure.get_mut("areas").and_then(Value::as_array_mut);
+    for area in areas.into_iter().flatten() {
+        let categories = area.get_mut("categories").and_then(Value::as_array_mut);
+        for category in categories.into_iter().flatten() {
+            let items = category.get_mut("items").and_then(Value::as_array_mut);
+            for item in items.into_iter().flatten() {
+                let files = item.get_mut("files").and_then(Value::as_array_mut);
+                for file in files.into_iter().flatten() {
+                    if let Some(relative) = file
+                        .as_str()
+                        .and_then(|f| Path::new(f).strip_prefix(&root).ok())
+                    {
+                        *file = Value::String(relative.to_string_lossy().replace('\\', "/"));
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(serde_json::json!({
+        "format": EXCHANGE_FORMAT,
+        "version": 1,
+        "exported_at": chrono::Utc::now(),
+        "structure": structure,
+    }))
+}
+
+/// Gives an imported structure a fresh ID and resolves its relative file
+/// paths against `root_path`.
+fn rebase(mut structure: JDStructure, root_path: &str) -> JDStructure {
+    let root = Path::new(root_path);
+    for item in items_mut(&mut structure) {
+        for file in &mut item.files {
+            *file = root.join(file.as_str()).to_string_lossy().to_string();
+        }
+    }
+
+    let now = chrono::Utc::now();
+    JDStructure {
+        id: Uuid::new_v4().to_string(),
+        root_path: root_path.to_string(),
+        created_at: now,
+        modified_at: now,
+        ..structure
+    }
+}
+
+fn items_mut(structure: &mut JDStructure) -> impl Iterator<Item = &mut JDItem> {
+    structure
+        .areas
+        .iter_mut()
+        .flat_map(|a| a.categories.iter_mut())
+        .flat_map(|c| c.items.iter_mut())
+}
+
+fn next_item_index(category: &JDCategory) -> u32 {
+    category
+        .items
+        .iter()
+        .filter_map(|i| i.number.rsplit('.').next()?.parse::<u32>().ok())
+        .max()
+        .unwrap_or(0)
+        + 1
+}
+
+fn same_name(a: &str, b: &str) -> bool {
+    a.trim().eq_ignore_ascii_case(b.trim())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::organization_plan::PlannedOperation;
+    use crate::templates::builtin_template;
+
+    fn local_structure() -> JDStructure {
+        let mut structure = builtin_template("downloads")
+            .unwrap()
+            .instantiate("/home/me/Downloads");
+        structure.areas[1].categories[0].items[0]
+            .files
+            .push("/home/me/Downloads/visa.pdf".to_string());
+        structure
+    }
+
+    #[tokio::test]
+    async fn test_export_import_round_trip() {
+        let engine = JohnnyDecimalEngine::new().unwrap();
+        let local = local_structure();
+
+        let data = export_structure(&local, true).unwrap();
+        assert!(data.contains("\"version\": 1"));
+        assert!(data.contains("\"visa.pdf\""));
+
+        let imported = import_structure(&engine, &data, "/other").await.unwrap();
+        assert_eq!(imported.migrated_from, EXCHANGE_FORMAT_VERSION);
+        assert_ne!(imported.structure.id, local.id);
+        assert!(imported.remapped.is_empty());
+        assert!(imported.plan.is_empty());
+        assert_eq!(
+            imported.structure.areas[1].categories[0].items[0].files,
+            vec!["/other/visa.pdf".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_import_migrates_unversioned_structure() {
+        let engine = JohnnyDecimalEngine::new().unwrap();
+        let mut legacy = local_structure();
+        legacy.areas[1].number = 10; // Clashes with the first area
+
+        let data = serde_json::to_string(&legacy).unwrap();
+        let (document, version) = parse_exchange_file(&data).unwrap();
+        assert_eq!(version, 0);
+        assert_eq!(
+            document.structure.areas[1].categories[0].items[0].files,
+            vec!["visa.pdf".to_string()]
+        );
+
+        let imported = import_structure(&engine, &data, "/other").await.unwrap();
+        assert!(imported.validation.is_valid);
+        assert!(imported
+            .remapped
+            .iter()
+            .any(|a| matches!(a, JDRepairAction::AreaRenumbered { from: 10, .. })));
+        // The recorded file follows its item to the renumbered area and the
+        // plan moves it there from where the shared structure had it
+        let visa = "/other/20-29 Official Documents/21 Immigration & Visa Documents/21.01 Visa Documents/visa.pdf";
+        assert_eq!(
+            imported.structure.areas[1].categories[0].items[0].files,
+            vec![visa.to_string()]
+        );
+        assert!(imported
+            .plan
+            .operations
+            .contains(&PlannedOperation::MoveFile {
+                source: "/other/visa.pdf".to_string(),
+                destination: visa.to_string(),
+            }));
+    }
+
+    #[tokio::test]
+    async fn test_merge_keeps_local_files_and_remaps_conflicts() {
+        let engine = JohnnyDecimalEngine::new().unwrap();
+        let local = local_structure();
+
+        let mut shared = builtin_template("downloads")
+            .unwrap()
+            .instantiate("/shared");
+        shared.areas.truncate(2);
+        shared.areas[0].name = "10-19 Music".to_string(); // Number taken locally
+        shared.areas[1].categories[0].items.push(JDItem {
+            number: "21.05".to_string(),
+            name: "Residence Permits".to_string(),
+            description: None,
+            files: Vec::new(),
+        });
+        let data = export_structure(&shared, false).unwrap();
+
+        let merged = merge_structure(&engine, &local, &data).await.unwrap();
+        let areas = &merged.structure.areas;
+
+        assert_eq!(merged.structure.id, local.id);
+        assert_eq!(areas.len(), 5);
+        assert_eq!(areas[4].name, "50-59 Music");
+        assert_eq!(
+            areas[1].categories[0].items[0].files,
+            vec!["/home/me/Downloads/visa.pdf"]
+        );
+        assert_eq!(areas[1].categories[0].items[4].name, "Residence Permits");
+        assert!(merged
+            .remapped
+            .iter()
+            .any(|a| matches!(a, JDRepairAction::AreaRenumbered { from: 10, to: 50 })));
+    }
+}
@@ -1,4 +1,5 @@
 use crate::error::{AppError, Result};
+use crate::organization_plan::{OrganizationPlan, PlannedOperation};
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
@@ -26,6 +27,15 @@ pub struct FilePermissions {
     pub executable: bool,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlanExecutionReport {
+    pub plan_id: String,
+    pub executed: Vec<PlannedOperation>,
+    pub skipped: Vec<PlannedOperation>,
+    /// Reverts everything in `executed` when passed back to `execute_plan`
+    pub undo: OrganizationPlan,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ScanResult {
     pub files: Vec<FileMetadata>,
@@ -272,6 +282,86 @@ impl FileOperations {
 
         Ok(())
     }
+
+    /// Runs a plan operation by operation. If one fails, everything done so
+    /// far is rolled back before the error is returned.
+    pub async fn execute_plan(&self, plan: &OrganizationPlan) -> Result<PlanExecutionReport> {
+        let mut executed = Vec::new();
+        let mut skipped = Vec::new();
+
+        for operation in &plan.operations {
+            match self.execute_operation(operation).await {
+                Ok(true) => executed.push(operation.clone()),
+                Ok(false) => skipped.push(operation.clone()),
+                Err(e) => {
+                    log::error!("Plan {} failed at {:?}: {}", plan.id, operation, e);
+                    for done in executed.iter().rev() {
+                        if let Err(undo_error) = self.execute_operation(&done.inverse()).await {
+                            log::error!("Failed to roll back {:?}: {}", done, undo_error);
+                        }
+                    }
+                    return Err(e);
+                }
+            }
+        }
+
+        let mut undo = OrganizationPlan::new(&plan.root_path);
+        undo.operations = executed.iter().rev().map(|op| op.inverse()).collect();
+
+        Ok(PlanExecutionReport {
+            plan_id: plan.id.clone(),
+            executed,
+            skipped,
+            undo,
+        })
+    }
+
+    /// Returns `false` when the operation had nothing to do.
+    async fn execute_operation(&self, operation: &PlannedOperation) -> Result<bool> {
+        match operation {
+            PlannedOperation::CreateDirectory { path } => {
+                if PathBuf::from(path).is_dir() {
+                    return Ok(false);
+                }
+                self.create_directory(path).await?;
+            }
+            PlannedOperation::MoveFile {
+                source,
+                destination,
+            } => {
+                if PathBuf::from(destination).exists() {
+                    return Err(AppError::FileSystem(format!(
+                        "Destination already exists: {}",
+                        destination
+                    )));
+                }
+                self.move_file(source, destination, true).await?;
+            }
+            PlannedOperation::RenameDirectory {
+                source,
+                destination,
+            } => {
+                if PathBuf::from(destination).exists() {
+                    return Err(AppError::FileSystem(format!(
+                        "Destination already exists: {}",
+                        destination
+                    )));
+                }
+                fs::rename(source, destination)
+                    .await
+                    .map_err(AppError::Io)?;
+            }
+            PlannedOperation::RemoveDirectory { path } => {
+                let path_buf = PathBuf::from(path);
+                if !path_buf.is_dir() || std::fs::read_dir(&path_buf)?.next().is_some() {
+                    return Ok(false);
+                }
+                fs::remove_dir(path).await.map_err(AppError::Io)?;
+            }
+        }
+
+        Ok(true)
+    }
 }
 
 #[cfg(test)]
@@ -321,4 +411,33 @@ mod tests {
         assert!(dest.exists());
         assert!(source.exists()); // Should still exist after copy
     }
+
+    #[tokio::test]
+    async fn test_execute_plan_and_undo() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = temp_dir.path().join("report.pdf");
+        let target_dir = temp_dir.path().join("21 Reports");
+        let dest = target_dir.join("report.pdf");
+        File::create(&source).await.unwrap();
+
+        let mut plan = OrganizationPlan::new(temp_dir.path().to_str().unwrap());
+        plan.operations = vec![
+            PlannedOperation::CreateDirectory {
+                path: target_dir.to_string_lossy().to_string(),
+            },
+            PlannedOperation::MoveFile {
+                source: source.to_string_lossy().to_string(),
+                destination: dest.to_string_lossy().to_string(),
+            },
+        ];
+
+        let ops = FileOperations::new().unwrap();
+        let report = ops.execute_plan(&plan).await.unwrap();
+        assert_eq!(report.executed.len(), 2);
+        assert!(dest.exists());
+
+        ops.execute_plan(&report.undo).await.unwrap();
+        assert!(source.exists());
+        assert!(!target_dir.exists());
+    }
 }
@@ -0,0 +1,407 @@
+use crate::error::{AppError, Result};
+use crate::johnny_decimal::{is_valid_area_number, JDArea, JDCategory, JDItem, JDStructure};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FolderLevel {
+    Area,
+    Category,
+    Item,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum JDNumber {
+    Area(u8),                         // 10-19
+    Category(u8),                     // 11
+    Item { category: u8, index: u8 }, // 11.01
+}
+
+/// A Johnny Decimal ID such as `10-19`, `11`, `11.01` or, in a multi-system
+/// setup, `P01.11.01`. Serialized as its string form.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct JDId {
+    pub system: Option<String>, // e.g. P01, always upper case
+    pub number: JDNumber,
+}
+
+#[allow(dead_code)]
+impl JDId {
+    pub fn area(number: u8) -> Result<Self> {
+        if !is_valid_area_number(number) {
+            return Err(invalid_id(&number.to_string(), "areas are 10, 20, ..., 90"));
+        }
+        Ok(Self::local(JDNumber::Area(number)))
+    }
+
+    pub fn category(number: u8) -> Result<Self> {
+        if !(10..=99).contains(&number) {
+            return Err(invalid_id(&number.to_string(), "categories are 10-99"));
+        }
+        Ok(Self::local(JDNumber::Category(number)))
+    }
+
+    pub fn item(category: u8, index: u8) -> Result<Self> {
+        Self::category(category)?;
+        if !(1..=99).contains(&index) {
+            return Err(invalid_id(
+                &format!("{}.{:02}", category, index),
+                "items are 01-99",
+            ));
+        }
+        Ok(Self::local(JDNumber::Item { category, index }))
+    }
+
+    pub fn with_system(mut self, system: &str) -> Result<Self> {
+        if !is_system_code(system) {
+            return Err(invalid_id(system, "systems look like P01"));
+        }
+        self.system = Some(system.to_ascii_uppercase());
+        Ok(self)
+    }
+
+    pub fn level(&self) -> FolderLevel {
+        match self.number {
+            JDNumber::Area(_) => FolderLevel::Area,
+            JDNumber::Category(_) => FolderLevel::Category,
+            JDNumber::Item { .. } => FolderLevel::Item,
+        }
+    }
+
+    /// The area this ID belongs to, e.g. 10 for `11.01`.
+    pub fn area_number(&self) -> u8 {
+        match self.number {
+            JDNumber::Area(area) => area,
+            JDNumber::Category(category) | JDNumber::Item { category, .. } => category / 10 * 10,
+        }
+    }
+
+    pub fn category_number(&self) -> Option<u8> {
+        match self.number {
+            JDNumber::Area(_) => None,
+            JDNumber::Category(category) | JDNumber::Item { category, .. } => Some(category),
+        }
+    }
+
+    /// The ID without its system prefix, as used in folder names.
+    pub fn local_id(&self) -> String {
+        match self.number {
+            JDNumber::Area(area) => format!("{:02}-{:02}", area, area + 9),
+            JDNumber::Category(category) => format!("{:02}", category),
+            JDNumber::Item { category, index } => format!("{:02}.{:02}", category, index),
+        }
+    }
+
+    fn local(number: JDNumber) -> Self {
+        Self {
+            system: None,
+            number,
+        }
+    }
+}
+
+impl FromStr for JDId {
+    type Err = AppError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let s = s.trim();
+        let (system, local) = match s.split_once('.') {
+            Some((prefix, rest)) if is_system_code(prefix) => (Some(prefix), rest),
+            _ => (None, s),
+        };
+
+        let id = if let Some((start, end)) = local.split_once('-') {
+            let (start, end) = (two_digits(start, s)?, two_digits(end, s)?);
+            if end != start.saturating_add(9) {
+                return Err(invalid_id(s, "area ranges span ten numbers, e.g. 10-19"));
+            }
+            JDId::area(start)
+        } else if let Some((category, index)) = local.split_once('.') {
+            JDId::item(two_digits(category, s)?, two_digits(index, s)?)
+        } else {
+            JDId::category(two_digits(local, s)?)
+        }
+        .map_err(|_| invalid_id(s, "number out of range"))?;
+
+        match system {
+            Some(system) => id.with_system(system),
+            None => Ok(id),
+        }
+    }
+}
+
+impl fmt::Display for JDId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.system {
+            Some(system) => write!(f, "{}.{}", system, self.local_id()),
+            None => f.write_str(&self.local_id()),
+        }
+    }
+}
+
+impl TryFrom<String> for JDId {
+    type Error = AppError;
+
+    fn try_from(value: String) -> Result<Self> {
+        value.parse()
+    }
+}
+
+impl From<JDId> for String {
+    fn from(id: JDId) -> Self {
+        id.to_string()
+    }
+}
+
+/// Where an ID or name resolved to inside a structure.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JDLookup {
+    pub id: JDId,
+    pub level: FolderLevel,
+    pub name: String,
+    pub path: PathBuf,
+    pub score: f64, // 1.0 for ID and exact name matches
+}
+
+#[allow(dead_code)]
+impl JDStructure {
+    /// Resolves an ID when `query` parses as one, otherwise the best fuzzy
+    /// name match.
+    pub fn lookup(&self, query: &str) -> Option<JDLookup> {
+        match query.parse::<JDId>() {
+            Ok(id) => self.lookup_id(&id),
+            Err(_) => self.search(query, 1).into_iter().next(),
+        }
+    }
+
+    /// `None` when the ID is missing or names another system.
+    pub fn lookup_id(&self, id: &JDId) -> Option<JDLookup> {
+        if id.system.is_some() && !same_system(id.system.as_deref(), self.system.as_deref()) {
+            return None;
+        }
+
+        let area = self.areas.iter().find(|a| a.number == id.area_number())?;
+        let found = match id.number {
+            JDNumber::Area(_) => self.area_lookup(area, 1.0),
+            JDNumber::Category(number) => {
+                let category = area.categories.iter().find(|c| c.number == number)?;
+                self.category_lookup(area, category, 1.0)
+            }
+            JDNumber::Item { category, .. } => {
+                let category = area.categories.iter().find(|c| c.number == category)?;
+                let item = category.items.iter().find(|i| {
+                    i.number.parse::<JDId>().ok().as_ref() == Some(&without_system(id))
+                })?;
+                self.item_lookup(area, category, item, 1.0)
+            }
+        };
+        Some(found)
+    }
+
+    /// Areas, categories and items whose names match `query`, best first.
+    pub fn search(&self, query: &str, limit: usize) -> Vec<JDLookup> {
+        let mut matches = Vec::new();
+
+        for area in &self.areas {
+            if let Some(score) = name_score(query, area.label()) {
+                matches.push(self.area_lookup(area, score));
+            }
+            for category in &area.categories {
+                if let Some(score) = name_score(query, &category.name) {
+                    matches.push(self.category_lookup(area, category, score));
+                }
+                for item in &category.items {
+                    if let Some(score) = name_score(query, &item.name) {
+                        matches.push(self.item_lookup(area, category, item, score));
+                    }
+                }
+            }
+        }
+
+        // Prefer the more specific folder on equal scores
+        matches.sort_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then(b.level.cmp(&a.level))
+        });
+        matches.truncate(limit);
+        matches
+    }
+
+    fn area_lookup(&self, area: &JDArea, score: f64) -> JDLookup {
+        JDLookup {
+            id: self.system_id(JDId::local(JDNumber::Area(area.number))),
+            level: FolderLevel::Area,
+            name: area.label().to_string(),
+            path: self.area_path(area),
+            score,
+        }
+    }
+
+    fn category_lookup(&self, area: &JDArea, category: &JDCategory, score: f64) -> JDLookup {
+        JDLookup {
+            id: self.system_id(JDId::local(JDNumber::Category(category.number))),
+            level: FolderLevel::Category,
+            name: category.name.clone(),
+            path: self.category_path(area, category),
+            score,
+        }
+    }
+
+    fn item_lookup(
+        &self,
+        area: &JDArea,
+        category: &JDCategory,
+        item: &JDItem,
+        score: f64,
+    ) -> JDLookup {
+        let number = item
+            .number
+            .parse::<JDId>()
+            .map(|id| id.number)
+            .unwrap_or(JDNumber::Category(category.number));
+
+        JDLookup {
+            id: self.system_id(JDId::local(number)),
+            level: FolderLevel::Item,
+            name: item.name.clone(),
+            path: self.item_path(area, category, item),
+            score,
+        }
+    }
+
+    fn system_id(&self, id: JDId) -> JDId {
+        JDId {
+            system: self.system.clone(),
+            ..id
+        }
+    }
+}
+
+fn without_system(id: &JDId) -> JDId {
+    JDId {
+        system: None,
+        ..id.clone()
+    }
+}
+
+fn same_system(a: Option<&str>, b: Option<&str>) -> bool {
+    match (a, b) {
+        (Some(a), Some(b)) => a.eq_ignore_ascii_case(b),
+        _ => false,
+    }
+}
+
+/// 1.0 for an exact match, then prefix, substring and word overlap.
+fn name_score(query: &str, name: &str) -> Option<f64> {
+    let query = query.trim().to_lowercase();
+    let name = name.to_lowercase();
+    if query.is_empty() {
+        return None;
+    }
+
+    if name == query {
+        return Some(1.0);
+    }
+    if name.starts_with(&query) {
+        return Some(0.8);
+    }
+    if name.contains(&query) {
+        return Some(0.6);
+    }
+
+    let words: Vec<&str> = query.split_whitespace().collect();
+    let found = words
+        .iter()
+        .filter(|w| {
+            name.split(|c: char| !c.is_alphanumeric())
+                .any(|n| n.starts_with(*w))
+        })
+        .count();
+    (found > 0).then(|| 0.5 * found as f64 / words.len() as f64)
+}
+
+/// Systems are a letter followed by two digits, e.g. `P01` or `W02`.
+fn is_system_code(s: &str) -> bool {
+    let bytes = s.as_bytes();
+    bytes.len() == 3
+        && bytes[0].is_ascii_alphabetic()
+        && bytes[1].is_ascii_digit()
+        && bytes[2].is_ascii_digit()
+}
+
+fn two_digits(s: &str, id: &str) -> Result<u8> {
+    if s.len() == 2 && s.chars().all(|c| c.is_ascii_digit()) {
+        Ok(s.parse().unwrap_or_default())
+    } else {
+        Err(invalid_id(id, "expected two digits"))
+    }
+}
+
+fn invalid_id(id: &str, reason: &str) -> AppError {
+    AppError::InvalidInput(format!("Invalid Johnny Decimal ID {}: {}", id, reason))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::templates::builtin_template;
+
+    #[test]
+    fn test_parse_and_format_ids() {
+        for id in ["10-19", "11", "11.01", "P01.11.01", "W02.20-29"] {
+            assert_eq!(id.parse::<JDId>().unwrap().to_string(), id);
+        }
+
+        let id: JDId = "p01.21.07".parse().unwrap();
+        assert_eq!(id.system.as_deref(), Some("P01"));
+        assert_eq!(
+            id.number,
+            JDNumber::Item {
+                category: 21,
+                index: 7
+            }
+        );
+        assert_eq!(id.area_number(), 20);
+        assert_eq!(id.level(), FolderLevel::Item);
+
+        for invalid in ["", "1", "05-14", "10-18", "11.00", "11.1", "100", "X1.11"] {
+            assert!(invalid.parse::<JDId>().is_err(), "{} parsed", invalid);
+        }
+    }
+
+    #[test]
+    fn test_lookup_by_id_and_name() {
+        let mut structure = builtin_template("downloads").unwrap().instantiate("/dl");
+
+        let item = structure.lookup("21.01").unwrap();
+        assert_eq!(item.level, FolderLevel::Item);
+        assert_eq!(item.name, "Visa Documents");
+        assert!(item
+            .path
+            .ends_with("21 Immigration & Visa Documents/21.01 Visa Documents"));
+
+        assert_eq!(
+            structure
+                .lookup("photography tools")
+                .unwrap()
+                .id
+                .to_string(),
+            "14"
+        );
+        assert_eq!(
+            structure.lookup("lightroom").unwrap().id.to_string(),
+            "14.01"
+        );
+
+        assert!(structure.lookup("P01.21").is_none());
+        structure.system = Some("P01".to_string());
+        assert_eq!(structure.lookup("P01.21").unwrap().id.to_string(), "P01.21");
+        assert!(structure.lookup("W01.21").is_none());
+    }
+}
@@ -0,0 +1,485 @@
+use crate::error::Result;
+use crate::johnny_decimal::{JDArea, JDCategory, JDItem, JDStructure};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+pub const JDEX_SCHEMA_VERSION: u32 = 1;
+
+/// JSON Schema describing the `Json` export format.
+pub const JDEX_JSON_SCHEMA: &str = include_str!("../schemas/jdex-v1.schema.json");
+/// File name of the schema, matching its `$id`.
+pub const JDEX_JSON_SCHEMA_FILE: &str = "jdex-v1.schema.json";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JdexFormat {
+    Markdown,
+    Html,
+    Csv,
+    Json,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JdexDocument {
+    pub schema: String,
+    pub schema_version: u32,
+    pub name: String,
+    pub root_path: String,
+    pub areas: Vec<JdexArea>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JdexArea {
+    pub id: String,
+    pub number: u8,
+    pub name: String,
+    pub description: Option<String>,
+    pub categories: Vec<JdexCategory>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JdexCategory {
+    pub id: String,
+    pub number: u8,
+    pub name: String,
+    pub description: Option<String>,
+    pub items: Vec<JdexItem>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JdexItem {
+    pub id: String,
+    pub name: String,
+    pub description: Option<String>,
+    pub file_count: usize,
+    pub files: Vec<String>, // Relative to root_path, sorted
+}
+
+/// Renders the structure as a JDex index. The output only depends on the
+/// structure (and, for HTML, on file sizes on disk): everything is sorted by
+/// ID and no timestamps are written, so exports can be committed to git.
+pub fn export_jdex(structure: &JDStructure, format: JdexFormat) -> Result<String> {
+    let sorted = sorted_structure(structure);
+
+    match format {
+        JdexFormat::Markdown => Ok(render_markdown(&sorted)),
+        JdexFormat::Html => Ok(render_html(&sorted)),
+        JdexFormat::Csv => Ok(render_csv(&sorted)),
+        JdexFormat::Json => {
+            let mut json = serde_json::to_string_pretty(&jdex_document(&sorted))?;
+            json.push('\n');
+            Ok(json)
+        }
+    }
+}
+
+/// Writes the export to `destination`. A JSON export gets its schema written
+/// next to it. Returns the files written.
+pub fn write_jdex(
+    structure: &JDStructure,
+    format: JdexFormat,
+    destination: &Path,
+) -> Result<Vec<PathBuf>> {
+    std::fs::write(destination, export_jdex(structure, format)?)?;
+    let mut written = vec![destination.to_path_buf()];
+
+    if format == JdexFormat::Json {
+        let schema_path = destination.with_file_name(JDEX_JSON_SCHEMA_FILE);
+        std::fs::write(&schema_path, JDEX_JSON_SCHEMA)?;
+        written.push(schema_path);
+    }
+    Ok(written)
+}
+
+pub fn jdex_document(structure: &JDStructure) -> JdexDocument {
+    let root = Path::new(&structure.root_path);
+
+    JdexDocument {
+        schema: "jdex".to_string(),
+        schema_version: JDEX_SCHEMA_VERSION,
+        name: structure.name.clone(),
+        root_path: structure.root_path.clone(),
+        areas: structure
+            .areas
+            .iter()
+            .map(|area| JdexArea {
+                id: area_id(area),
+                number: area.number,
+                name: area.label().to_string(),
+                description: area.description.clone(),
+                categories: area
+                    .categories
+                    .iter()
+                    .map(|category| JdexCategory {
+                        id: format!("{:02}", category.number),
+                        number: category.number,
+                        name: category.name.clone(),
+                        description: category.description.clone(),
+                        items: category
+                            .items
+                            .iter()
+                            .map(|item| {
+                                let mut files: Vec<String> = item
+                                    .files
+                                    .iter()
+                                    .map(|f| {
+                                        Path::new(f)
+                                            .strip_prefix(root)
+                                            .unwrap_or(Path::new(f))
+                                            .to_string_lossy()
+                                            .replace('\\', "/")
+                                    })
+                                    .collect();
+                                files.sort();
+
+                                JdexItem {
+                                    id: item.number.clone(),
+                                    name: item.name.clone(),
+                                    description: item.description.clone(),
+                                    file_count: files.len(),
+                                    files,
+                                }
+                            })
+                            .collect(),
+                    })
+                    .collect(),
+            })
+            .collect(),
+    }
+}
+
+fn render_markdown(structure: &JDStructure) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "# {}\n", structure.name);
+
+    for area in &structure.areas {
+        let _ = writeln!(out, "## {} {}\n", area_id(area), area.label());
+        if let Some(description) = &area.description {
+            let _ = writeln!(out, "{}\n", description);
+        }
+
+        for category in &area.categories {
+            let _ = writeln!(out, "### {:02} {}\n", category.number, category.name);
+            if let Some(description) = &category.description {
+                let _ = writeln!(out, "{}\n", description);
+            }
+
+            for item in &category.items {
+                let _ = write!(out, "- **{}** {}", item.number, item.name);
+                if let Some(description) = &item.description {
+                    let _ = write!(out, " — {}", description);
+                }
+                let _ = writeln!(out, " ({})", file_count_label(item.files.len()));
+            }
+            if !category.items.is_empty() {
+                out.push('\n');
+            }
+        }
+    }
+
+    out
+}
+
+fn render_csv(structure: &JDStructure) -> String {
+    let mut out = String::from("id,level,name,description,file_count\n");
+
+    let mut row =
+        |id: &str, level: &str, name: &str, description: &Option<String>, files: usize| {
+            let _ = writeln!(
+                out,
+                "{},{},{},{},{}",
+                csv_field(id),
+                level,
+                csv_field(name),
+                csv_field(description.as_deref().unwrap_or("")),
+                files
+            );
+        };
+
+    for area in &structure.areas {
+        row(
+            &area_id(area),
+            "area",
+            area.label(),
+            &area.description,
+            area_file_count(area),
+        );
+        for category in &area.categories {
+            row(
+                &format!("{:02}", category.number),
+                "category",
+                &category.name,
+                &category.description,
+                category_file_count(category),
+            );
+            for item in &category.items {
+                row(
+                    &item.number,
+                    "item",
+                    &item.name,
+                    &item.description,
+                    item.files.len(),
+                );
+            }
+        }
+    }
+
+    out
+}
+
+fn render_html(structure: &JDStructure) -> String {
+    let mut out = String::new();
+    let title = html_escape(&structure.name);
+
+    let _ = writeln!(
+        out,
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<title>{title}</title>
+<style>
+body {{ font-family: system-ui, sans-serif; margin: 2rem auto; max-width: 60rem; color: #1f2937; }}
+h2 {{ border-bottom: 1px solid #e5e7eb; padding-bottom: .25rem; }}
+table {{ border-collapse: collapse; width: 100%; margin-bottom: 1.5rem; }}
+th, td {{ text-align: left; padding: .25rem .5rem; border-bottom: 1px solid #f3f4f6; }}
+td.id {{ font-family: ui-monospace, monospace; white-space: nowrap; }}
+td.num {{ text-align: right; white-space: nowrap; }}
+.description {{ color: #6b7280; }}
+</style>
+</head>
+<body>
+<h1>{title}</h1>"#
+    );
+
+    for area in &structure.areas {
+        let _ = writeln!(
+            out,
+            r#"<h2><a href="{}">{} {}</a></h2>"#,
+            html_escape(&file_url(&structure.area_path(area))),
+            area_id(area),
+            html_escape(area.label())
+        );
+        if let Some(description) = &area.description {
+            let _ = writeln!(
+                out,
+                r#"<p class="description">{}</p>"#,
+                html_escape(description)
+            );
+        }
+
+        for category in &area.categories {
+            let _ = writeln!(
+                out,
+                r#"<h3><a href="{}">{:02} {}</a></h3>"#,
+                html_escape(&file_url(&structure.category_path(area, category))),
+                category.number,
+                html_escape(&category.name)
+            );
+            if category.items.is_empty() {
+                continue;
+            }
+
+            out.push_str(
+                "<table>\n<tr><th>ID</th><th>Name</th><th>Description</th><th>Files</th><th>Size</th></tr>\n",
+            );
+            for item in &category.items {
+                let _ = writeln!(
+                    out,
+                    r#"<tr><td class="id">{}</td><td><a href="{}">{}</a></td><td class="description">{}</td><td class="num">{}</td><td class="num">{}</td></tr>"#,
+                    html_escape(&item.number),
+                    html_escape(&file_url(&structure.item_path(area, category, item))),
+                    html_escape(&item.name),
+                    html_escape(item.description.as_deref().unwrap_or("")),
+                    item.files.len(),
+                    format_size(item_size(item))
+                );
+            }
+            out.push_str("</table>\n");
+        }
+    }
+
+    out.push_str("</body>\n</html>\n");
+    out
+}
+
+/// Copy of the structure with areas, categories and items ordered by ID.
+fn sorted_structure(structure: &JDStructure) -> JDStructure {
+    let mut sorted = structure.clone();
+    sorted.areas.sort_by_key(|a| a.number);
+    for area in &mut sorted.areas {
+        area.categories.sort_by_key(|c| c.number);
+        for category in &mut area.categories {
+            category.items.sort_by(|a, b| a.number.cmp(&b.number));
+            for item in &mut category.items {
+                item.files.sort();
+            }
+        }
+    }
+    sorted
+}
+
+fn area_id(area: &JDArea) -> String {
+    format!("{:02}-{:02}", area.number, area.number.saturating_add(9))
+}
+
+fn area_file_count(area: &JDArea) -> usize {
+    area.categories.iter().map(category_file_count).sum()
+}
+
+fn category_file_count(category: &JDCategory) -> usize {
+    category.items.iter().map(|i| i.files.len()).sum()
+}
+
+fn item_size(item: &JDItem) -> u64 {
+    item.files
+        .iter()
+        .filter_map(|f| std::fs::metadata(f).ok())
+        .map(|m| m.len())
+        .sum()
+}
+
+fn file_count_label(count: usize) -> String {
+    match count {
+        1 => "1 file".to_string(),
+        n => format!("{} files", n),
+    }
+}
+
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["B", "KB", "MB", "GB", "TB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+
+    if unit == 0 {
+        format!("{} {}", bytes, UNITS[0])
+    } else {
+        format!("{:.1} {}", size, UNITS[unit])
+    }
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+fn html_escape(value: &str) -> String {
+    value
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+/// `file://` URL for a local folder, percent-encoding anything outside the
+/// unreserved set.
+fn file_url(path: &Path) -> String {
+    let path = path.to_string_lossy().replace('\\', "/");
+    let mut url = String::from("file://");
+    if !path.starts_with('/') {
+        url.push('/');
+    }
+
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' | b':' => {
+                url.push(byte as char)
+            }
+            _ => {
+                let _ = write!(url, "%{:02X}", byte);
+            }
+        }
+    }
+
+    url
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::templates::builtin_template;
+
+    fn structure() -> JDStructure {
+        let mut structure = builtin_template("personal_home")
+            .unwrap()
+            .instantiate("/home/me");
+        structure.areas.reverse();
+        structure.areas[0].categories[1].items[0].description =
+            Some("Old, \"boxed\" files".to_string());
+        structure.areas[0].categories[1].items[0].files =
+            vec!["/home/me/b.pdf".to_string(), "/home/me/a.pdf".to_string()];
+        structure
+    }
+
+    #[test]
+    fn test_export_is_deterministic_and_sorted() {
+        let structure = structure();
+
+        for format in [
+            JdexFormat::Markdown,
+            JdexFormat::Html,
+            JdexFormat::Csv,
+            JdexFormat::Json,
+        ] {
+            let first = export_jdex(&structure, format).unwrap();
+            let second = export_jdex(&structure, format).unwrap();
+            assert_eq!(first, second);
+        }
+
+        let markdown = export_jdex(&structure, JdexFormat::Markdown).unwrap();
+        assert!(markdown.starts_with("# Personal Home\n\n## 10-19 Life Admin"));
+        assert!(markdown.contains("- **92.01** Old Files — Old, \"boxed\" files (2 files)"));
+
+        let html = export_jdex(&structure, JdexFormat::Html).unwrap();
+        assert!(html.contains("file:///home/me/90-99%20Inbox%20%26%20Archive"));
+    }
+
+    #[test]
+    fn test_csv_and_json_exports() {
+        let structure = structure();
+
+        let csv = export_jdex(&structure, JdexFormat::Csv).unwrap();
+        assert!(csv.starts_with("id,level,name,description,file_count\n10-19,area,Life Admin,"));
+        assert!(csv.contains("92.01,item,Old Files,\"Old, \"\"boxed\"\" files\",2\n"));
+
+        let json = export_jdex(&structure, JdexFormat::Json).unwrap();
+        let document: JdexDocument = serde_json::from_str(&json).unwrap();
+        assert_eq!(document.schema_version, JDEX_SCHEMA_VERSION);
+        let item = &document.areas.last().unwrap().categories[1].items[0];
+        assert_eq!(item.files, vec!["a.pdf", "b.pdf"]);
+    }
+
+    #[test]
+    fn test_json_export_ships_its_schema() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let destination = temp_dir.path().join("index.json");
+
+        let written = write_jdex(&structure(), JdexFormat::Json, &destination).unwrap();
+        assert_eq!(written.len(), 2);
+        let schema: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&written[1]).unwrap()).unwrap();
+        assert_eq!(schema["$id"], JDEX_JSON_SCHEMA_FILE);
+
+        // Every field the schema requires is in the document
+        let document: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&destination).unwrap()).unwrap();
+        for field in schema["required"].as_array().unwrap() {
+            assert!(document.get(field.as_str().unwrap()).is_some());
+        }
+
+        let csv = temp_dir.path().join("index.csv");
+        assert_eq!(
+            write_jdex(&structure(), JdexFormat::Csv, &csv).unwrap(),
+            vec![csv]
+        );
+    }
+}
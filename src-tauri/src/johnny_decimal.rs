@@ -1,13 +1,30 @@
-use crate::error::Result;
+use crate::categorization::{rank_candidates, Categorization, CategorizationContext};
+use crate::clustering::{FileClusterer, MAX_ITEMS_PER_CATEGORY};
+use crate::error::{AppError, Result};
+use crate::labels::{label_set, label_sets, select_label_set, LabelSet, DEFAULT_LOCALE};
+use crate::lint::{lint_structure, LintSettings};
+use crate::organization_plan::{relocate_files, OrganizationPlan};
+use crate::taxonomy::{extension_map, FALLBACK_CATEGORY};
+use crate::templates::{builtin_template, StructureTemplate, DEFAULT_TEMPLATE_ID};
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use uuid::Uuid;
 
+/// Confidence of `assign_file` for an extension match and for the
+/// miscellaneous fallback.
+pub const EXTENSION_MATCH_CONFIDENCE: f64 = 0.85;
+pub const FALLBACK_CONFIDENCE: f64 = 0.5;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct JDStructure {
     pub id: String,
     pub name: String,
     pub root_path: String,
+    #[serde(default)]
+    pub system: Option<String>, // Multi-system prefix such as P01
+    #[serde(default)]
+    pub locale: Option<String>, // Language of generated folder names
     pub areas: Vec<JDArea>,
     pub created_at: chrono::DateTime<chrono::Utc>,
     pub modified_at: chrono::DateTime<chrono::Utc>,
@@ -37,7 +54,54 @@ pub struct JDItem {
     pub files: Vec<String>, // File paths
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl JDStructure {
+    pub fn area_path(&self, area: &JDArea) -> PathBuf {
+        Path::new(&self.root_path).join(area.folder_name())
+    }
+
+    pub fn category_path(&self, area: &JDArea, category: &JDCategory) -> PathBuf {
+        self.area_path(area).join(category.folder_name())
+    }
+
+    pub fn item_path(&self, area: &JDArea, category: &JDCategory, item: &JDItem) -> PathBuf {
+        self.category_path(area, category).join(item.folder_name())
+    }
+}
+
+impl JDArea {
+    /// Folder name on disk, e.g. `20-29 Documents`.
+    pub fn folder_name(&self) -> String {
+        format!("{} {}", self.range_label(), self.label())
+    }
+
+    /// The area name without its `NN-NN` range prefix.
+    pub fn label(&self) -> &str {
+        self.name
+            .strip_prefix(&self.range_label())
+            .map(str::trim_start)
+            .unwrap_or(&self.name)
+    }
+
+    fn range_label(&self) -> String {
+        format!("{}-{}", self.number, self.number.saturating_add(9))
+    }
+}
+
+impl JDCategory {
+    /// Folder name on disk, e.g. `21 Text Documents`.
+    pub fn folder_name(&self) -> String {
+        format!("{:02} {}", self.number, self.name)
+    }
+}
+
+impl JDItem {
+    /// Folder name on disk, e.g. `21.01 Invoices`.
+    pub fn folder_name(&self) -> String {
+        format!("{} {}", self.number, self.name)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct CategoryAssignment {
     pub area_number: u8,
     pub category_number: u8,
@@ -66,69 +130,87 @@ pub struct JDValidationWarning {
     pub warning_type: String,
     pub message: String,
     pub suggestion: Option<String>,
+    #[serde(default)]
+    pub severity: WarningSeverity,
+    #[serde(default)]
+    pub code: Option<String>, // Lint rule code such as JD101
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WarningSeverity {
+    Info,
+    #[default]
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum JDRepairAction {
+    AreaRenumbered {
+        from: u8,
+        to: u8,
+    },
+    CategoryRenumbered {
+        area_number: u8,
+        from: u8,
+        to: u8,
+    },
+    CategorySplit {
+        from: u8,
+        to: u8,
+        items_moved: usize,
+    },
+    ItemRenumbered {
+        from: String,
+        to: String,
+    },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JDRepairResult {
+    pub structure: JDStructure,
+    pub actions: Vec<JDRepairAction>,
+    pub plan: OrganizationPlan,
 }
 
 #[allow(dead_code)]
 pub struct JohnnyDecimalEngine {
     // File type mappings for automatic categorization
     file_type_mappings: HashMap<String, (u8, String)>, // extension -> (area, category_name)
+    // Names and descriptions of the default areas
+    area_template: StructureTemplate,
+    // Language of the folder names the engine generates
+    locale: String,
+    label_sets: Vec<LabelSet>,
 }
 
 #[allow(dead_code)]
 impl JohnnyDecimalEngine {
     pub fn new() -> Result<Self> {
-        let mut file_type_mappings = HashMap::new();
-
-        // Documents area (20-29)
-        file_type_mappings.insert("pdf".to_string(), (20, "Reports and Documents".to_string()));
-        file_type_mappings.insert("doc".to_string(), (20, "Text Documents".to_string()));
-        file_type_mappings.insert("docx".to_string(), (20, "Text Documents".to_string()));
-        file_type_mappings.insert("txt".to_string(), (20, "Text Documents".to_string()));
-        file_type_mappings.insert("rtf".to_string(), (20, "Text Documents".to_string()));
-        file_type_mappings.insert("xls".to_string(), (20, "Spreadsheets".to_string()));
-        file_type_mappings.insert("xlsx".to_string(), (20, "Spreadsheets".to_string()));
-        file_type_mappings.insert("csv".to_string(), (20, "Spreadsheets".to_string()));
-        file_type_mappings.insert("ppt".to_string(), (20, "Presentations".to_string()));
-        file_type_mappings.insert("pptx".to_string(), (20, "Presentations".to_string()));
-
-        // Media area (30-39)
-        file_type_mappings.insert("jpg".to_string(), (30, "Images".to_string()));
-        file_type_mappings.insert("jpeg".to_string(), (30, "Images".to_string()));
-        file_type_mappings.insert("png".to_string(), (30, "Images".to_string()));
-        file_type_mappings.insert("gif".to_string(), (30, "Images".to_string()));
-        file_type_mappings.insert("bmp".to_string(), (30, "Images".to_string()));
-        file_type_mappings.insert("svg".to_string(), (30, "Images".to_string()));
-        file_type_mappings.insert("mp4".to_string(), (30, "Videos".to_string()));
-        file_type_mappings.insert("avi".to_string(), (30, "Videos".to_string()));
-        file_type_mappings.insert("mkv".to_string(), (30, "Videos".to_string()));
-        file_type_mappings.insert("mov".to_string(), (30, "Videos".to_string()));
-        file_type_mappings.insert("mp3".to_string(), (30, "Audio".to_string()));
-        file_type_mappings.insert("wav".to_string(), (30, "Audio".to_string()));
-        file_type_mappings.insert("flac".to_string(), (30, "Audio".to_string()));
-
-        // Development area (40-49)
-        file_type_mappings.insert("js".to_string(), (40, "Source Code".to_string()));
-        file_type_mappings.insert("ts".to_string(), (40, "Source Code".to_string()));
-        file_type_mappings.insert("py".to_string(), (40, "Source Code".to_string()));
-        file_type_mappings.insert("rs".to_string(), (40, "Source Code".to_string()));
-        file_type_mappings.insert("java".to_string(), (40, "Source Code".to_string()));
-        file_type_mappings.insert("cpp".to_string(), (40, "Source Code".to_string()));
-        file_type_mappings.insert("c".to_string(), (40, "Source Code".to_string()));
-        file_type_mappings.insert("html".to_string(), (40, "Web Files".to_string()));
-        file_type_mappings.insert("css".to_string(), (40, "Web Files".to_string()));
-        file_type_mappings.insert("json".to_string(), (40, "Configuration".to_string()));
-        file_type_mappings.insert("xml".to_string(), (40, "Configuration".to_string()));
-        file_type_mappings.insert("yaml".to_string(), (40, "Configuration".to_string()));
-        file_type_mappings.insert("yml".to_string(), (40, "Configuration".to_string()));
-
-        // Archives area (50-59)
-        file_type_mappings.insert("zip".to_string(), (50, "Compressed Files".to_string()));
-        file_type_mappings.insert("rar".to_string(), (50, "Compressed Files".to_string()));
-        file_type_mappings.insert("7z".to_string(), (50, "Compressed Files".to_string()));
-        file_type_mappings.insert("tar".to_string(), (50, "Compressed Files".to_string()));
-        file_type_mappings.insert("gz".to_string(), (50, "Compressed Files".to_string()));
-
-        Ok(Self { file_type_mappings })
+        Self::with_locale(DEFAULT_LOCALE)
+    }
+
+    pub fn with_locale(locale: &str) -> Result<Self> {
+        Self::with_template(builtin_template(DEFAULT_TEMPLATE_ID)?, locale)
+    }
+
+    /// An engine that names new areas after `template`, in `locale`.
+    pub fn with_template(area_template: StructureTemplate, locale: &str) -> Result<Self> {
+        let label_sets = label_sets()?;
+        if select_label_set(&label_sets, locale).is_none() {
+            return Err(AppError::Configuration(
+                "No label sets available".to_string(),
+            ));
+        }
+
+        Ok(Self {
+            file_type_mappings: extension_map(),
+            area_template,
+            locale: locale.to_string(),
+            label_sets,
+        })
     }
 
     pub async fn create_structure(
@@ -136,89 +218,133 @@ impl JohnnyDecimalEngine {
         files: Vec<serde_json::Value>,
         root_path: &str,
     ) -> Result<JDStructure> {
+        Ok(self.build_structure(&files, root_path))
+    }
+
+    pub fn build_structure(&self, files: &[serde_json::Value], root_path: &str) -> JDStructure {
         let mut areas_map: HashMap<u8, JDArea> = HashMap::new();
         let now = chrono::Utc::now();
+        let clusterer = FileClusterer::new(root_path, files);
 
-        // Group files by area based on file extensions
+        // Group files by area and category based on file extensions
+        let mut category_files: Vec<((u8, String), Vec<serde_json::Value>)> = Vec::new();
         for file_data in files {
             let extension = file_data["extension"].as_str().unwrap_or("").to_lowercase();
 
-            let (area_number, category_name) = self
+            let key = self
                 .file_type_mappings
                 .get(&extension)
                 .cloned()
-                .unwrap_or((90, "Miscellaneous".to_string())); // Default to area 90
+                .unwrap_or((FALLBACK_CATEGORY.area, FALLBACK_CATEGORY.name.to_string()));
+
+            match category_files.iter_mut().find(|(k, _)| *k == key) {
+                Some((_, grouped)) => grouped.push(file_data.clone()),
+                None => category_files.push((key, vec![file_data.clone()])),
+            }
+        }
+
+        let labels = self.labels_for(None);
+        for ((area_number, category_name), grouped) in category_files {
+            let category_name = labels.category(&category_name).to_string();
 
             // Get or create area
             let area = areas_map.entry(area_number).or_insert_with(|| JDArea {
                 number: area_number,
-                name: self.get_area_name(area_number),
-                description: Some(self.get_area_description(area_number)),
+                name: self.get_area_name(area_number, &self.locale),
+                description: Some(self.get_area_description(area_number, &self.locale)),
                 categories: Vec::new(),
             });
 
-            // Find or create category
-            let category_number = area_number + 1; // First category in area
-            if let Some(category) = area.categories.iter_mut().find(|c| c.name == category_name) {
-                // Add file to existing category
-                if let Some(item) = category.items.first_mut() {
-                    if let Some(path) = file_data["path"].as_str() {
-                        item.files.push(path.to_string());
+            // Split the category into items by shared project, name, folder or date
+            let items = clusterer
+                .cluster(&category_name, &grouped)
+                .into_iter()
+                .map(|cluster| {
+                    log::debug!(
+                        "{} > {}: {} files grouped by {:?}",
+                        category_name,
+                        cluster.name,
+                        cluster.files.len(),
+                        cluster.signal
+                    );
+                    JDItem {
+                        number: String::new(), // Assigned during renumbering below
+                        name: cluster.name,
+                        description: Some(cluster.description),
+                        files: cluster.files,
                     }
-                }
-            } else {
-                // Create new category
-                let mut category = JDCategory {
-                    number: category_number,
-                    name: category_name.clone(),
-                    description: Some(format!("Files of type: {}", category_name)),
-                    items: Vec::new(),
-                };
-
-                // Create first item in category
-                let item = JDItem {
-                    number: format!("{}.01", category_number),
-                    name: format!("{} Files", category_name),
-                    description: Some(format!(
-                        "Collection of {} files",
-                        category_name.to_lowercase()
-                    )),
-                    files: if let Some(path) = file_data["path"].as_str() {
-                        vec![path.to_string()]
-                    } else {
-                        vec![]
-                    },
-                };
+                })
+                .collect();
 
-                category.items.push(item);
-                area.categories.push(category);
-            }
+            area.categories.push(JDCategory {
+                number: area_number + 1, // Assigned during renumbering below
+                name: category_name.clone(),
+                description: Some(format!("Files of type: {}", category_name)),
+                items,
+            });
         }
 
         // Convert HashMap to sorted Vec
         let mut areas: Vec<JDArea> = areas_map.into_values().collect();
         areas.sort_by_key(|a| a.number);
 
-        // Ensure proper numbering within each area
+        // Ensure proper numbering within each area, setting aside what does not fit
+        let mut used_categories = HashSet::new();
+        let mut overflow = Vec::new();
         for area in &mut areas {
-            area.categories.sort_by_key(|c| c.number);
-            for (i, category) in area.categories.iter_mut().enumerate() {
-                category.number = area.number + (i as u8) + 1;
-
-                for (j, item) in category.items.iter_mut().enumerate() {
-                    item.number = format!("{}.{:02}", category.number, j + 1);
+            for mut category in std::mem::take(&mut area.categories) {
+                match free_category_number(area.number, &used_categories) {
+                    Some(number) => {
+                        used_categories.insert(number);
+                        category.number = number;
+                        area.categories.push(category);
+                    }
+                    None => overflow.push((area.number, category)),
                 }
             }
         }
 
-        Ok(JDStructure {
+        let mut structure = JDStructure {
             id: Uuid::new_v4().to_string(),
             name: "AI Generated Structure".to_string(),
             root_path: root_path.to_string(),
+            system: None,
+            locale: Some(self.locale.clone()),
             areas,
             created_at: now,
             modified_at: now,
-        })
+        };
+
+        // Areas with more than ten categories continue in an overflow area
+        let mut used_areas: HashSet<u8> = structure.areas.iter().map(|a| a.number).collect();
+        let mut overflow_areas = HashMap::new();
+        for (area_number, mut category) in overflow {
+            match place_in_overflow_area(
+                &mut structure,
+                area_number,
+                &mut overflow_areas,
+                &mut used_areas,
+                &mut used_categories,
+            ) {
+                Ok(number) => {
+                    category.number = number;
+                    push_category(&mut structure, number, category);
+                }
+                Err(e) => {
+                    log::error!("No room for category {}: {}", category.name, e);
+                    push_category(&mut structure, area_number, category);
+                }
+            }
+        }
+
+        structure.areas.sort_by_key(|a| a.number);
+        for category in structure.areas.iter_mut().flat_map(|a| &mut a.categories) {
+            for (j, item) in category.items.iter_mut().enumerate() {
+                item.number = format!("{}.{:02}", category.number, j + 1);
+            }
+        }
+
+        structure
     }
 
     pub async fn validate_structure(&self, structure: &JDStructure) -> Result<JDValidationResult> {
@@ -296,6 +422,8 @@ impl JohnnyDecimalEngine {
                                 item.number, expected_number
                             ),
                             suggestion: Some(format!("Renumber to {}", expected_number)),
+                            severity: WarningSeverity::Warning,
+                            code: None,
                         });
                     }
                 }
@@ -309,6 +437,8 @@ impl JohnnyDecimalEngine {
                             category.number, category.items.len()
                         ),
                         suggestion: Some("Split large categories for better organization".to_string()),
+                        severity: WarningSeverity::Warning,
+                        code: None,
                     });
                 }
             }
@@ -321,6 +451,8 @@ impl JohnnyDecimalEngine {
                     suggestion: Some(
                         "Consider removing empty areas or adding categories".to_string(),
                     ),
+                    severity: WarningSeverity::Warning,
+                    code: None,
                 });
             }
         }
@@ -332,22 +464,214 @@ impl JohnnyDecimalEngine {
         })
     }
 
+    /// `validate_structure` followed by the lint rules enabled in `lint`.
+    pub async fn validate_and_lint(
+        &self,
+        structure: &JDStructure,
+        lint: &LintSettings,
+    ) -> Result<JDValidationResult> {
+        let mut result = self.validate_structure(structure).await?;
+        result.warnings.extend(lint_structure(structure, lint));
+        Ok(result)
+    }
+
+    /// Fixes what `validate_structure` reports: duplicate or out-of-range
+    /// numbers get free slots, overfull categories are split and items are
+    /// renumbered sequentially. The returned plan moves files on disk to match.
+    pub async fn repair_structure(&self, structure: &JDStructure) -> Result<JDRepairResult> {
+        let mut repaired = structure.clone();
+        let mut actions = Vec::new();
+
+        // Keep the first valid use of each area number, move the rest to free slots
+        let mut used_areas = HashSet::new();
+        let mut misplaced_areas = Vec::new();
+        for (index, area) in repaired.areas.iter().enumerate() {
+            if !is_valid_area_number(area.number) || !used_areas.insert(area.number) {
+                misplaced_areas.push(index);
+            }
+        }
+
+        for index in misplaced_areas {
+            let area = &mut repaired.areas[index];
+            let from = area.number;
+            let preferred = (from / 10 * 10).clamp(10, 90);
+            let to = if used_areas.contains(&preferred) {
+                next_free_area(&used_areas)?
+            } else {
+                preferred
+            };
+            used_areas.insert(to);
+
+            // Categories keep their offset within the area
+            for category in &mut area.categories {
+                if category.number >= from && category.number - from < 10 {
+                    category.number = to + (category.number - from);
+                }
+            }
+            if area.label() != area.name {
+                area.name = format!("{}-{} {}", to, to + 9, area.label());
+            }
+            area.number = to;
+
+            actions.push(JDRepairAction::AreaRenumbered { from, to });
+        }
+
+        // Same for categories: first valid use wins, the rest find a free slot
+        let mut used_categories = HashSet::new();
+        let mut misplaced_categories = Vec::new();
+        for (area_index, area) in repaired.areas.iter().enumerate() {
+            for (category_index, category) in area.categories.iter().enumerate() {
+                let in_range = category.number >= area.number && category.number - area.number < 10;
+                if !in_range || !used_categories.insert(category.number) {
+                    misplaced_categories.push((area_index, category_index));
+                }
+            }
+        }
+
+        let mut evicted = Vec::new();
+        for (area_index, category_index) in misplaced_categories {
+            let area_number = repaired.areas[area_index].number;
+            let category = &mut repaired.areas[area_index].categories[category_index];
+            let from = category.number;
+
+            match free_category_number(area_number, &used_categories) {
+                Some(to) => {
+                    used_categories.insert(to);
+                    category.number = to;
+                    actions.push(JDRepairAction::CategoryRenumbered {
+                        area_number,
+                        from,
+                        to,
+                    });
+                }
+                None => evicted.push((area_index, category_index)),
+            }
+        }
+
+        // Areas without room push their extra categories into an overflow area
+        let mut overflow_areas = HashMap::new();
+        for (area_index, category_index) in evicted.into_iter().rev() {
+            let area_number = repaired.areas[area_index].number;
+            let mut category = repaired.areas[area_index].categories.remove(category_index);
+            let from = category.number;
+            let to = place_in_overflow_area(
+                &mut repaired,
+                area_number,
+                &mut overflow_areas,
+                &mut used_areas,
+                &mut used_categories,
+            )?;
+            category.number = to;
+            push_category(&mut repaired, to, category);
+            actions.push(JDRepairAction::CategoryRenumbered {
+                area_number: to / 10 * 10,
+                from,
+                to,
+            });
+        }
+
+        // Split categories that hold more items than IDs 01-99 allow
+        let mut overfull = Vec::new();
+        for area in &mut repaired.areas {
+            for category in &mut area.categories {
+                if category.items.len() > MAX_ITEMS_PER_CATEGORY {
+                    let extra = category.items.split_off(MAX_ITEMS_PER_CATEGORY);
+                    overfull.push((area.number, category.clone(), extra));
+                }
+            }
+        }
+
+        for (area_number, source, extra) in overfull {
+            for (part, items) in extra.chunks(MAX_ITEMS_PER_CATEGORY).enumerate() {
+                let to = match free_category_number(area_number, &used_categories) {
+                    Some(to) => {
+                        used_categories.insert(to);
+                        to
+                    }
+                    None => place_in_overflow_area(
+                        &mut repaired,
+                        area_number,
+                        &mut overflow_areas,
+                        &mut used_areas,
+                        &mut used_categories,
+                    )?,
+                };
+
+                push_category(
+                    &mut repaired,
+                    to,
+                    JDCategory {
+                        number: to,
+                        name: format!("{} ({})", source.name, part + 2),
+                        description: source.description.clone(),
+                        items: items.to_vec(),
+                    },
+                );
+                actions.push(JDRepairAction::CategorySplit {
+                    from: source.number,
+                    to,
+                    items_moved: items.len(),
+                });
+            }
+        }
+
+        repaired.areas.sort_by_key(|a| a.number);
+        for area in &mut repaired.areas {
+            area.categories.sort_by_key(|c| c.number);
+            for category in &mut area.categories {
+                for (i, item) in category.items.iter_mut().enumerate() {
+                    let expected = format!("{}.{:02}", category.number, i + 1);
+                    if item.number != expected {
+                        actions.push(JDRepairAction::ItemRenumbered {
+                            from: item.number.clone(),
+                            to: expected.clone(),
+                        });
+                        item.number = expected;
+                    }
+                }
+            }
+        }
+
+        let plan = OrganizationPlan::between(structure, &repaired);
+        relocate_files(&mut repaired);
+        if !actions.is_empty() {
+            repaired.modified_at = chrono::Utc::now();
+        }
+
+        Ok(JDRepairResult {
+            structure: repaired,
+            actions,
+            plan,
+        })
+    }
+
+    /// Ranked candidate IDs for a file, with the signals behind each.
     pub async fn categorize_file(
         &self,
         file_info: serde_json::Value,
         structure: &JDStructure,
-    ) -> Result<CategoryAssignment> {
+        context: &CategorizationContext,
+    ) -> Result<Categorization> {
+        Ok(rank_candidates(self, &file_info, structure, context))
+    }
+
+    pub fn assign_file(
+        &self,
+        file_info: &serde_json::Value,
+        structure: &JDStructure,
+    ) -> CategoryAssignment {
         let extension = file_info["extension"].as_str().unwrap_or("").to_lowercase();
+        let labels = self.labels_for(structure.locale.as_deref());
 
         // Try to find appropriate area and category
         if let Some((area_number, category_name)) = self.file_type_mappings.get(&extension) {
             // Find the area in the structure
             if let Some(area) = structure.areas.iter().find(|a| a.number == *area_number) {
-                // Find matching category
+                // Find matching category, in English or the structure's language
+                let localized_name = labels.category(category_name).to_lowercase();
                 if let Some(category) = area.categories.iter().find(|c| {
-                    c.name
-                        .to_lowercase()
-                        .contains(&category_name.to_lowercase())
+                    let name = c.name.to_lowercase();
+                    name.contains(&category_name.to_lowercase()) || name.contains(&localized_name)
                 }) {
                     // Find appropriate item or suggest new one
                     let item_number = if let Some(item) = category.items.first() {
@@ -356,62 +680,222 @@ impl JohnnyDecimalEngine {
                         format!("{}.01", category.number)
                     };
 
-                    return Ok(CategoryAssignment {
+                    return CategoryAssignment {
                         area_number: *area_number,
                         category_number: category.number,
                         item_number,
-                        confidence: 0.85,
+                        confidence: EXTENSION_MATCH_CONFIDENCE,
                         reasoning: format!(
                             "File extension '{}' matches category '{}' in area {}",
                             extension, category_name, area_number
                         ),
-                    });
+                    };
                 }
             }
         }
 
-        // Fallback to miscellaneous
-        Ok(CategoryAssignment {
-            area_number: 90,
-            category_number: 91,
-            item_number: "91.01".to_string(),
-            confidence: 0.5,
+        // Fallback to the miscellaneous category of the structure, if it has one
+        let fallback = structure
+            .areas
+            .iter()
+            .find(|a| a.number == FALLBACK_CATEGORY.area)
+            .and_then(|area| {
+                let localized_name = labels.category(FALLBACK_CATEGORY.name).to_lowercase();
+                area.categories
+                    .iter()
+                    .find(|c| {
+                        let name = c.name.to_lowercase();
+                        name == FALLBACK_CATEGORY.name.to_lowercase() || name == localized_name
+                    })
+                    .or_else(|| area.categories.first())
+            });
+        let (category_number, item_number) = match fallback {
+            Some(category) => (
+                category.number,
+                category
+                    .items
+                    .first()
+                    .map(|item| item.number.clone())
+                    .unwrap_or_else(|| format!("{}.01", category.number)),
+            ),
+            None => (
+                FALLBACK_CATEGORY.area + 1,
+                format!("{}.01", FALLBACK_CATEGORY.area + 1),
+            ),
+        };
+
+        CategoryAssignment {
+            area_number: FALLBACK_CATEGORY.area,
+            category_number,
+            item_number,
+            confidence: FALLBACK_CONFIDENCE,
             reasoning: format!(
                 "No specific category found for extension '{}', assigned to miscellaneous",
                 extension
             ),
-        })
+        }
+    }
+
+    /// The `area/category` folder of an assignment relative to the root. Uses
+    /// the structure's folder names, or the default ones when the structure
+    /// has no such category yet.
+    pub fn assignment_folder(
+        &self,
+        assignment: &CategoryAssignment,
+        structure: &JDStructure,
+    ) -> PathBuf {
+        let area = structure
+            .areas
+            .iter()
+            .find(|a| a.number == assignment.area_number);
+        let category = area.and_then(|a| {
+            a.categories
+                .iter()
+                .find(|c| c.number == assignment.category_number)
+        });
+
+        match (area, category) {
+            (Some(area), Some(category)) => {
+                PathBuf::from(area.folder_name()).join(category.folder_name())
+            }
+            _ => {
+                let locale = structure.locale.as_deref().unwrap_or(&self.locale);
+                PathBuf::from(self.get_area_name(assignment.area_number, locale)).join(format!(
+                    "{:02} {}",
+                    assignment.category_number,
+                    self.labels_for(Some(locale))
+                        .category(FALLBACK_CATEGORY.name)
+                ))
+            }
+        }
     }
 
-    fn get_area_name(&self, number: u8) -> String {
-        match number {
-            10 => "10-19 Administration".to_string(),
-            20 => "20-29 Documents".to_string(),
-            30 => "30-39 Media".to_string(),
-            40 => "40-49 Development".to_string(),
-            50 => "50-59 Archives".to_string(),
-            60 => "60-69 Projects".to_string(),
-            70 => "70-79 Reference".to_string(),
-            80 => "80-89 Resources".to_string(),
-            90 => "90-99 Miscellaneous".to_string(),
-            _ => format!("{}-{} Custom Area", number, number + 9),
+    /// The `area/category/item` folder of an assignment relative to the root.
+    /// An item the structure does not have yet is named after its category.
+    pub fn assignment_item_folder(
+        &self,
+        assignment: &CategoryAssignment,
+        structure: &JDStructure,
+    ) -> PathBuf {
+        let folder = self.assignment_folder(assignment, structure);
+        let category = structure
+            .areas
+            .iter()
+            .flat_map(|a| &a.categories)
+            .find(|c| c.number == assignment.category_number);
+
+        match category.and_then(|c| c.items.iter().find(|i| i.number == assignment.item_number)) {
+            Some(item) => folder.join(item.folder_name()),
+            None => {
+                let name = match category {
+                    Some(category) => category.name.clone(),
+                    None => {
+                        let locale = structure.locale.as_deref().unwrap_or(&self.locale);
+                        self.labels_for(Some(locale))
+                            .category(FALLBACK_CATEGORY.name)
+                            .to_string()
+                    }
+                };
+                folder.join(format!("{} {}", assignment.item_number, name))
+            }
         }
     }
 
-    fn get_area_description(&self, number: u8) -> String {
-        match number {
-            10 => "Administrative documents, policies, and organizational files".to_string(),
-            20 => "Text documents, reports, presentations, and written content".to_string(),
-            30 => "Images, videos, audio files, and multimedia content".to_string(),
-            40 => "Source code, development tools, and programming resources".to_string(),
-            50 => "Compressed files, archives, and backup collections".to_string(),
-            60 => "Active projects and work-in-progress materials".to_string(),
-            70 => "Reference materials, manuals, and documentation".to_string(),
-            80 => "Tools, utilities, and supporting resources".to_string(),
-            90 => "Uncategorized and miscellaneous files".to_string(),
-            _ => "Custom area for specialized content".to_string(),
+    /// Labels for `locale`, or for the engine's own locale.
+    fn labels_for(&self, locale: Option<&str>) -> &LabelSet {
+        select_label_set(&self.label_sets, locale.unwrap_or(&self.locale))
+            .unwrap_or(&self.label_sets[0])
+    }
+
+    fn get_area_name(&self, number: u8, locale: &str) -> String {
+        let name = match self.area_template.area(number) {
+            Some(area) => area.localized_name(locale).to_string(),
+            None => self.labels_for(Some(locale)).custom_area.clone(),
+        };
+        format!("{}-{} {}", number, number + 9, name)
+    }
+
+    fn get_area_description(&self, number: u8, locale: &str) -> String {
+        self.area_template
+            .area(number)
+            .and_then(|area| area.localized_description(locale))
+            .map(str::to_string)
+            .unwrap_or_else(|| {
+                self.labels_for(Some(locale))
+                    .custom_area_description
+                    .clone()
+            })
+    }
+}
+
+pub fn is_valid_area_number(number: u8) -> bool {
+    matches!(number, 10 | 20 | 30 | 40 | 50 | 60 | 70 | 80 | 90)
+}
+
+pub fn next_free_area(used: &HashSet<u8>) -> Result<u8> {
+    (10..=90)
+        .step_by(10)
+        .find(|n| !used.contains(n))
+        .ok_or_else(|| AppError::invalid_johnny_decimal("no free area number left"))
+}
+
+/// Prefers `x1`-`x9` and falls back to the `x0` slot of the area.
+pub fn free_category_number(area_number: u8, used: &HashSet<u8>) -> Option<u8> {
+    (1..10)
+        .chain(std::iter::once(0))
+        .map(|offset| area_number + offset)
+        .find(|n| !used.contains(n))
+}
+
+/// Finds a category slot in the overflow area of `source_area`, creating a
+/// new overflow area when none exists yet or the current one is full.
+pub fn place_in_overflow_area(
+    structure: &mut JDStructure,
+    source_area: u8,
+    overflow_areas: &mut HashMap<u8, u8>,
+    used_areas: &mut HashSet<u8>,
+    used_categories: &mut HashSet<u8>,
+) -> Result<u8> {
+    if let Some(&overflow) = overflow_areas.get(&source_area) {
+        if let Some(number) = free_category_number(overflow, used_categories) {
+            used_categories.insert(number);
+            return Ok(number);
         }
     }
+
+    let overflow = next_free_area(used_areas)?;
+    used_areas.insert(overflow);
+    overflow_areas.insert(source_area, overflow);
+
+    let source = structure.areas.iter().find(|a| a.number == source_area);
+    let label = source.map(|a| a.label().to_string()).unwrap_or_default();
+    structure.areas.push(JDArea {
+        number: overflow,
+        name: format!(
+            "{}-{} {} ({})",
+            overflow,
+            overflow + 9,
+            label,
+            label_set(structure.locale.as_deref().unwrap_or(DEFAULT_LOCALE))?.continued
+        ),
+        description: source.and_then(|a| a.description.clone()),
+        categories: Vec::new(),
+    });
+
+    let number = free_category_number(overflow, used_categories)
+        .ok_or_else(|| AppError::invalid_johnny_decimal("overflow area is full"))?;
+    used_categories.insert(number);
+    Ok(number)
+}
+
+pub fn push_category(structure: &mut JDStructure, number: u8, category: JDCategory) {
+    if let Some(area) = structure
+        .areas
+        .iter_mut()
+        .find(|a| number >= a.number && number - a.number < 10)
+    {
+        area.categories.push(category);
+    }
 }
 
 #[cfg(test)]
@@ -450,6 +934,102 @@ mod tests {
         assert!(structure.areas.iter().any(|a| a.number == 30)); // Media
     }
 
+    #[tokio::test]
+    async fn test_create_structure_clusters_items() {
+        let engine = JohnnyDecimalEngine::new().unwrap();
+
+        let files = vec![
+            serde_json::json!({ "path": "/test/invoice_01.pdf", "extension": "pdf" }),
+            serde_json::json!({ "path": "/test/invoice_02.pdf", "extension": "pdf" }),
+            serde_json::json!({ "path": "/test/taxes/w2.pdf", "extension": "pdf" }),
+            serde_json::json!({ "path": "/test/taxes/1099.pdf", "extension": "pdf" }),
+        ];
+
+        let structure = engine.create_structure(files, "/test").await.unwrap();
+        let category = &structure.areas[0].categories[0];
+
+        assert_eq!(category.items.len(), 2);
+        assert_eq!(category.items[0].number, "21.01");
+        assert_eq!(category.items[0].name, "Invoice");
+        assert_eq!(category.items[1].number, "21.02");
+        assert_eq!(category.items[1].name, "taxes");
+    }
+
+    #[tokio::test]
+    async fn test_validate_and_lint_follows_settings() {
+        let engine = JohnnyDecimalEngine::new().unwrap();
+        let mut structure = builtin_template("general").unwrap().instantiate("/home");
+        structure.areas.reverse();
+
+        let result = engine
+            .validate_and_lint(&structure, &LintSettings::default())
+            .await
+            .unwrap();
+        assert!(result.is_valid);
+        assert!(result
+            .warnings
+            .iter()
+            .any(|w| w.code.as_deref() == Some("JD107")));
+
+        let quiet = LintSettings {
+            disabled_rules: vec!["JD107".to_string()],
+            ..LintSettings::default()
+        };
+        let result = engine.validate_and_lint(&structure, &quiet).await.unwrap();
+        assert!(result.warnings.iter().all(|w| w.code.is_none()));
+    }
+
+    #[test]
+    fn test_localized_structure() {
+        let files = vec![
+            serde_json::json!({"path": "/test/report.pdf", "extension": "pdf"}),
+            serde_json::json!({"path": "/test/notes.xyz", "extension": "xyz"}),
+        ];
+
+        let engine = JohnnyDecimalEngine::with_locale("de").unwrap();
+        let structure = engine.build_structure(&files, "/test");
+        assert_eq!(structure.areas[0].name, "20-29 Dokumente");
+        assert_eq!(
+            structure.areas[0].categories[0].name,
+            "Berichte und Dokumente"
+        );
+        assert_eq!(structure.areas[1].categories[0].name, "Sonstiges");
+
+        // The English engine still finds the German categories
+        let assignment = JohnnyDecimalEngine::new()
+            .unwrap()
+            .assign_file(&files[0], &structure);
+        assert_eq!(assignment.item_number, "21.01");
+        assert_eq!(assignment.confidence, 0.85);
+    }
+
+    #[tokio::test]
+    async fn test_create_structure_overflows_full_areas() {
+        let mut engine = JohnnyDecimalEngine::new().unwrap();
+        let mut files = Vec::new();
+        for i in 0..12 {
+            let extension = format!("x{}", i);
+            engine
+                .file_type_mappings
+                .insert(extension.clone(), (60, format!("Kind {:02}", i)));
+            files.push(serde_json::json!({"path": format!("/test/f.{}", extension), "extension": extension}));
+        }
+
+        let structure = engine.build_structure(&files, "/test");
+
+        // The overflow area takes the first free number
+        assert_eq!(structure.areas[0].name, "10-19 Projects (continued)");
+        assert_eq!(structure.areas[0].categories.len(), 2);
+        assert_eq!(structure.areas[1].categories.len(), 10);
+        assert!(
+            engine
+                .validate_structure(&structure)
+                .await
+                .unwrap()
+                .is_valid
+        );
+    }
+
     #[tokio::test]
     async fn test_validate_structure() {
         let engine = JohnnyDecimalEngine::new().unwrap();
@@ -458,6 +1038,8 @@ mod tests {
             id: "test".to_string(),
             name: "Test Structure".to_string(),
             root_path: "/test".to_string(),
+            system: None,
+            locale: None,
             areas: vec![JDArea {
                 number: 20,
                 name: "Documents".to_string(),
@@ -477,4 +1059,71 @@ mod tests {
         assert!(result.is_valid);
         assert!(result.errors.is_empty());
     }
+
+    #[tokio::test]
+    async fn test_repair_structure() {
+        let engine = JohnnyDecimalEngine::new().unwrap();
+
+        let item = |number: &str| JDItem {
+            number: number.to_string(),
+            name: "Item".to_string(),
+            description: None,
+            files: vec![],
+        };
+        let category = |number: u8, items: Vec<JDItem>| JDCategory {
+            number,
+            name: format!("Category {}", number),
+            description: None,
+            items,
+        };
+
+        let structure = JDStructure {
+            id: "test".to_string(),
+            name: "Broken".to_string(),
+            root_path: "/test".to_string(),
+            system: None,
+            locale: None,
+            areas: vec![JDArea {
+                number: 20,
+                name: "20-29 Documents".to_string(),
+                description: None,
+                categories: vec![
+                    category(21, vec![item("21.05")]),
+                    category(21, vec![]),
+                    category(22, (0..120).map(|_| item("22.01")).collect()),
+                ],
+            }],
+            created_at: chrono::Utc::now(),
+            modified_at: chrono::Utc::now(),
+        };
+
+        let result = engine.repair_structure(&structure).await.unwrap();
+        let validation = engine.validate_structure(&result.structure).await.unwrap();
+
+        assert!(validation.is_valid);
+        assert!(validation.warnings.is_empty());
+        let categories = &result.structure.areas[0].categories;
+        assert_eq!(
+            categories.iter().map(|c| c.number).collect::<Vec<_>>(),
+            vec![21, 22, 23, 24]
+        );
+        assert_eq!(categories[1].items.len(), 99);
+        assert_eq!(categories[3].items.len(), 21);
+        assert!(result.actions.iter().any(|a| matches!(
+            a,
+            JDRepairAction::CategoryRenumbered {
+                from: 21,
+                to: 23,
+                ..
+            }
+        )));
+        assert!(result.actions.iter().any(|a| matches!(
+            a,
+            JDRepairAction::CategorySplit {
+                from: 22,
+                to: 24,
+                items_moved: 21
+            }
+        )));
+    }
 }
@@ -0,0 +1,121 @@
+use crate::error::{AppError, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+pub const DEFAULT_LOCALE: &str = "en";
+
+/// Label sets shipped with the app, embedded from `src-tauri/locales`.
+const LABEL_SETS: &[&str] = &[
+    include_str!("../locales/en.json"),
+    include_str!("../locales/de.json"),
+    include_str!("../locales/fr.json"),
+    include_str!("../locales/es.json"),
+];
+
+/// Folder labels the engine generates itself, in one language. Area names
+/// come from the template; this covers taxonomy categories and fallbacks.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LabelSet {
+    pub locale: String,
+    pub name: String, // Language name in that language, e.g. Deutsch
+    pub custom_area: String,
+    pub custom_area_description: String,
+    pub continued: String,
+    pub categories: HashMap<String, String>, // English taxonomy name -> label
+}
+
+impl LabelSet {
+    /// The label for a taxonomy category, or the English name if there is none.
+    pub fn category<'a>(&'a self, name: &'a str) -> &'a str {
+        self.categories
+            .get(name)
+            .map(String::as_str)
+            .unwrap_or(name)
+    }
+}
+
+pub fn label_sets() -> Result<Vec<LabelSet>> {
+    LABEL_SETS
+        .iter()
+        .map(|data| Ok(serde_json::from_str(data)?))
+        .collect()
+}
+
+/// The label set for `locale` (`de`, `de-AT`, `fr_CA`, ...), falling back to
+/// English for languages without one.
+pub fn label_set(locale: &str) -> Result<LabelSet> {
+    select_label_set(&label_sets()?, locale)
+        .cloned()
+        .ok_or_else(|| AppError::Configuration("No default label set".to_string()))
+}
+
+/// Picks the set for the language of `locale` from `sets`, or the English one.
+pub fn select_label_set<'a>(sets: &'a [LabelSet], locale: &str) -> Option<&'a LabelSet> {
+    sets.iter()
+        .find(|set| locale_matches(&set.locale, locale))
+        .or_else(|| sets.iter().find(|set| set.locale == DEFAULT_LOCALE))
+}
+
+/// Looks up a per-locale value, trying the full tag, then the bare language,
+/// then any other region of the language.
+pub fn localized<'a, T>(labels: &'a HashMap<String, T>, locale: &str) -> Option<&'a T> {
+    let tag = normalize_locale(locale);
+    let lang = language(locale);
+    labels
+        .iter()
+        .find(|(key, _)| normalize_locale(key) == tag)
+        .or_else(|| labels.iter().find(|(key, _)| normalize_locale(key) == lang))
+        .or_else(|| labels.iter().find(|(key, _)| locale_matches(key, locale)))
+        .map(|(_, value)| value)
+}
+
+/// Whether `candidate` is the language of `locale`, e.g. `de` for `de-AT`.
+fn locale_matches(candidate: &str, locale: &str) -> bool {
+    !locale.is_empty() && language(candidate) == language(locale)
+}
+
+fn language(locale: &str) -> String {
+    normalize_locale(locale)
+        .split('-')
+        .next()
+        .unwrap_or_default()
+        .to_string()
+}
+
+fn normalize_locale(locale: &str) -> String {
+    locale.trim().replace('_', "-").to_lowercase()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_label_sets_cover_taxonomy() {
+        let english = label_set("en").unwrap();
+        for set in label_sets().unwrap() {
+            for category in crate::taxonomy::TAXONOMY {
+                assert!(
+                    set.categories.contains_key(category.name),
+                    "{} has no label for {}",
+                    set.locale,
+                    category.name
+                );
+            }
+            assert_eq!(set.categories.len(), english.categories.len());
+        }
+    }
+
+    #[test]
+    fn test_locale_negotiation() {
+        assert_eq!(label_set("de_AT").unwrap().locale, "de");
+        assert_eq!(label_set("FR-ca").unwrap().locale, "fr");
+        assert_eq!(label_set("ja").unwrap().locale, "en");
+        assert_eq!(label_set("es").unwrap().category("Images"), "Imágenes");
+
+        let labels = HashMap::from([("pt".to_string(), 1), ("pt-BR".to_string(), 2)]);
+        assert_eq!(localized(&labels, "pt_br"), Some(&2));
+        assert_eq!(localized(&labels, "pt-PT"), Some(&1));
+        assert_eq!(localized(&labels, "it"), None);
+    }
+}
@@ -0,0 +1,370 @@
+use crate::error::{AppError, Result};
+use crate::johnny_decimal::JDStructure;
+use crate::organization_plan::{OrganizationPlan, PlannedOperation};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+/// How an organization is applied to disk. Stored in `AppSettings`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OrganizationMode {
+    #[default]
+    Move, // Move the originals into the structure
+    Symlink,
+    Hardlink,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkKind {
+    Symlink,
+    Hardlink,
+}
+
+impl LinkKind {
+    pub fn for_mode(mode: OrganizationMode) -> Option<Self> {
+        match mode {
+            OrganizationMode::Move => None,
+            OrganizationMode::Symlink => Some(LinkKind::Symlink),
+            OrganizationMode::Hardlink => Some(LinkKind::Hardlink),
+        }
+    }
+}
+
+/// A link in the farm whose original is gone or was replaced.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DanglingLink {
+    pub link: String,
+    pub target: Option<String>, // None for hard links, which do not record one
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LinkFarmReport {
+    pub created: Vec<String>,
+    pub repaired: Vec<String>,
+    pub removed: Vec<String>,
+    pub dangling: Vec<DanglingLink>, // Links removed because their original is missing
+}
+
+/// Materializes a `JDStructure` as a parallel tree of links under `root`,
+/// leaving the originals where they are.
+#[derive(Debug, Clone)]
+pub struct LinkFarm {
+    pub root: PathBuf,
+    pub kind: LinkKind,
+}
+
+#[allow(dead_code)]
+impl LinkFarm {
+    pub fn new(root: impl Into<PathBuf>, kind: LinkKind) -> Self {
+        Self {
+            root: root.into(),
+            kind,
+        }
+    }
+
+    /// The farm configured in the settings: `None` in move mode, an error
+    /// when a link mode has no farm folder.
+    pub fn from_settings(
+        mode: OrganizationMode,
+        link_farm_path: Option<&str>,
+    ) -> Result<Option<Self>> {
+        let Some(kind) = LinkKind::for_mode(mode) else {
+            return Ok(None);
+        };
+        match link_farm_path {
+            Some(root) if !root.is_empty() => Ok(Some(Self::new(root, kind))),
+            _ => Err(AppError::Configuration(
+                "Link mode needs a folder for the link tree".to_string(),
+            )),
+        }
+    }
+
+    /// The usual planner run against the farm root. `MoveFile` operations
+    /// name the original as source and the link as destination.
+    pub fn plan(&self, structure: &JDStructure) -> OrganizationPlan {
+        OrganizationPlan::for_structure(&self.farm_structure(structure))
+    }
+
+    /// Runs a plan from `plan` by linking instead of moving. Links that
+    /// already point at the right original are left alone.
+    pub fn execute_plan(&self, plan: &OrganizationPlan) -> Result<LinkFarmReport> {
+        let mut report = LinkFarmReport::default();
+
+        for operation in &plan.operations {
+            match operation {
+                PlannedOperation::CreateDirectory { path } => std::fs::create_dir_all(path)?,
+                PlannedOperation::MoveFile {
+                    source,
+                    destination,
+                } => {
+                    let link = Path::new(destination);
+                    if link.symlink_metadata().is_ok() {
+                        if self.links_to(link, Path::new(source)) {
+                            continue;
+                        }
+                        std::fs::remove_file(link)?;
+                        report.repaired.push(destination.clone());
+                    } else {
+                        report.created.push(destination.clone());
+                    }
+                    self.link(Path::new(source), link)?;
+                }
+                PlannedOperation::RenameDirectory {
+                    source,
+                    destination,
+                } => std::fs::rename(source, destination)?,
+                PlannedOperation::RemoveDirectory { path } => {
+                    if std::fs::read_dir(path).is_ok_and(|mut d| d.next().is_none()) {
+                        std::fs::remove_dir(path)?;
+                    }
+                }
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Brings the farm in line with `structure` after a rescan: creates
+    /// missing links, repoints stale ones, and removes links and folders the
+    /// structure no longer has, including links whose original is gone.
+    pub fn sync(&self, structure: &JDStructure) -> Result<LinkFarmReport> {
+        let plan = self.plan(structure);
+        let wanted: HashMap<PathBuf, PathBuf> = plan
+            .operations
+            .iter()
+            .filter_map(|op| match op {
+                PlannedOperation::MoveFile {
+                    source,
+                    destination,
+                } => Some((PathBuf::from(destination), PathBuf::from(source))),
+                _ => None,
+            })
+            .collect();
+
+        let dangling = self.dangling_links();
+        let mut removed = Vec::new();
+        for entry in self.entries(false) {
+            let path = entry.path();
+            let stale = match wanted.get(path) {
+                Some(original) => !original.exists(),
+                None => true,
+            };
+            if stale {
+                std::fs::remove_file(path)?;
+                removed.push(path.to_string_lossy().to_string());
+            }
+        }
+
+        // Originals that are missing get no link rather than a dangling one
+        let mut plan = plan;
+        plan.operations.retain(|op| match op {
+            PlannedOperation::MoveFile { source, .. } => Path::new(source).exists(),
+            _ => true,
+        });
+        let mut report = self.execute_plan(&plan)?;
+
+        for dir in self.entries(true) {
+            let path = dir.path();
+            if path != self.root && std::fs::read_dir(path)?.next().is_none() {
+                std::fs::remove_dir(path)?;
+            }
+        }
+
+        report.dangling = dangling
+            .into_iter()
+            .filter(|d| removed.contains(&d.link))
+            .collect();
+        report.removed = removed;
+        Ok(report)
+    }
+
+    /// Symlinks whose target no longer exists, and hard links whose original
+    /// was deleted or replaced so the link is the only copy left.
+    pub fn dangling_links(&self) -> Vec<DanglingLink> {
+        self.entries(false)
+            .filter_map(|entry| {
+                let path = entry.path();
+                let dangling = match self.kind {
+                    LinkKind::Symlink => !path.exists(),
+                    LinkKind::Hardlink => link_count(path) == Some(1),
+                };
+                dangling.then(|| DanglingLink {
+                    link: path.to_string_lossy().to_string(),
+                    target: std::fs::read_link(path)
+                        .ok()
+                        .map(|t| t.to_string_lossy().to_string()),
+                })
+            })
+            .collect()
+    }
+
+    fn farm_structure(&self, structure: &JDStructure) -> JDStructure {
+        JDStructure {
+            root_path: self.root.to_string_lossy().to_string(),
+            ..structure.clone()
+        }
+    }
+
+    /// Files and links (or, with `dirs`, folders deepest first) in the farm.
+    fn entries(&self, dirs: bool) -> impl Iterator<Item = walkdir::DirEntry> {
+        WalkDir::new(&self.root)
+            .contents_first(true)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(move |e| e.file_type().is_dir() == dirs)
+    }
+
+    fn links_to(&self, link: &Path, original: &Path) -> bool {
+        match self.kind {
+            LinkKind::Symlink => std::fs::read_link(link).is_ok_and(|t| t == original),
+            LinkKind::Hardlink => same_file(link, original),
+        }
+    }
+
+    fn link(&self, original: &Path, link: &Path) -> Result<()> {
+        if let Some(parent) = link.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        match self.kind {
+            LinkKind::Symlink => symlink(original, link),
+            LinkKind::Hardlink => std::fs::hard_link(original, link).map_err(|e| {
+                AppError::FileSystem(format!(
+                    "Cannot hard link {} (hard links need the same volume): {}",
+                    original.display(),
+                    e
+                ))
+            }),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn symlink(original: &Path, link: &Path) -> Result<()> {
+    Ok(std::os::unix::fs::symlink(original, link)?)
+}
+
+#[cfg(windows)]
+fn symlink(original: &Path, link: &Path) -> Result<()> {
+    std::os::windows::fs::symlink_file(original, link).map_err(|e| {
+        AppError::PermissionDenied(format!(
+            "Creating symlinks needs Developer Mode or admin rights: {}",
+            e
+        ))
+    })
+}
+
+#[cfg(unix)]
+fn link_count(path: &Path) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    std::fs::metadata(path).ok().map(|m| m.nlink())
+}
+
+#[cfg(not(unix))]
+fn link_count(_path: &Path) -> Option<u64> {
+    None
+}
+
+#[cfg(unix)]
+fn same_file(a: &Path, b: &Path) -> bool {
+    use std::os::unix::fs::MetadataExt;
+    match (std::fs::metadata(a), std::fs::metadata(b)) {
+        (Ok(a), Ok(b)) => a.dev() == b.dev() && a.ino() == b.ino(),
+        _ => false,
+    }
+}
+
+#[cfg(not(unix))]
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (std::fs::metadata(a), std::fs::metadata(b)) {
+        (Ok(a), Ok(b)) => a.len() == b.len() && a.modified().ok() == b.modified().ok(),
+        _ => false,
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::johnny_decimal::JohnnyDecimalEngine;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_symlink_farm_sync() {
+        let originals = TempDir::new().unwrap();
+        let farm_root = TempDir::new().unwrap();
+        let report = originals.path().join("report.pdf");
+        let photo = originals.path().join("photo.jpg");
+        std::fs::write(&report, "pdf").unwrap();
+        std::fs::write(&photo, "jpg").unwrap();
+
+        let files: Vec<_> = [&report, &photo]
+            .iter()
+            .map(|p| serde_json::json!({"path": p.to_str().unwrap(), "extension": p.extension().unwrap().to_str().unwrap()}))
+            .collect();
+        let engine = JohnnyDecimalEngine::new().unwrap();
+        let mut structure = engine.build_structure(&files, originals.path().to_str().unwrap());
+
+        let farm = LinkFarm::new(farm_root.path(), LinkKind::Symlink);
+        let first = farm.sync(&structure).unwrap();
+        assert_eq!(first.created.len(), 2);
+        assert!(farm.sync(&structure).unwrap().created.is_empty());
+        assert!(report.exists() && photo.exists());
+
+        let link = PathBuf::from(&first.created[0]);
+        assert_eq!(std::fs::read_to_string(&link).unwrap(), "pdf");
+
+        // The photo disappears from disk and from the rescanned structure
+        std::fs::remove_file(&photo).unwrap();
+        assert_eq!(farm.dangling_links().len(), 1);
+        structure.areas.pop();
+
+        let second = farm.sync(&structure).unwrap();
+        assert_eq!(second.removed.len(), 1);
+        assert_eq!(second.dangling.len(), 1);
+        assert!(farm.dangling_links().is_empty());
+        assert!(link.exists());
+        assert_eq!(std::fs::read_dir(farm_root.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn test_hardlink_farm_repairs_replaced_original() {
+        let originals = TempDir::new().unwrap();
+        let farm_root = TempDir::new().unwrap();
+        let notes = originals.path().join("notes.txt");
+        std::fs::write(&notes, "v1").unwrap();
+
+        let files = vec![serde_json::json!({"path": notes.to_str().unwrap(), "extension": "txt"})];
+        let engine = JohnnyDecimalEngine::new().unwrap();
+        let structure = engine.build_structure(&files, originals.path().to_str().unwrap());
+
+        let farm = LinkFarm::new(farm_root.path(), LinkKind::Hardlink);
+        let link = PathBuf::from(&farm.sync(&structure).unwrap().created[0]);
+
+        // Editors often save by replacing the file, which breaks a hard link
+        std::fs::remove_file(&notes).unwrap();
+        std::fs::write(&notes, "v2").unwrap();
+        assert_eq!(farm.dangling_links().len(), 1);
+
+        let report = farm.sync(&structure).unwrap();
+        assert_eq!(report.repaired, vec![link.to_string_lossy().to_string()]);
+        assert_eq!(std::fs::read_to_string(&link).unwrap(), "v2");
+        assert!(farm.dangling_links().is_empty());
+    }
+
+    #[test]
+    fn test_farm_from_settings() {
+        assert!(
+            LinkFarm::from_settings(OrganizationMode::Move, Some("/links"))
+                .unwrap()
+                .is_none()
+        );
+        let farm = LinkFarm::from_settings(OrganizationMode::Hardlink, Some("/links"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(farm.kind, LinkKind::Hardlink);
+        assert!(LinkFarm::from_settings(OrganizationMode::Symlink, None).is_err());
+        assert!(LinkFarm::from_settings(OrganizationMode::Symlink, Some("")).is_err());
+    }
+}
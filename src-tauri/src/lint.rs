@@ -0,0 +1,402 @@
+use crate::johnny_decimal::{JDStructure, JDValidationWarning, WarningSeverity};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Longest path the platform accepts.
+#[cfg(windows)]
+pub const PATH_MAX: usize = 260;
+#[cfg(target_os = "macos")]
+pub const PATH_MAX: usize = 1024;
+#[cfg(not(any(windows, target_os = "macos")))]
+pub const PATH_MAX: usize = 4096;
+
+const ILLEGAL_CHARACTERS: &[char] = &['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LintRule {
+    TooManyCategories,
+    SimilarCategoryNames,
+    EmptyItem,
+    OversizedItem,
+    IllegalCharacters,
+    PathTooLong,
+    AreasOutOfSequence,
+}
+
+impl LintRule {
+    pub const ALL: [LintRule; 7] = [
+        LintRule::TooManyCategories,
+        LintRule::SimilarCategoryNames,
+        LintRule::EmptyItem,
+        LintRule::OversizedItem,
+        LintRule::IllegalCharacters,
+        LintRule::PathTooLong,
+        LintRule::AreasOutOfSequence,
+    ];
+
+    /// Stable code reported in `JDValidationWarning::code`.
+    pub fn code(&self) -> &'static str {
+        match self {
+            LintRule::TooManyCategories => "JD101",
+            LintRule::SimilarCategoryNames => "JD102",
+            LintRule::EmptyItem => "JD103",
+            LintRule::OversizedItem => "JD104",
+            LintRule::IllegalCharacters => "JD105",
+            LintRule::PathTooLong => "JD106",
+            LintRule::AreasOutOfSequence => "JD107",
+        }
+    }
+
+    /// Used as `warning_type` and accepted in settings next to the code.
+    pub fn name(&self) -> &'static str {
+        match self {
+            LintRule::TooManyCategories => "too_many_categories",
+            LintRule::SimilarCategoryNames => "similar_category_names",
+            LintRule::EmptyItem => "empty_item",
+            LintRule::OversizedItem => "oversized_item",
+            LintRule::IllegalCharacters => "illegal_characters",
+            LintRule::PathTooLong => "path_too_long",
+            LintRule::AreasOutOfSequence => "areas_out_of_sequence",
+        }
+    }
+
+    pub fn default_severity(&self) -> WarningSeverity {
+        match self {
+            LintRule::IllegalCharacters | LintRule::PathTooLong => WarningSeverity::Error,
+            LintRule::EmptyItem | LintRule::AreasOutOfSequence => WarningSeverity::Info,
+            _ => WarningSeverity::Warning,
+        }
+    }
+
+    fn matches(&self, key: &str) -> bool {
+        key.eq_ignore_ascii_case(self.code()) || key.eq_ignore_ascii_case(self.name())
+    }
+}
+
+/// Stored in `AppSettings::lint`. Rules are referred to by code or name.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct LintSettings {
+    pub disabled_rules: Vec<String>,
+    pub severity_overrides: HashMap<String, WarningSeverity>,
+    pub max_categories_per_area: usize,
+    pub max_files_per_item: usize,
+}
+
+impl Default for LintSettings {
+    fn default() -> Self {
+        Self {
+            disabled_rules: Vec::new(),
+            severity_overrides: HashMap::new(),
+            max_categories_per_area: 10,
+            max_files_per_item: 1000,
+        }
+    }
+}
+
+impl LintSettings {
+    pub fn is_enabled(&self, rule: LintRule) -> bool {
+        !self.disabled_rules.iter().any(|key| rule.matches(key))
+    }
+
+    pub fn severity(&self, rule: LintRule) -> WarningSeverity {
+        self.severity_overrides
+            .iter()
+            .find(|(key, _)| rule.matches(key))
+            .map(|(_, severity)| *severity)
+            .unwrap_or_else(|| rule.default_severity())
+    }
+}
+
+/// A rule as configured, for listing in the settings screen.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LintRuleStatus {
+    pub rule: LintRule,
+    pub code: String,
+    pub enabled: bool,
+    pub severity: WarningSeverity,
+}
+
+/// Every rule with its effective state under `settings`.
+pub fn rule_statuses(settings: &LintSettings) -> Vec<LintRuleStatus> {
+    LintRule::ALL
+        .iter()
+        .map(|rule| LintRuleStatus {
+            rule: *rule,
+            code: rule.code().to_string(),
+            enabled: settings.is_enabled(*rule),
+            severity: settings.severity(*rule),
+        })
+        .collect()
+}
+
+/// Best-practice checks on top of `validate_structure`. Findings are sorted
+/// by severity, most severe first.
+pub fn lint_structure(
+    structure: &JDStructure,
+    settings: &LintSettings,
+) -> Vec<JDValidationWarning> {
+    let mut findings = Vec::new();
+    let mut report = |rule: LintRule, message: String, suggestion: &str| {
+        if settings.is_enabled(rule) {
+            findings.push(JDValidationWarning {
+                warning_type: rule.name().to_string(),
+                message,
+                suggestion: Some(suggestion.to_string()),
+                severity: settings.severity(rule),
+                code: Some(rule.code().to_string()),
+            });
+        }
+    };
+
+    if !is_sorted(structure.areas.iter().map(|a| a.number)) {
+        report(
+            LintRule::AreasOutOfSequence,
+            "Areas are not in ascending order".to_string(),
+            "Sort areas by number",
+        );
+    }
+
+    for area in &structure.areas {
+        check_name(&mut report, &format!("Area {}", area.number), area.label());
+
+        if area.categories.len() > settings.max_categories_per_area {
+            report(
+                LintRule::TooManyCategories,
+                format!(
+                    "Area {} has {} categories, more than {}",
+                    area.number,
+                    area.categories.len(),
+                    settings.max_categories_per_area
+                ),
+                "Move some categories to a new area",
+            );
+        }
+        if !is_sorted(area.categories.iter().map(|c| c.number)) {
+            report(
+                LintRule::AreasOutOfSequence,
+                format!(
+                    "Categories of area {} are not in ascending order",
+                    area.number
+                ),
+                "Sort categories by number",
+            );
+        }
+
+        for (i, category) in area.categories.iter().enumerate() {
+            check_name(
+                &mut report,
+                &format!("Category {:02}", category.number),
+                &category.name,
+            );
+
+            for other in &area.categories[i + 1..] {
+                if similar_names(&category.name, &other.name) {
+                    report(
+                        LintRule::SimilarCategoryNames,
+                        format!(
+                            "Categories {:02} '{}' and {:02} '{}' have nearly the same name",
+                            category.number, category.name, other.number, other.name
+                        ),
+                        "Merge the categories or give them distinct names",
+                    );
+                }
+            }
+
+            for item in &category.items {
+                check_name(&mut report, &format!("Item {}", item.number), &item.name);
+
+                if item.files.is_empty() {
+                    report(
+                        LintRule::EmptyItem,
+                        format!("Item {} has no files", item.number),
+                        "Remove the item or file something into it",
+                    );
+                } else if item.files.len() >= settings.max_files_per_item {
+                    report(
+                        LintRule::OversizedItem,
+                        format!("Item {} holds {} files", item.number, item.files.len()),
+                        "Split the item into several items",
+                    );
+                }
+
+                // The longest file inside the folder has to fit as well
+                let path = structure.item_path(area, category, item);
+                let longest_file = item
+                    .files
+                    .iter()
+                    .filter_map(|f| Path::new(f).file_name())
+                    .map(|name| name.len() + 1)
+                    .max()
+                    .unwrap_or(0);
+                let length = path.as_os_str().len() + longest_file;
+                if length > PATH_MAX {
+                    report(
+                        LintRule::PathTooLong,
+                        format!(
+                            "Paths in item {} are up to {} characters long, the limit is {}",
+                            item.number, length, PATH_MAX
+                        ),
+                        "Shorten the area, category, item or file names",
+                    );
+                }
+            }
+        }
+    }
+
+    findings.sort_by_key(|f| std::cmp::Reverse(f.severity));
+    findings
+}
+
+fn check_name(report: &mut impl FnMut(LintRule, String, &str), what: &str, name: &str) {
+    let illegal = name
+        .chars()
+        .any(|c| ILLEGAL_CHARACTERS.contains(&c) || c.is_control());
+    if illegal || name.ends_with(['.', ' ']) {
+        report(
+            LintRule::IllegalCharacters,
+            format!("{} name '{}' cannot be used as a folder name", what, name),
+            "Remove characters like < > : \" / \\ | ? * and trailing dots",
+        );
+    }
+}
+
+fn is_sorted(numbers: impl Iterator<Item = u8>) -> bool {
+    let numbers: Vec<u8> = numbers.collect();
+    numbers.windows(2).all(|w| w[0] <= w[1])
+}
+
+/// Equal after normalizing case, punctuation and plurals, or one or two
+/// edits apart for longer names.
+fn similar_names(a: &str, b: &str) -> bool {
+    let (a, b) = (normalize_name(a), normalize_name(b));
+    if a == b {
+        return true;
+    }
+    a.len().min(b.len()) >= 6 && edit_distance(&a, &b) <= 2
+}
+
+fn normalize_name(name: &str) -> String {
+    let normalized: String = name
+        .to_lowercase()
+        .chars()
+        .filter(|c| c.is_alphanumeric())
+        .collect();
+    normalized
+        .strip_suffix('s')
+        .map(str::to_string)
+        .unwrap_or(normalized)
+}
+
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let cost = usize::from(ca != *cb);
+            current.push(
+                (previous[j] + cost)
+                    .min(previous[j + 1] + 1)
+                    .min(current[j] + 1),
+            );
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::templates::builtin_template;
+
+    fn codes(findings: &[JDValidationWarning]) -> Vec<&str> {
+        findings.iter().filter_map(|f| f.code.as_deref()).collect()
+    }
+
+    #[test]
+    fn test_lint_structure() {
+        let mut structure = builtin_template("downloads").unwrap().instantiate("/dl");
+        for item in structure
+            .areas
+            .iter_mut()
+            .flat_map(|a| &mut a.categories)
+            .flat_map(|c| &mut c.items)
+        {
+            item.files.push("/dl/file".to_string());
+        }
+        assert!(lint_structure(&structure, &LintSettings::default()).is_empty());
+
+        structure.areas.swap(0, 1);
+        structure.areas[0].categories[1].name = "Tax & Financial Document".to_string();
+        structure.areas[0].categories[2].name = "Legal: Documents".to_string();
+        structure.areas[0].categories[3].items[0].files.clear();
+        structure.areas[0].categories[3].items[1].files = vec!["/dl/x".to_string(); 1000];
+
+        let findings = lint_structure(&structure, &LintSettings::default());
+        assert_eq!(codes(&findings), vec!["JD105", "JD104", "JD107", "JD103"]);
+        assert_eq!(findings[0].severity, WarningSeverity::Error);
+
+        structure.areas[0].categories[1].name = "Tax, Financial Document".to_string();
+        structure.areas[0].categories[2].name = "Tax & Financial Documents".to_string();
+        assert!(codes(&lint_structure(&structure, &LintSettings::default())).contains(&"JD102"));
+    }
+
+    #[test]
+    fn test_lint_settings() {
+        let mut structure = builtin_template("general").unwrap().instantiate("/home");
+        structure.areas.reverse();
+
+        let default = lint_structure(&structure, &LintSettings::default());
+        assert_eq!(codes(&default), vec!["JD107"]);
+        assert_eq!(default[0].severity, WarningSeverity::Info);
+
+        let settings: LintSettings = serde_json::from_value(serde_json::json!({
+            "severity_overrides": {"areas_out_of_sequence": "error"}
+        }))
+        .unwrap();
+        assert_eq!(settings.max_files_per_item, 1000);
+        assert_eq!(
+            lint_structure(&structure, &settings)[0].severity,
+            WarningSeverity::Error
+        );
+
+        let settings = LintSettings {
+            disabled_rules: vec!["jd107".to_string()],
+            ..LintSettings::default()
+        };
+        assert!(lint_structure(&structure, &settings).is_empty());
+
+        let statuses = rule_statuses(&settings);
+        assert_eq!(statuses.len(), LintRule::ALL.len());
+        assert!(statuses.iter().all(|s| s.enabled == (s.code != "JD107")));
+    }
+
+    #[test]
+    fn test_path_length_counts_file_names() {
+        let mut structure = builtin_template("downloads").unwrap().instantiate("/home");
+        let item = &structure.areas[0].categories[0].items[0];
+        let folder = structure
+            .item_path(&structure.areas[0], &structure.areas[0].categories[0], item)
+            .as_os_str()
+            .len();
+
+        // The folder fits, but not with this file inside
+        let name = "f".repeat(PATH_MAX - folder);
+        structure.areas[0].categories[0].items[0].files = vec![format!("/home/{}", name)];
+        let settings = LintSettings {
+            disabled_rules: vec!["empty_item".to_string()],
+            ..LintSettings::default()
+        };
+        let findings = lint_structure(&structure, &settings);
+        assert_eq!(codes(&findings), vec!["JD106"]);
+        assert!(findings[0]
+            .message
+            .contains(&format!("{} characters", PATH_MAX + 1)));
+    }
+}
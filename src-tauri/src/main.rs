@@ -1,31 +1,118 @@
 // Prevents additional console window on Windows in release, DO NOT REMOVE!!
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
+mod ai_provider;
 mod ai_service;
+mod batch_analysis;
+mod capacity;
+mod categorization;
+mod clustering;
 mod commands;
+mod content_extraction;
+mod corrections;
 mod database;
+mod drift;
 mod error;
+mod exchange;
 mod file_operations;
+mod jd_id;
+mod jdex_export;
 mod johnny_decimal;
+mod labels;
+mod link_farm;
+mod lint;
+mod model_manager;
+mod ollama_process;
+mod organization_plan;
+mod organization_scheme;
+mod semantic_index;
+mod structure_diff;
+mod taxonomy;
+mod templates;
 
 use commands::*;
-use error::Result;
+use error::{AppError, Result};
+use tauri::{Manager, RunEvent};
 
 #[tokio::main]
 async fn main() -> Result<()> {
     // Initialize logging
     env_logger::init();
 
-    tauri::Builder::default()
+    let app = tauri::Builder::default()
         .invoke_handler(tauri::generate_handler![
             greet,
             get_app_info,
             health_check,
             test_scan_files,
-            test_ai_analysis
+            test_ai_analysis,
+            start_ai_service,
+            stop_ai_service,
+            ai_service_status,
+            pull_model,
+            delete_model,
+            show_model,
+            switch_model,
+            analyze_files,
+            categorize_file,
+            cancel_batch_analysis,
+            record_correction,
+            analysis_cache_stats,
+            purge_analysis_cache,
+            detect_structure_drift,
+            reconcile_structure_with_disk,
+            plan_drift_reconciliation,
+            repair_structure,
+            execute_plan,
+            undo_last_plan,
+            validate_scheme_layout,
+            plan_scheme_organization,
+            export_jdex,
+            export_structure_file,
+            import_structure_file,
+            merge_structure_file,
+            list_structure_templates,
+            save_structure_as_template,
+            instantiate_template,
+            delete_structure_template,
+            list_structure_revisions,
+            diff_structure_revisions,
+            restore_structure_revision,
+            lookup_jd_id,
+            structure_capacity_report,
+            propose_category_split,
+            sync_link_farm,
+            index_structure_semantics,
+            assign_file_semantically,
+            find_similar_files,
+            validate_saved_structure,
+            list_lint_rules
         ])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application");
+
+    // The database lives in the per-user app data folder
+    let data_dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| AppError::Configuration(e.to_string()))?;
+    std::fs::create_dir_all(&data_dir)?;
+    let db_path = data_dir.join("organizer.db");
+    app.manage(AppState::new(&db_path.to_string_lossy()).await?);
+
+    app.run(|handle, event| {
+        // Stop the Ollama process this app started before the app goes away
+        if let RunEvent::Exit = event {
+            let state = handle.state::<AppState>();
+            let stopped = tokio::task::block_in_place(|| {
+                tokio::runtime::Handle::current()
+                    .block_on(async { state.ai.write().await.stop().await })
+            });
+            if let Err(e) = stopped {
+                log::warn!("Could not stop the AI service: {}", e);
+            }
+        }
+    });
 
     Ok(())
 }
@@ -0,0 +1,270 @@
+use crate::ai_provider::ProviderConfig;
+use crate::error::{AppError, Result};
+use reqwest::{Client, Method, RequestBuilder};
+use serde::{Deserialize, Serialize};
+
+/// One line of the `/api/pull` stream. Download steps carry byte counts.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PullProgress {
+    pub status: String,
+    #[serde(default)]
+    pub digest: Option<String>,
+    #[serde(default)]
+    pub total: Option<u64>,
+    #[serde(default)]
+    pub completed: Option<u64>,
+}
+
+impl PullProgress {
+    /// Share of the current layer downloaded, when known.
+    pub fn fraction(&self) -> Option<f64> {
+        match (self.completed, self.total) {
+            (Some(completed), Some(total)) if total > 0 => Some(completed as f64 / total as f64),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModelDetails {
+    pub name: String,
+    pub size: Option<u64>, // Bytes on disk
+    pub family: Option<String>,
+    pub parameter_size: Option<String>, // e.g. "3.2B"
+    pub quantization: Option<String>,   // e.g. "Q4_K_M"
+    pub context_length: Option<u64>,
+}
+
+/// Installs, removes and inspects models on an Ollama server.
+pub struct ModelManager {
+    client: Client,
+    config: ProviderConfig,
+}
+
+#[allow(dead_code)]
+impl ModelManager {
+    pub fn new(config: ProviderConfig) -> Result<Self> {
+        // Pulls run for minutes, so only connecting is bounded by the timeout
+        let client = Client::builder()
+            .connect_timeout(std::time::Duration::from_secs(config.timeout_secs))
+            .build()?;
+        Ok(Self { client, config })
+    }
+
+    /// Downloads `model`, calling `on_progress` for every status line the
+    /// server streams. Returns once the server reports success.
+    pub async fn pull(
+        &self,
+        model: &str,
+        mut on_progress: impl FnMut(&PullProgress) + Send,
+    ) -> Result<()> {
+        let mut response = self
+            .request(Method::POST, "/api/pull")
+            .json(&serde_json::json!({"model": model, "stream": true}))
+            .send()
+            .await?
+            .error_for_status()?;
+
+        let mut buffer = Vec::new();
+        let mut succeeded = false;
+        while let Some(chunk) = response.chunk().await? {
+            buffer.extend_from_slice(&chunk);
+            while let Some(end) = buffer.iter().position(|b| *b == b'\n') {
+                let line: Vec<u8> = buffer.drain(..=end).collect();
+                succeeded |= handle_line(&line, &mut on_progress)?;
+            }
+        }
+        succeeded |= handle_line(&buffer, &mut on_progress)?;
+
+        if !succeeded {
+            return Err(AppError::AiService(format!(
+                "Pull of {} ended before it finished",
+                model
+            )));
+        }
+        Ok(())
+    }
+
+    pub async fn delete(&self, model: &str) -> Result<()> {
+        let response = self
+            .request(Method::DELETE, "/api/delete")
+            .json(&serde_json::json!({"model": model}))
+            .send()
+            .await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Err(AppError::AiService(format!(
+                "Model {} is not installed",
+                model
+            )));
+        }
+        response.error_for_status()?;
+        Ok(())
+    }
+
+    pub async fn show(&self, model: &str) -> Result<ModelDetails> {
+        let response = self
+            .request(Method::POST, "/api/show")
+            .json(&serde_json::json!({"model": model}))
+            .send()
+            .await?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Err(AppError::AiService(format!(
+                "Model {} is not installed",
+                model
+            )));
+        }
+        let data: serde_json::Value = response.error_for_status()?.json().await?;
+
+        let tags: serde_json::Value = self
+            .request(Method::GET, "/api/tags")
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let size = tags["models"].as_array().and_then(|models| {
+            models
+                .iter()
+                .find(|m| same_model(m["name"].as_str().unwrap_or_default(), model))
+                .and_then(|m| m["size"].as_u64())
+        });
+
+        Ok(details_from_show(model, &data, size))
+    }
+
+    fn request(&self, method: Method, path: &str) -> RequestBuilder {
+        let url = format!("{}{}", self.config.base_url.trim_end_matches('/'), path);
+        let request = self.client.request(method, url);
+        match &self.config.auth_header {
+            Some(value) => request.header(reqwest::header::AUTHORIZATION, value),
+            None => request,
+        }
+    }
+}
+
+/// Whether two model names refer to the same model; a missing tag means
+/// `latest`.
+pub fn same_model(a: &str, b: &str) -> bool {
+    let tagged = |name: &str| {
+        if name.contains(':') {
+            name.to_string()
+        } else {
+            format!("{}:latest", name)
+        }
+    };
+    tagged(a) == tagged(b)
+}
+
+/// Reads an `/api/show` reply. The context length lives under an
+/// architecture-specific key such as `llama.context_length`.
+pub fn details_from_show(model: &str, data: &serde_json::Value, size: Option<u64>) -> ModelDetails {
+    let text = |value: &serde_json::Value| value.as_str().map(str::to_string);
+    let info = &data["model_info"];
+    let context_length = info["general.architecture"]
+        .as_str()
+        .and_then(|arch| info[format!("{}.context_length", arch)].as_u64());
+
+    ModelDetails {
+        name: model.to_string(),
+        size,
+        family: text(&data["details"]["family"]),
+        parameter_size: text(&data["details"]["parameter_size"]),
+        quantization: text(&data["details"]["quantization_level"]),
+        context_length,
+    }
+}
+
+/// Reports one stream line. True when it is the final `success` status.
+fn handle_line(line: &[u8], on_progress: &mut impl FnMut(&PullProgress)) -> Result<bool> {
+    let line = String::from_utf8_lossy(line);
+    let line = line.trim();
+    if line.is_empty() {
+        return Ok(false);
+    }
+
+    let value: serde_json::Value = serde_json::from_str(line)?;
+    if let Some(error) = value["error"].as_str() {
+        return Err(AppError::AiService(format!("Pull failed: {}", error)));
+    }
+    let progress: PullProgress = serde_json::from_value(value)?;
+    on_progress(&progress);
+    Ok(progress.status == "success")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai_provider::AiBackendSettings;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Answers one request with `body` sent in two chunks that split a line.
+    async fn serve_chunked(body: &'static str) -> ProviderConfig {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut buffer = vec![0; 4096];
+            let _request = socket.read(&mut buffer).await.unwrap();
+            let (first, second) = body.split_at(body.len() / 2);
+            let mut response =
+                "HTTP/1.1 200 OK\r\nContent-Type: application/x-ndjson\r\nTransfer-Encoding: chunked\r\n\r\n"
+                    .to_string();
+            for part in [first, second] {
+                response.push_str(&format!("{:x}\r\n{}\r\n", part.len(), part));
+            }
+            response.push_str("0\r\n\r\n");
+            socket.write_all(response.as_bytes()).await.unwrap();
+        });
+        ProviderConfig {
+            base_url: address,
+            ..AiBackendSettings::default().ollama
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pull_streams_progress() {
+        let config = serve_chunked(concat!(
+            "{\"status\":\"pulling manifest\"}\n",
+            "{\"status\":\"pulling 6a0746a1ec1a\",\"digest\":\"sha256:6a07\",\"total\":100,\"completed\":40}\n",
+            "{\"status\":\"pulling 6a0746a1ec1a\",\"digest\":\"sha256:6a07\",\"total\":100,\"completed\":100}\n",
+            "{\"status\":\"success\"}\n"
+        ))
+        .await;
+        let manager = ModelManager::new(config).unwrap();
+
+        let mut events = Vec::new();
+        manager
+            .pull("llama3.2:1b", |p| events.push(p.clone()))
+            .await
+            .unwrap();
+        assert_eq!(events.len(), 4);
+        assert_eq!(events[1].fraction(), Some(0.4));
+        assert_eq!(events[3].status, "success");
+
+        let failing = ModelManager::new(
+            serve_chunked(
+                "{\"status\":\"pulling manifest\"}\n{\"error\":\"file does not exist\"}\n",
+            )
+            .await,
+        )
+        .unwrap();
+        let error = failing.pull("nope", |_| {}).await.unwrap_err();
+        assert!(error.to_string().contains("file does not exist"));
+    }
+
+    #[test]
+    fn test_details_from_show() {
+        let data = serde_json::json!({
+            "details": {"family": "llama", "parameter_size": "1.2B", "quantization_level": "Q8_0"},
+            "model_info": {"general.architecture": "llama", "llama.context_length": 131072}
+        });
+        let details = details_from_show("llama3.2:1b", &data, Some(1_321_098_329));
+        assert_eq!(details.quantization.as_deref(), Some("Q8_0"));
+        assert_eq!(details.context_length, Some(131072));
+        assert_eq!(details.size, Some(1_321_098_329));
+
+        assert!(same_model("llama3.2", "llama3.2:latest"));
+        assert!(!same_model("llama3.2", "llama3.2:1b"));
+    }
+}
@@ -0,0 +1,478 @@
+use crate::ai_provider::{AiProvider, OllamaProvider, ProviderConfig};
+use crate::error::{AppError, Result};
+use serde::{Deserialize, Serialize};
+use std::process::Stdio;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::process::{Child, Command};
+use tokio::sync::watch;
+use tokio::task::JoinHandle;
+use tokio::time::{sleep, timeout, Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProcessState {
+    Stopped,
+    Starting,
+    Running,
+    Unresponsive, // Alive but failing health checks
+    Restarting,
+    External, // Someone else runs the server; it is left alone
+    Failed,   // Crashed more often than `max_restarts`
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProcessStatus {
+    pub state: ProcessState,
+    pub pid: Option<u32>,
+    pub restarts: u32,
+    pub last_message: Option<String>, // Last stderr line, or why it stopped
+}
+
+#[derive(Debug, Clone)]
+pub struct SupervisorOptions {
+    pub command: Vec<String>,
+    pub ready_timeout: Duration,
+    pub initial_poll: Duration, // Doubled after every failed readiness check
+    pub max_poll: Duration,
+    pub health_interval: Duration,
+    pub max_failed_checks: u32, // Failed health checks in a row before a restart
+    pub max_restarts: u32,
+    pub shutdown_grace: Duration,
+}
+
+impl Default for SupervisorOptions {
+    fn default() -> Self {
+        Self {
+            command: vec!["ollama".to_string(), "serve".to_string()],
+            ready_timeout: Duration::from_secs(30),
+            initial_poll: Duration::from_millis(100),
+            max_poll: Duration::from_secs(2),
+            health_interval: Duration::from_secs(10),
+            max_failed_checks: 3,
+            max_restarts: 3,
+            shutdown_grace: Duration::from_secs(5),
+        }
+    }
+}
+
+/// Owns an `ollama serve` child process: starts it, watches its health,
+/// restarts it after crashes and stops it gracefully.
+pub struct OllamaSupervisor {
+    config: ProviderConfig,
+    options: SupervisorOptions,
+    status: Arc<Mutex<ProcessStatus>>,
+    shutdown: Option<watch::Sender<bool>>,
+    monitor: Option<JoinHandle<()>>,
+}
+
+#[allow(dead_code)]
+impl OllamaSupervisor {
+    pub fn new(config: ProviderConfig, options: SupervisorOptions) -> Self {
+        Self {
+            config,
+            options,
+            status: Arc::new(Mutex::new(ProcessStatus {
+                state: ProcessState::Stopped,
+                pid: None,
+                restarts: 0,
+                last_message: None,
+            })),
+            shutdown: None,
+            monitor: None,
+        }
+    }
+
+    pub fn status(&self) -> ProcessStatus {
+        self.status.lock().unwrap().clone()
+    }
+
+    /// Uses a server that is already up, or spawns one and waits until it
+    /// answers.
+    pub async fn start(&mut self) -> Result<ProcessState> {
+        if let Some(monitor) = &self.monitor {
+            if !monitor.is_finished() {
+                return Ok(self.status().state);
+            }
+            // The monitor gave up after too many restarts; start afresh
+            self.monitor = None;
+            self.shutdown = None;
+            self.status.lock().unwrap().restarts = 0;
+        }
+        let probe = OllamaProvider::new(self.config.clone())?;
+        if probe.health().await.available {
+            set_state(&self.status, ProcessState::External);
+            return Ok(ProcessState::External);
+        }
+
+        set_state(&self.status, ProcessState::Starting);
+        let child = match spawn(&self.config, &self.options, &self.status) {
+            Ok(child) => child,
+            Err(e) => return Err(self.fail(e)),
+        };
+        let child = match wait_ready(&probe, child, &self.options).await {
+            Ok(child) => child,
+            Err(e) => return Err(self.fail(e)),
+        };
+        set_state(&self.status, ProcessState::Running);
+
+        let (shutdown, stop) = watch::channel(false);
+        self.shutdown = Some(shutdown);
+        self.monitor = Some(tokio::spawn(supervise(
+            probe,
+            child,
+            self.config.clone(),
+            self.options.clone(),
+            self.status.clone(),
+            stop,
+        )));
+        Ok(ProcessState::Running)
+    }
+
+    /// Stops a server this supervisor started. External servers keep running.
+    pub async fn stop(&mut self) -> Result<()> {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(true);
+        }
+        if let Some(monitor) = self.monitor.take() {
+            monitor
+                .await
+                .map_err(|e| AppError::AiService(format!("Ollama monitor failed: {}", e)))?;
+        }
+        let mut status = self.status.lock().unwrap();
+        status.state = ProcessState::Stopped;
+        status.pid = None;
+        Ok(())
+    }
+
+    fn fail(&self, error: AppError) -> AppError {
+        let mut status = self.status.lock().unwrap();
+        status.state = ProcessState::Failed;
+        status.pid = None;
+        let reason = match error {
+            AppError::AiService(message) => message,
+            other => other.to_string(),
+        };
+        let detail = match &status.last_message {
+            Some(stderr) => format!("{} ({})", reason, stderr),
+            None => reason,
+        };
+        status.last_message = Some(detail.clone());
+        AppError::AiService(format!("Failed to start Ollama: {}", detail))
+    }
+}
+
+impl Drop for OllamaSupervisor {
+    fn drop(&mut self) {
+        // The monitor shuts the child down; `kill_on_drop` covers a runtime
+        // that is already gone
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(true);
+        }
+    }
+}
+
+fn set_state(status: &Mutex<ProcessStatus>, state: ProcessState) {
+    status.lock().unwrap().state = state;
+}
+
+fn spawn(
+    config: &ProviderConfig,
+    options: &SupervisorOptions,
+    status: &Arc<Mutex<ProcessStatus>>,
+) -> Result<Child> {
+    let (program, args) = options
+        .command
+        .split_first()
+        .ok_or_else(|| AppError::Configuration("Empty Ollama command".to_string()))?;
+    let host = config
+        .base_url
+        .split("://")
+        .last()
+        .unwrap_or_default()
+        .trim_end_matches('/');
+
+    let mut child = Command::new(program)
+        .args(args)
+        .env("OLLAMA_HOST", host)
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| AppError::AiService(format!("Failed to spawn {}: {}", program, e)))?;
+    status.lock().unwrap().pid = child.id();
+
+    // Ollama logs to stderr; keep it in our logs and the last line for errors
+    if let Some(stderr) = child.stderr.take() {
+        let status = status.clone();
+        tokio::spawn(async move {
+            let mut lines = BufReader::new(stderr).lines();
+            while let Ok(Some(line)) = lines.next_line().await {
+                log::info!("ollama: {}", line);
+                status.lock().unwrap().last_message = Some(line);
+            }
+        });
+    }
+    Ok(child)
+}
+
+/// Polls the server with growing pauses until it answers, the child exits
+/// or `ready_timeout` passes.
+async fn wait_ready(
+    probe: &OllamaProvider,
+    mut child: Child,
+    options: &SupervisorOptions,
+) -> Result<Child> {
+    let deadline = Instant::now() + options.ready_timeout;
+    let mut pause = options.initial_poll;
+    loop {
+        if let Some(exit) = child.try_wait()? {
+            // Give the stderr reader a moment to record why
+            sleep(Duration::from_millis(50)).await;
+            return Err(AppError::AiService(format!(
+                "Ollama exited during startup with {}",
+                exit
+            )));
+        }
+        if probe.health().await.available {
+            return Ok(child);
+        }
+        if Instant::now() >= deadline {
+            terminate(&mut child, options.shutdown_grace).await;
+            return Err(AppError::AiService(format!(
+                "Ollama did not become ready within {:?}",
+                options.ready_timeout
+            )));
+        }
+        sleep(pause.min(deadline.saturating_duration_since(Instant::now()))).await;
+        pause = (pause * 2).min(options.max_poll);
+    }
+}
+
+/// Watches the child until shutdown: checks health every `health_interval`
+/// and restarts the server after a crash or repeated failed checks.
+async fn supervise(
+    probe: OllamaProvider,
+    mut child: Child,
+    config: ProviderConfig,
+    options: SupervisorOptions,
+    status: Arc<Mutex<ProcessStatus>>,
+    mut stop: watch::Receiver<bool>,
+) {
+    let mut failed_checks = 0;
+    loop {
+        let crashed = tokio::select! {
+            exit = child.wait() => Some(match exit {
+                Ok(exit) => format!("Ollama exited with {}", exit),
+                Err(e) => e.to_string(),
+            }),
+            _ = sleep(options.health_interval) => {
+                if probe.health().await.available {
+                    failed_checks = 0;
+                    set_state(&status, ProcessState::Running);
+                    None
+                } else {
+                    failed_checks += 1;
+                    set_state(&status, ProcessState::Unresponsive);
+                    (failed_checks >= options.max_failed_checks).then(|| {
+                        format!("Ollama failed {} health checks", failed_checks)
+                    })
+                }
+            }
+            _ = stop.changed() => {
+                terminate(&mut child, options.shutdown_grace).await;
+                return;
+            }
+        };
+        let reason = match crashed {
+            Some(reason) => reason,
+            None => continue,
+        };
+
+        log::warn!("{}", reason);
+        terminate(&mut child, options.shutdown_grace).await;
+
+        // Failed respawns use up the restart budget like crashes do
+        let mut reason = reason;
+        child = loop {
+            let restarts = {
+                let mut status = status.lock().unwrap();
+                status.last_message = Some(reason);
+                status.pid = None;
+                status.restarts += 1;
+                status.restarts
+            };
+            if restarts > options.max_restarts {
+                set_state(&status, ProcessState::Failed);
+                return;
+            }
+
+            set_state(&status, ProcessState::Restarting);
+            tokio::select! {
+                _ = sleep(options.initial_poll * 2u32.pow(restarts)) => {},
+                _ = stop.changed() => return,
+            }
+            let restarted = match spawn(&config, &options, &status) {
+                Ok(child) => wait_ready(&probe, child, &options).await,
+                Err(e) => Err(e),
+            };
+            match restarted {
+                Ok(new_child) => break new_child,
+                Err(e) => {
+                    log::error!("Could not restart Ollama: {}", e);
+                    reason = e.to_string();
+                }
+            }
+        };
+        failed_checks = 0;
+        set_state(&status, ProcessState::Running);
+    }
+}
+
+/// Asks the child to exit, then kills it after `grace`.
+async fn terminate(child: &mut Child, grace: Duration) {
+    if matches!(child.try_wait(), Ok(Some(_))) {
+        return;
+    }
+    #[cfg(unix)]
+    if let Some(pid) = child.id() {
+        let _ = Command::new("kill")
+            .args(["-TERM", &pid.to_string()])
+            .status()
+            .await;
+    }
+    if timeout(grace, child.wait()).await.is_err() {
+        let _ = child.kill().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai_provider::AiBackendSettings;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    fn config(base_url: String) -> ProviderConfig {
+        ProviderConfig {
+            base_url,
+            timeout_secs: 1,
+            ..AiBackendSettings::default().ollama
+        }
+    }
+
+    #[tokio::test]
+    async fn test_external_server_is_left_alone() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            while let Ok((mut socket, _)) = listener.accept().await {
+                let mut buffer = vec![0; 4096];
+                let _request = socket.read(&mut buffer).await.unwrap();
+                let body = r#"{"models":[]}"#;
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(),
+                    body
+                );
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+
+        let options = SupervisorOptions {
+            command: vec!["does-not-exist".to_string()],
+            ..SupervisorOptions::default()
+        };
+        let mut supervisor = OllamaSupervisor::new(config(address), options);
+        assert_eq!(supervisor.start().await.unwrap(), ProcessState::External);
+        assert_eq!(supervisor.status().pid, None);
+        supervisor.stop().await.unwrap();
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_startup_crash_reports_stderr() {
+        let port = TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let options = SupervisorOptions {
+            command: [
+                "sh",
+                "-c",
+                "echo 'Error: listen tcp: bind: address in use' >&2; exit 1",
+            ]
+            .iter()
+            .map(|s| s.to_string())
+            .collect(),
+            ready_timeout: Duration::from_secs(5),
+            ..SupervisorOptions::default()
+        };
+        let mut supervisor =
+            OllamaSupervisor::new(config(format!("http://127.0.0.1:{}", port)), options);
+
+        let error = supervisor.start().await.unwrap_err().to_string();
+        assert!(error.contains("exited during startup"));
+        assert!(error.contains("address in use"));
+        assert_eq!(supervisor.status().state, ProcessState::Failed);
+    }
+
+    #[cfg(unix)]
+    #[tokio::test]
+    async fn test_failed_restarts_use_up_the_budget() {
+        // Only the readiness check after the first spawn is answered
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move {
+            let mut requests = 0;
+            while let Ok((mut socket, _)) = listener.accept().await {
+                requests += 1;
+                let mut buffer = vec![0; 4096];
+                let _request = socket.read(&mut buffer).await.unwrap();
+                let (code, body) = match requests {
+                    2 => ("200 OK", r#"{"models":[]}"#),
+                    _ => ("503 Service Unavailable", "{}"),
+                };
+                let response = format!(
+                    "HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    code,
+                    body.len(),
+                    body
+                );
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+
+        let options = SupervisorOptions {
+            command: ["sh", "-c", "sleep 0.3; exit 1"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            ready_timeout: Duration::from_secs(5),
+            initial_poll: Duration::from_millis(10),
+            health_interval: Duration::from_secs(60),
+            max_restarts: 2,
+            ..SupervisorOptions::default()
+        };
+        let mut supervisor = OllamaSupervisor::new(config(address), options);
+        assert_eq!(supervisor.start().await.unwrap(), ProcessState::Running);
+
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while supervisor.status().state != ProcessState::Failed {
+            assert!(Instant::now() < deadline, "supervisor never gave up");
+            sleep(Duration::from_millis(50)).await;
+        }
+        let status = supervisor.status();
+        assert_eq!(status.restarts, 3);
+        assert!(status
+            .last_message
+            .unwrap()
+            .contains("exited during startup"));
+
+        // A new start spawns again instead of reporting the dead monitor
+        let error = supervisor.start().await.unwrap_err().to_string();
+        assert!(error.contains("exited during startup"));
+    }
+}
@@ -0,0 +1,335 @@
+use crate::johnny_decimal::JDStructure;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeSet, HashSet};
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PlannedOperation {
+    CreateDirectory {
+        path: String,
+    },
+    MoveFile {
+        source: String,
+        destination: String,
+    },
+    RenameDirectory {
+        source: String,
+        destination: String,
+    },
+    /// Only removes the directory if it is empty when the plan runs
+    RemoveDirectory {
+        path: String,
+    },
+}
+
+impl PlannedOperation {
+    /// The operation that reverts this one once it has been executed.
+    pub fn inverse(&self) -> PlannedOperation {
+        match self {
+            PlannedOperation::CreateDirectory { path } => {
+                PlannedOperation::RemoveDirectory { path: path.clone() }
+            }
+            PlannedOperation::MoveFile {
+                source,
+                destination,
+            } => PlannedOperation::MoveFile {
+                source: destination.clone(),
+                destination: source.clone(),
+            },
+            PlannedOperation::RenameDirectory {
+                source,
+                destination,
+            } => PlannedOperation::RenameDirectory {
+                source: destination.clone(),
+                destination: source.clone(),
+            },
+            PlannedOperation::RemoveDirectory { path } => {
+                PlannedOperation::CreateDirectory { path: path.clone() }
+            }
+        }
+    }
+}
+
+/// An ordered list of file system changes that turns the disk into the
+/// layout described by a `JDStructure`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OrganizationPlan {
+    pub id: String,
+    pub root_path: String,
+    pub operations: Vec<PlannedOperation>,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+}
+
+#[allow(dead_code)]
+impl OrganizationPlan {
+    pub fn new(root_path: &str) -> Self {
+        Self {
+            id: Uuid::new_v4().to_string(),
+            root_path: root_path.to_string(),
+            operations: Vec::new(),
+            created_at: chrono::Utc::now(),
+        }
+    }
+
+    /// Plans the changes needed to go from `before` to `after`. Files are
+    /// expected to still be at the paths recorded in `after`; each one is
+    /// moved into the folder of the item that now owns it.
+    pub fn between(before: &JDStructure, after: &JDStructure) -> Self {
+        let mut plan = Self::new(&after.root_path);
+
+        let existing_dirs = structure_directories(before);
+        let target_dirs = structure_directories(after);
+
+        for dir in target_dirs.difference(&existing_dirs) {
+            plan.operations
+                .push(PlannedOperation::CreateDirectory { path: dir.clone() });
+        }
+
+        for (source, destination) in file_destinations(after) {
+            if source != destination {
+                plan.operations.push(PlannedOperation::MoveFile {
+                    source,
+                    destination,
+                });
+            }
+        }
+
+        // Deepest folders first so parents are empty by the time we reach them
+        let stale_dirs: Vec<&String> = existing_dirs.difference(&target_dirs).collect();
+        for dir in stale_dirs.into_iter().rev() {
+            plan.operations
+                .push(PlannedOperation::RemoveDirectory { path: dir.clone() });
+        }
+
+        plan
+    }
+
+    /// Plans the full folder tree of a structure from scratch.
+    pub fn for_structure(structure: &JDStructure) -> Self {
+        let empty = JDStructure {
+            areas: Vec::new(),
+            ..structure.clone()
+        };
+        Self::between(&empty, structure)
+    }
+
+    /// Plans moving each file into a folder given relative to `root_path`.
+    pub fn for_folders(root_path: &str, placements: &[(String, PathBuf)]) -> Self {
+        let mut plan = Self::new(root_path);
+        let root = Path::new(root_path);
+
+        // Include intermediate folders so undoing the plan removes them too
+        let folders: BTreeSet<PathBuf> = placements
+            .iter()
+            .flat_map(|(_, folder)| {
+                folder
+                    .ancestors()
+                    .filter(|a| !a.as_os_str().is_empty())
+                    .map(|a| root.join(a))
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        for folder in &folders {
+            if !folder.is_dir() {
+                plan.operations.push(PlannedOperation::CreateDirectory {
+                    path: folder.to_string_lossy().to_string(),
+                });
+            }
+        }
+
+        let mut claimed = HashSet::new();
+        for (file, folder) in placements {
+            let destination = unique_destination(&root.join(folder), file, &mut claimed);
+            if Path::new(file) != destination {
+                plan.operations.push(PlannedOperation::MoveFile {
+                    source: file.clone(),
+                    destination: destination.to_string_lossy().to_string(),
+                });
+            }
+        }
+
+        plan
+    }
+
+    /// The plan that reverts this one, operation by operation.
+    pub fn inverse(&self) -> Self {
+        Self {
+            operations: self
+                .operations
+                .iter()
+                .rev()
+                .map(PlannedOperation::inverse)
+                .collect(),
+            ..Self::new(&self.root_path)
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+}
+
+/// Maps every file recorded in the structure to its path inside the folder
+/// of its item, de-duplicating file names that collide within one item.
+pub fn file_destinations(structure: &JDStructure) -> Vec<(String, String)> {
+    let mut destinations = Vec::new();
+
+    for area in &structure.areas {
+        for category in &area.categories {
+            for item in &category.items {
+                let item_dir = structure.item_path(area, category, item);
+                let mut claimed = HashSet::new();
+
+                for file in &item.files {
+                    let destination = unique_destination(&item_dir, file, &mut claimed);
+                    destinations.push((file.clone(), destination.to_string_lossy().to_string()));
+                }
+            }
+        }
+    }
+
+    destinations
+}
+
+/// Picks a path for `file` inside `dir`, adding ` (2)`, ` (3)`, ... to the
+/// name when another file already claimed it.
+pub fn unique_destination(dir: &Path, file: &str, claimed: &mut HashSet<PathBuf>) -> PathBuf {
+    let file_name = Path::new(file)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| file.to_string());
+
+    let mut destination = dir.join(&file_name);
+    let mut suffix = 2;
+    while !claimed.insert(destination.clone()) {
+        let path = Path::new(&file_name);
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        destination = match path.extension() {
+            Some(ext) => dir.join(format!("{} ({}).{}", stem, suffix, ext.to_string_lossy())),
+            None => dir.join(format!("{} ({})", stem, suffix)),
+        };
+        suffix += 1;
+    }
+
+    destination
+}
+
+/// Rewrites the recorded file paths to the locations the plan moves them to.
+pub fn relocate_files(structure: &mut JDStructure) {
+    let mut destinations = file_destinations(structure)
+        .into_iter()
+        .map(|(_, destination)| destination);
+
+    for area in &mut structure.areas {
+        for category in &mut area.categories {
+            for item in &mut category.items {
+                for file in &mut item.files {
+                    if let Some(destination) = destinations.next() {
+                        *file = destination;
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// All area, category and item folders of a structure, parents before children.
+fn structure_directories(structure: &JDStructure) -> BTreeSet<String> {
+    let mut dirs = BTreeSet::new();
+
+    for area in &structure.areas {
+        dirs.insert(structure.area_path(area).to_string_lossy().to_string());
+        for category in &area.categories {
+            dirs.insert(
+                structure
+                    .category_path(area, category)
+                    .to_string_lossy()
+                    .to_string(),
+            );
+            for item in &category.items {
+                dirs.insert(
+                    structure
+                        .item_path(area, category, item)
+                        .to_string_lossy()
+                        .to_string(),
+                );
+            }
+        }
+    }
+
+    dirs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::johnny_decimal::{JDArea, JDCategory, JDItem};
+
+    fn structure(item_number: &str, files: Vec<String>) -> JDStructure {
+        JDStructure {
+            id: "test".to_string(),
+            name: "Test".to_string(),
+            root_path: "/root".to_string(),
+            system: None,
+            locale: None,
+            areas: vec![JDArea {
+                number: 20,
+                name: "20-29 Documents".to_string(),
+                description: None,
+                categories: vec![JDCategory {
+                    number: 21,
+                    name: "Reports".to_string(),
+                    description: None,
+                    items: vec![JDItem {
+                        number: item_number.to_string(),
+                        name: "Invoices".to_string(),
+                        description: None,
+                        files,
+                    }],
+                }],
+            }],
+            created_at: chrono::Utc::now(),
+            modified_at: chrono::Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_plan_between_structures() {
+        let before = structure("21.01", vec![]);
+        let after = structure("21.02", vec!["/root/a.pdf".to_string()]);
+
+        let plan = OrganizationPlan::between(&before, &after);
+
+        assert_eq!(
+            plan.operations,
+            vec![
+                PlannedOperation::CreateDirectory {
+                    path: "/root/20-29 Documents/21 Reports/21.02 Invoices".to_string()
+                },
+                PlannedOperation::MoveFile {
+                    source: "/root/a.pdf".to_string(),
+                    destination: "/root/20-29 Documents/21 Reports/21.02 Invoices/a.pdf"
+                        .to_string()
+                },
+                PlannedOperation::RemoveDirectory {
+                    path: "/root/20-29 Documents/21 Reports/21.01 Invoices".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_colliding_file_names_are_suffixed() {
+        let after = structure(
+            "21.01",
+            vec!["/a/report.pdf".to_string(), "/b/report.pdf".to_string()],
+        );
+
+        let destinations = file_destinations(&after);
+
+        assert!(destinations[0].1.ends_with("21.01 Invoices/report.pdf"));
+        assert!(destinations[1].1.ends_with("21.01 Invoices/report (2).pdf"));
+    }
+}
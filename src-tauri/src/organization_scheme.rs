@@ -0,0 +1,691 @@
+use crate::drift::{parse_area_folder, parse_category_folder, parse_item_folder};
+use crate::error::{AppError, Result};
+use crate::johnny_decimal::{
+    CategoryAssignment, JDArea, JDCategory, JDItem, JDStructure, JDValidationError,
+    JDValidationResult, JDValidationWarning, JohnnyDecimalEngine, WarningSeverity,
+};
+use crate::organization_plan::{relocate_files, OrganizationPlan};
+use crate::taxonomy::{classify_extension, FALLBACK_CATEGORY, TAXONOMY};
+use chrono::Datelike;
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+
+pub const JOHNNY_DECIMAL: &str = "JOHNNY_DECIMAL";
+pub const DATE_BASED: &str = "DATE";
+pub const FILE_TYPE: &str = "FILE_TYPE";
+pub const PARA: &str = "PARA";
+
+/// A way of laying out files under a root. The planner, the layout
+/// validator and the AI prompt all go through this trait, so every scheme
+/// produces an `OrganizationPlan` that runs through the same executor and
+/// can be undone the same way.
+pub trait OrganizationScheme: Send + Sync {
+    /// Identifier stored in `AnalysisRequest.organization_scheme`.
+    fn id(&self) -> &'static str;
+
+    fn display_name(&self) -> &'static str;
+
+    /// Folder, relative to the root, that a scanned file belongs in.
+    fn target_folder(&self, file: &serde_json::Value) -> PathBuf;
+
+    /// Checks folders (relative to the root) against the scheme's layout.
+    fn validate_layout(&self, folders: &[PathBuf]) -> JDValidationResult;
+
+    /// Describes the scheme to the AI model.
+    fn prompt_guidance(&self) -> String;
+
+    /// Example of the `category` value the model should answer with.
+    fn category_example(&self) -> &'static str;
+
+    fn plan(&self, root_path: &str, files: &[serde_json::Value]) -> Result<OrganizationPlan> {
+        let placements: Vec<(String, PathBuf)> = files
+            .iter()
+            .filter_map(|file| {
+                let path = file["path"].as_str()?;
+                Some((path.to_string(), self.target_folder(file)))
+            })
+            .collect();
+
+        Ok(OrganizationPlan::for_folders(root_path, &placements))
+    }
+
+    /// Lists the folders under `root` and validates them against the layout.
+    fn validate_root(&self, root: &str) -> Result<JDValidationResult> {
+        let root_path = Path::new(root);
+        if !root_path.is_dir() {
+            return Err(AppError::directory_not_found(root));
+        }
+
+        let folders: Vec<PathBuf> = walkdir::WalkDir::new(root_path)
+            .min_depth(1)
+            .max_depth(3)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_dir())
+            .filter_map(|e| e.path().strip_prefix(root_path).ok().map(Path::to_path_buf))
+            .collect();
+
+        Ok(self.validate_layout(&folders))
+    }
+}
+
+/// Looks up a scheme by the identifier used in analysis requests. Johnny
+/// Decimal uses `structure` when given and generates one otherwise.
+pub fn scheme_from_id(
+    id: &str,
+    structure: Option<JDStructure>,
+) -> Result<Box<dyn OrganizationScheme>> {
+    match id.to_uppercase().as_str() {
+        JOHNNY_DECIMAL => Ok(Box::new(JohnnyDecimalScheme::new(structure)?)),
+        DATE_BASED => Ok(Box::new(DateScheme)),
+        FILE_TYPE => Ok(Box::new(FileTypeScheme)),
+        PARA => Ok(Box::new(ParaScheme)),
+        other => Err(AppError::Configuration(format!(
+            "Unknown organization scheme: {}",
+            other
+        ))),
+    }
+}
+
+pub struct JohnnyDecimalScheme {
+    engine: JohnnyDecimalEngine,
+    structure: Option<JDStructure>,
+}
+
+impl JohnnyDecimalScheme {
+    pub fn new(structure: Option<JDStructure>) -> Result<Self> {
+        Ok(Self {
+            engine: JohnnyDecimalEngine::new()?,
+            structure,
+        })
+    }
+}
+
+impl OrganizationScheme for JohnnyDecimalScheme {
+    fn id(&self) -> &'static str {
+        JOHNNY_DECIMAL
+    }
+
+    fn display_name(&self) -> &'static str {
+        "Johnny Decimal"
+    }
+
+    fn target_folder(&self, file: &serde_json::Value) -> PathBuf {
+        let generated;
+        let structure = match &self.structure {
+            Some(structure) => structure,
+            None => {
+                generated = self.engine.build_structure(std::slice::from_ref(file), "");
+                &generated
+            }
+        };
+
+        let assignment = self.engine.assign_file(file, structure);
+        self.engine.assignment_item_folder(&assignment, structure)
+    }
+
+    fn validate_layout(&self, folders: &[PathBuf]) -> JDValidationResult {
+        let mut errors = Vec::new();
+
+        for folder in folders {
+            let parts = folder_parts(folder);
+            let valid = match parts.as_slice() {
+                [area] => parse_area_folder(area).is_some(),
+                [_, category] => parse_category_folder(category).is_some(),
+                [_, category, item] => {
+                    match (parse_category_folder(category), parse_item_folder(item)) {
+                        (Some((number, _)), Some((id, _))) => {
+                            id.starts_with(&format!("{:02}.", number))
+                        }
+                        _ => false,
+                    }
+                }
+                _ => true,
+            };
+
+            if !valid {
+                errors.push(layout_error(
+                    "invalid_folder_name",
+                    format!(
+                        "Folder '{}' does not follow Johnny Decimal numbering",
+                        folder.display()
+                    ),
+                ));
+            }
+        }
+
+        layout_result(errors, Vec::new())
+    }
+
+    fn prompt_guidance(&self) -> String {
+        "You are a file organization assistant using the Johnny Decimal system.
+Johnny Decimal areas (10-19, 20-29, 30-39, etc.) should be used for broad categories.
+Categories (11, 12, 13, etc.) should be specific within each area."
+            .to_string()
+    }
+
+    fn category_example(&self) -> &'static str {
+        "Area Name/Category Name"
+    }
+
+    fn plan(&self, root_path: &str, files: &[serde_json::Value]) -> Result<OrganizationPlan> {
+        let Some(structure) = &self.structure else {
+            let structure = self.engine.build_structure(files, root_path);
+            return Ok(OrganizationPlan::for_structure(&structure));
+        };
+
+        // Record each new file in the item it is assigned to and move it there
+        let mut updated = structure.clone();
+        relocate_files(&mut updated);
+        let settled = updated.clone();
+        let recorded: HashSet<&String> = recorded_files(structure)
+            .chain(recorded_files(&settled))
+            .collect();
+        for file in files {
+            let Some(path) = file["path"].as_str() else {
+                continue;
+            };
+            if recorded.contains(&path.to_string()) {
+                continue;
+            }
+            let assignment = self.engine.assign_file(file, &updated);
+            assigned_item(&self.engine, &mut updated, &assignment)
+                .files
+                .push(path.to_string());
+        }
+
+        Ok(OrganizationPlan::between(&settled, &updated))
+    }
+}
+
+fn recorded_files(structure: &JDStructure) -> impl Iterator<Item = &String> {
+    structure
+        .areas
+        .iter()
+        .flat_map(|a| &a.categories)
+        .flat_map(|c| &c.items)
+        .flat_map(|i| &i.files)
+}
+
+/// The item of an assignment, added to the structure under the names
+/// `assignment_item_folder` gives it when the structure lacks it.
+fn assigned_item<'a>(
+    engine: &JohnnyDecimalEngine,
+    structure: &'a mut JDStructure,
+    assignment: &CategoryAssignment,
+) -> &'a mut JDItem {
+    let folder = engine.assignment_item_folder(assignment, structure);
+    let parts: Vec<String> = folder_parts(&folder)
+        .into_iter()
+        .map(str::to_string)
+        .collect();
+    let label = |index: usize| {
+        parts
+            .get(index)
+            .and_then(|part| part.split_once(' '))
+            .map(|(_, name)| name.to_string())
+            .unwrap_or_default()
+    };
+
+    let areas = &mut structure.areas;
+    let area = match areas
+        .iter()
+        .position(|a| a.number == assignment.area_number)
+    {
+        Some(index) => index,
+        None => {
+            areas.push(JDArea {
+                number: assignment.area_number,
+                name: parts.first().cloned().unwrap_or_default(),
+                description: None,
+                categories: Vec::new(),
+            });
+            areas.sort_by_key(|a| a.number);
+            areas
+                .iter()
+                .position(|a| a.number == assignment.area_number)
+                .unwrap_or_default()
+        }
+    };
+
+    let categories = &mut areas[area].categories;
+    let category = match categories
+        .iter()
+        .position(|c| c.number == assignment.category_number)
+    {
+        Some(index) => index,
+        None => {
+            categories.push(JDCategory {
+                number: assignment.category_number,
+                name: label(1),
+                description: None,
+                items: Vec::new(),
+            });
+            categories.sort_by_key(|c| c.number);
+            categories
+                .iter()
+                .position(|c| c.number == assignment.category_number)
+                .unwrap_or_default()
+        }
+    };
+
+    let items = &mut categories[category].items;
+    let item = match items
+        .iter()
+        .position(|i| i.number == assignment.item_number)
+    {
+        Some(index) => index,
+        None => {
+            items.push(JDItem {
+                number: assignment.item_number.clone(),
+                name: label(2),
+                description: None,
+                files: Vec::new(),
+            });
+            items.sort_by(|a, b| a.number.cmp(&b.number));
+            items
+                .iter()
+                .position(|i| i.number == assignment.item_number)
+                .unwrap_or_default()
+        }
+    };
+    &mut items[item]
+}
+
+/// Sorts files into `YYYY/MM` folders by modification date.
+pub struct DateScheme;
+
+impl OrganizationScheme for DateScheme {
+    fn id(&self) -> &'static str {
+        DATE_BASED
+    }
+
+    fn display_name(&self) -> &'static str {
+        "Date based (YYYY/MM)"
+    }
+
+    fn target_folder(&self, file: &serde_json::Value) -> PathBuf {
+        match file_date(file) {
+            Some(date) => {
+                PathBuf::from(format!("{:04}", date.year())).join(format!("{:02}", date.month()))
+            }
+            None => PathBuf::from("Undated"),
+        }
+    }
+
+    fn validate_layout(&self, folders: &[PathBuf]) -> JDValidationResult {
+        let mut errors = Vec::new();
+
+        for folder in folders {
+            let parts = folder_parts(folder);
+            let valid = match parts.as_slice() {
+                [year] => *year == "Undated" || is_year(year),
+                [year, month] => is_year(year) && is_month(month),
+                _ => true,
+            };
+
+            if !valid {
+                errors.push(layout_error(
+                    "invalid_date_folder",
+                    format!(
+                        "Folder '{}' is not a YYYY or YYYY/MM folder",
+                        folder.display()
+                    ),
+                ));
+            }
+        }
+
+        layout_result(errors, Vec::new())
+    }
+
+    fn prompt_guidance(&self) -> String {
+        "You are a file organization assistant that sorts files by date.
+Files go into year folders with month subfolders, based on when the file was created or last changed."
+            .to_string()
+    }
+
+    fn category_example(&self) -> &'static str {
+        "YYYY/MM"
+    }
+}
+
+/// One top-level folder per kind of file.
+pub struct FileTypeScheme;
+
+impl OrganizationScheme for FileTypeScheme {
+    fn id(&self) -> &'static str {
+        FILE_TYPE
+    }
+
+    fn display_name(&self) -> &'static str {
+        "File type folders"
+    }
+
+    fn target_folder(&self, file: &serde_json::Value) -> PathBuf {
+        PathBuf::from(classify_extension(&file_extension(file)).name)
+    }
+
+    fn validate_layout(&self, folders: &[PathBuf]) -> JDValidationResult {
+        let mut warnings = Vec::new();
+        let type_folders = file_type_folders();
+
+        for folder in folders {
+            if let [top] = folder_parts(folder).as_slice() {
+                if !type_folders.contains(top) {
+                    warnings.push(layout_warning(
+                        "unknown_type_folder",
+                        format!("Folder '{}' is not a file type folder", top),
+                        format!("Use one of: {}", type_folders.join(", ")),
+                    ));
+                }
+            }
+        }
+
+        layout_result(Vec::new(), warnings)
+    }
+
+    fn prompt_guidance(&self) -> String {
+        format!(
+            "You are a file organization assistant that groups files by type.
+Use exactly one of these folders: {}.",
+            file_type_folders().join(", ")
+        )
+    }
+
+    fn category_example(&self) -> &'static str {
+        "Reports and Documents"
+    }
+}
+
+/// Tiago Forte's PARA method: Projects, Areas, Resources and Archive.
+pub struct ParaScheme;
+
+const PARA_FOLDERS: &[&str] = &["Projects", "Areas", "Resources", "Archive"];
+
+/// Taxonomy areas PARA files as area material and as archive.
+const DOCUMENTS_AREA: u8 = 20;
+const ARCHIVES_AREA: u8 = 50;
+/// Files untouched for longer than this are considered archived.
+const PARA_ARCHIVE_AFTER_DAYS: i64 = 365;
+
+impl OrganizationScheme for ParaScheme {
+    fn id(&self) -> &'static str {
+        PARA
+    }
+
+    fn display_name(&self) -> &'static str {
+        "PARA (Projects/Areas/Resources/Archive)"
+    }
+
+    fn target_folder(&self, file: &serde_json::Value) -> PathBuf {
+        let path = Path::new(file["path"].as_str().unwrap_or(""));
+        let parent = path
+            .parent()
+            .and_then(|p| p.file_name())
+            .map(|n| n.to_string_lossy().to_string());
+        let extension = file_extension(file);
+
+        let stale = file_date(file)
+            .map(|date| (chrono::Utc::now() - date).num_days() > PARA_ARCHIVE_AFTER_DAYS)
+            .unwrap_or(false);
+        let category = classify_extension(&extension);
+        if stale || category.area == ARCHIVES_AREA {
+            return PathBuf::from("Archive");
+        }
+
+        if let Some(project) = parent
+            .as_deref()
+            .filter(|p| p.to_lowercase().contains("project"))
+        {
+            return PathBuf::from("Projects").join(project);
+        }
+
+        if category.area == DOCUMENTS_AREA {
+            return PathBuf::from("Areas").join(parent.unwrap_or_else(|| "General".to_string()));
+        }
+        PathBuf::from("Resources").join(category.name)
+    }
+
+    fn validate_layout(&self, folders: &[PathBuf]) -> JDValidationResult {
+        let mut errors = Vec::new();
+
+        for folder in folders {
+            if let [top] = folder_parts(folder).as_slice() {
+                if !PARA_FOLDERS.contains(top) {
+                    errors.push(layout_error(
+                        "invalid_para_folder",
+                        format!(
+                            "Top-level folder '{}' must be one of {}",
+                            top,
+                            PARA_FOLDERS.join(", ")
+                        ),
+                    ));
+                }
+            }
+        }
+
+        layout_result(errors, Vec::new())
+    }
+
+    fn prompt_guidance(&self) -> String {
+        "You are a file organization assistant using the PARA method.
+Projects are short-term efforts with a goal, Areas are ongoing responsibilities,
+Resources are topics of interest or reference material, and Archive holds inactive items."
+            .to_string()
+    }
+
+    fn category_example(&self) -> &'static str {
+        "Projects/Project Name"
+    }
+}
+
+fn file_extension(file: &serde_json::Value) -> String {
+    file["extension"]
+        .as_str()
+        .or_else(|| file["file_type"].as_str())
+        .unwrap_or("")
+        .to_lowercase()
+}
+
+fn file_date(file: &serde_json::Value) -> Option<chrono::DateTime<chrono::Utc>> {
+    ["modified", "modified_at", "created", "created_at"]
+        .iter()
+        .filter_map(|key| file[*key].as_str())
+        .find_map(|s| chrono::DateTime::parse_from_rfc3339(s).ok())
+        .map(|date| date.with_timezone(&chrono::Utc))
+}
+
+/// Taxonomy category names, the folders of the file type scheme.
+fn file_type_folders() -> Vec<&'static str> {
+    TAXONOMY
+        .iter()
+        .chain([&FALLBACK_CATEGORY])
+        .map(|c| c.name)
+        .collect()
+}
+
+fn folder_parts(folder: &Path) -> Vec<&str> {
+    folder
+        .components()
+        .filter_map(|c| match c {
+            Component::Normal(part) => part.to_str(),
+            _ => None,
+        })
+        .collect()
+}
+
+fn is_year(s: &str) -> bool {
+    s.len() == 4 && s.chars().all(|c| c.is_ascii_digit())
+}
+
+fn is_month(s: &str) -> bool {
+    s.len() == 2 && matches!(s.parse::<u8>(), Ok(1..=12))
+}
+
+fn layout_error(error_type: &str, message: String) -> JDValidationError {
+    JDValidationError {
+        error_type: error_type.to_string(),
+        message,
+        area_number: None,
+        category_number: None,
+    }
+}
+
+fn layout_warning(warning_type: &str, message: String, suggestion: String) -> JDValidationWarning {
+    JDValidationWarning {
+        warning_type: warning_type.to_string(),
+        message,
+        suggestion: Some(suggestion),
+        severity: WarningSeverity::Warning,
+        code: None,
+    }
+}
+
+fn layout_result(
+    errors: Vec<JDValidationError>,
+    warnings: Vec<JDValidationWarning>,
+) -> JDValidationResult {
+    JDValidationResult {
+        is_valid: errors.is_empty(),
+        errors,
+        warnings,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scheme_from_id() {
+        assert_eq!(
+            scheme_from_id("JOHNNY_DECIMAL", None).unwrap().id(),
+            JOHNNY_DECIMAL
+        );
+        assert_eq!(scheme_from_id("para", None).unwrap().id(), PARA);
+        assert!(scheme_from_id("DEWEY", None).is_err());
+    }
+
+    #[test]
+    fn test_date_scheme_plan() {
+        let files = vec![
+            serde_json::json!({ "path": "/root/a.jpg", "modified": "2024-03-05T10:00:00Z" }),
+            serde_json::json!({ "path": "/root/b.jpg" }),
+        ];
+
+        let plan = DateScheme.plan("/root", &files).unwrap();
+        let moves: Vec<_> = plan
+            .operations
+            .iter()
+            .filter_map(|op| match op {
+                crate::organization_plan::PlannedOperation::MoveFile { destination, .. } => {
+                    Some(destination.as_str())
+                }
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(moves, vec!["/root/2024/03/a.jpg", "/root/Undated/b.jpg"]);
+    }
+
+    #[test]
+    fn test_validate_layouts() {
+        let para = ParaScheme.validate_layout(&[
+            PathBuf::from("Projects"),
+            PathBuf::from("Projects/Website"),
+            PathBuf::from("Stuff"),
+        ]);
+        assert_eq!(para.errors.len(), 1);
+
+        let jd = JohnnyDecimalScheme::new(None).unwrap().validate_layout(&[
+            PathBuf::from("20-29 Documents"),
+            PathBuf::from("20-29 Documents/21 Reports"),
+            PathBuf::from("20-29 Documents/21 Reports/22.01 Invoices"),
+        ]);
+        assert_eq!(jd.errors.len(), 1);
+    }
+
+    #[test]
+    fn test_validate_root() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(temp_dir.path().join("Projects/Website")).unwrap();
+        std::fs::create_dir(temp_dir.path().join("Stuff")).unwrap();
+        let root = temp_dir.path().to_str().unwrap();
+
+        let scheme = scheme_from_id(PARA, None).unwrap();
+        assert_eq!(scheme.validate_root(root).unwrap().errors.len(), 1);
+        assert!(scheme.validate_root(&format!("{}/missing", root)).is_err());
+    }
+
+    #[test]
+    fn test_jd_plan_keeps_recorded_files_and_adds_missing_items() {
+        let engine = crate::johnny_decimal::JohnnyDecimalEngine::new().unwrap();
+        let mut structure = engine.build_structure(
+            &[serde_json::json!({"path": "/root/report.pdf", "extension": "pdf"})],
+            "/root",
+        );
+        let reports = &mut structure.areas[0].categories[0];
+        reports.items.push(JDItem {
+            number: format!("{}.02", reports.number),
+            name: "Invoices".to_string(),
+            description: None,
+            files: vec!["/root/invoice.pdf".to_string()],
+        });
+        let scheme = JohnnyDecimalScheme::new(Some(structure)).unwrap();
+
+        let files = vec![
+            serde_json::json!({"path": "/root/report.pdf", "extension": "pdf"}),
+            serde_json::json!({"path": "/root/invoice.pdf", "extension": "pdf"}),
+            serde_json::json!({"path": "/root/x.qqq", "extension": "qqq"}),
+        ];
+        let plan = scheme.plan("/root", &files).unwrap();
+        let moves: Vec<(&str, &str)> = plan
+            .operations
+            .iter()
+            .filter_map(|op| match op {
+                crate::organization_plan::PlannedOperation::MoveFile {
+                    source,
+                    destination,
+                } => Some((source.as_str(), destination.as_str())),
+                _ => None,
+            })
+            .collect();
+
+        assert_eq!(moves.len(), 1);
+        assert_eq!(moves[0].0, "/root/x.qqq");
+        assert!(moves[0]
+            .1
+            .ends_with("/91 Miscellaneous/91.01 Miscellaneous/x.qqq"));
+    }
+
+    #[test]
+    fn test_file_type_folders_follow_taxonomy() {
+        let folder = FileTypeScheme.target_folder(&serde_json::json!({"extension": "csv"}));
+        assert_eq!(folder, PathBuf::from("Spreadsheets"));
+        let folder = FileTypeScheme.target_folder(&serde_json::json!({"extension": "qqq"}));
+        assert_eq!(folder, PathBuf::from(FALLBACK_CATEGORY.name));
+        assert!(FileTypeScheme
+            .validate_layout(&[PathBuf::from("Source Code"), PathBuf::from("Code")])
+            .warnings
+            .iter()
+            .all(|w| w.message.contains("'Code'")));
+    }
+
+    #[test]
+    fn test_jd_target_folder_fallback_is_localized() {
+        let engine = crate::johnny_decimal::JohnnyDecimalEngine::with_locale("de").unwrap();
+        let structure = engine.build_structure(
+            &[serde_json::json!({"path": "/root/report.pdf", "extension": "pdf"})],
+            "/root",
+        );
+        let scheme = JohnnyDecimalScheme::new(Some(structure)).unwrap();
+
+        let folder =
+            scheme.target_folder(&serde_json::json!({"path": "/root/x.qqq", "extension": "qqq"}));
+        let parts = folder_parts(&folder);
+        assert_eq!(parts.len(), 3);
+        assert!(parts[0].starts_with("90-99 "));
+        assert_eq!(parts[1], "91 Sonstiges");
+        assert_eq!(parts[2], "91.01 Sonstiges");
+    }
+}
@@ -0,0 +1,357 @@
+use crate::ai_provider::AiProvider;
+use crate::database::{DatabaseManager, StoredEmbedding};
+use crate::error::Result;
+use crate::jd_id::{JDId, JDNumber};
+use crate::johnny_decimal::{CategoryAssignment, JDStructure};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+
+pub const DEFAULT_EMBEDDING_MODEL: &str = "nomic-embed-text";
+/// Scope of file vectors in the `embeddings` table.
+pub const FILE_SCOPE: &str = "files";
+
+/// A stored vector close to the query. `key` is a JD ID for structure
+/// entries and a path for files.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SemanticMatch {
+    pub key: String,
+    pub score: f64, // Cosine similarity
+}
+
+/// Nearest-neighbour search over embeddings of structure entries and files.
+/// Each text is embedded once; vectors are reused until the text changes.
+pub struct SemanticIndex<'a> {
+    provider: &'a dyn AiProvider,
+    db: &'a DatabaseManager,
+    model: String,
+}
+
+#[allow(dead_code)]
+impl<'a> SemanticIndex<'a> {
+    pub fn new(provider: &'a dyn AiProvider, db: &'a DatabaseManager, model: &str) -> Self {
+        Self {
+            provider,
+            db,
+            model: model.to_string(),
+        }
+    }
+
+    /// Embeds every category and item of `structure` whose description
+    /// changed and drops vectors of removed entries. Returns how many were
+    /// (re)embedded.
+    pub async fn index_structure(&self, structure: &JDStructure) -> Result<usize> {
+        let stored = self.db.load_embeddings(&structure.id, &self.model).await?;
+        let entries = structure_texts(structure);
+
+        let mut embedded = 0;
+        for (key, text) in &entries {
+            let hash = text_hash(text);
+            if stored.iter().any(|e| &e.key == key && e.text_hash == hash) {
+                continue;
+            }
+            self.store(&structure.id, key, text, hash).await?;
+            embedded += 1;
+        }
+
+        let current: HashSet<&String> = entries.iter().map(|(key, _)| key).collect();
+        let removed: Vec<String> = stored
+            .into_iter()
+            .map(|e| e.key)
+            .filter(|key| !current.contains(key))
+            .collect();
+        self.db.delete_embeddings(&structure.id, &removed).await?;
+
+        Ok(embedded)
+    }
+
+    /// The vector for a file, embedding its name and content snippet unless
+    /// the stored one was made from the same text.
+    pub async fn index_file(
+        &self,
+        path: &str,
+        file_name: &str,
+        snippet: Option<&str>,
+    ) -> Result<Vec<f32>> {
+        let text = file_text(file_name, snippet);
+        let hash = text_hash(&text);
+        if let Some(existing) = self
+            .db
+            .load_embedding(FILE_SCOPE, path, &self.model)
+            .await?
+        {
+            if existing.text_hash == hash {
+                return Ok(existing.vector);
+            }
+        }
+        self.store(FILE_SCOPE, path, &text, hash).await
+    }
+
+    /// Categories and items of an indexed structure, closest first.
+    pub async fn suggest_categories(
+        &self,
+        structure: &JDStructure,
+        vector: &[f32],
+        limit: usize,
+    ) -> Result<Vec<SemanticMatch>> {
+        let stored = self.db.load_embeddings(&structure.id, &self.model).await?;
+        Ok(nearest(&stored, vector, limit))
+    }
+
+    /// Places a file at its nearest item. A category without items is
+    /// returned as its first item number.
+    pub async fn assign(
+        &self,
+        structure: &JDStructure,
+        path: &str,
+        file_name: &str,
+        snippet: Option<&str>,
+    ) -> Result<Option<CategoryAssignment>> {
+        let vector = self.index_file(path, file_name, snippet).await?;
+        let best = self.suggest_categories(structure, &vector, 1).await?;
+
+        Ok(best.into_iter().find_map(|m| {
+            let id: JDId = m.key.parse().ok()?;
+            let category_number = id.category_number()?;
+            let item_number = match id.number {
+                JDNumber::Item { .. } => id.local_id(),
+                _ => JDId::item(category_number, 1).ok()?.local_id(),
+            };
+            Some(CategoryAssignment {
+                area_number: id.area_number(),
+                category_number,
+                item_number,
+                confidence: m.score.clamp(0.0, 1.0),
+                reasoning: format!("Closest in meaning to {} ({:.2})", m.key, m.score),
+            })
+        }))
+    }
+
+    /// Indexed files closest to the file at `path`, for "find files like this".
+    pub async fn similar_files(&self, path: &str, limit: usize) -> Result<Vec<SemanticMatch>> {
+        let vector = match self
+            .db
+            .load_embedding(FILE_SCOPE, path, &self.model)
+            .await?
+        {
+            Some(e) => e.vector,
+            None => return Ok(vec![]),
+        };
+        let others: Vec<StoredEmbedding> = self
+            .db
+            .load_embeddings(FILE_SCOPE, &self.model)
+            .await?
+            .into_iter()
+            .filter(|e| e.key != path)
+            .collect();
+        Ok(nearest(&others, &vector, limit))
+    }
+
+    async fn store(&self, scope: &str, key: &str, text: &str, hash: String) -> Result<Vec<f32>> {
+        let vector = self.provider.embed(&self.model, text).await?;
+        self.db
+            .save_embedding(
+                scope,
+                &StoredEmbedding {
+                    key: key.to_string(),
+                    model: self.model.clone(),
+                    text_hash: hash,
+                    vector: vector.clone(),
+                },
+            )
+            .await?;
+        Ok(vector)
+    }
+}
+
+/// The text embedded for each category and item, keyed by JD ID. Items
+/// carry their category and area so short names keep their context.
+pub fn structure_texts(structure: &JDStructure) -> Vec<(String, String)> {
+    let mut texts = Vec::new();
+    for area in &structure.areas {
+        for category in &area.categories {
+            let category_text = with_description(
+                &format!("{} > {}", area.label(), category.name),
+                &category.description,
+            );
+            for item in &category.items {
+                texts.push((
+                    item.number.clone(),
+                    with_description(
+                        &format!("{} > {}", category_text, item.name),
+                        &item.description,
+                    ),
+                ));
+            }
+            texts.push((format!("{:02}", category.number), category_text));
+        }
+    }
+    texts
+}
+
+pub fn file_text(file_name: &str, snippet: Option<&str>) -> String {
+    match snippet {
+        Some(snippet) => format!("{}\n{}", file_name, snippet),
+        None => file_name.to_string(),
+    }
+}
+
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f64 {
+    if a.len() != b.len() || a.is_empty() {
+        return 0.0;
+    }
+    let (mut dot, mut norm_a, mut norm_b) = (0.0, 0.0, 0.0);
+    for (x, y) in a.iter().zip(b) {
+        let (x, y) = (*x as f64, *y as f64);
+        dot += x * y;
+        norm_a += x * x;
+        norm_b += y * y;
+    }
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    dot / (norm_a.sqrt() * norm_b.sqrt())
+}
+
+fn nearest(stored: &[StoredEmbedding], vector: &[f32], limit: usize) -> Vec<SemanticMatch> {
+    let mut matches: Vec<SemanticMatch> = stored
+        .iter()
+        .map(|e| SemanticMatch {
+            key: e.key.clone(),
+            score: cosine_similarity(&e.vector, vector),
+        })
+        .collect();
+    matches.sort_by(|a, b| {
+        b.score
+            .partial_cmp(&a.score)
+            .unwrap_or(std::cmp::Ordering::Equal)
+    });
+    matches.truncate(limit);
+    matches
+}
+
+fn with_description(text: &str, description: &Option<String>) -> String {
+    match description {
+        Some(description) if !description.is_empty() => format!("{}: {}", text, description),
+        _ => text.to_string(),
+    }
+}
+
+fn text_hash(text: &str) -> String {
+    format!("{:x}", Sha256::digest(text.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ai_provider::{AiBackendSettings, ProviderConfig, ProviderHealth};
+    use crate::error::AppError;
+    use crate::johnny_decimal::JohnnyDecimalEngine;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tempfile::tempdir;
+
+    /// Embeds text as counts of hashed letter trigrams.
+    struct TrigramEmbedder {
+        config: ProviderConfig,
+        calls: AtomicUsize,
+    }
+
+    #[async_trait::async_trait]
+    impl AiProvider for TrigramEmbedder {
+        fn id(&self) -> &'static str {
+            "trigram"
+        }
+
+        fn config(&self) -> &ProviderConfig {
+            &self.config
+        }
+
+        async fn health(&self) -> ProviderHealth {
+            ProviderHealth {
+                provider: self.id().to_string(),
+                available: true,
+                detail: None,
+            }
+        }
+
+        async fn list_models(&self) -> Result<Vec<String>> {
+            Ok(vec![])
+        }
+
+        async fn generate(
+            &self,
+            _model: &str,
+            _prompt: &str,
+            _schema: Option<&serde_json::Value>,
+        ) -> Result<String> {
+            Err(AppError::AiService(
+                "The trigram embedder does not generate text".to_string(),
+            ))
+        }
+
+        async fn embed(&self, _model: &str, input: &str) -> Result<Vec<f32>> {
+            self.calls.fetch_add(1, Ordering::SeqCst);
+            let mut vector = vec![0.0; 256];
+            let chars: Vec<char> = input.to_lowercase().chars().collect();
+            for window in chars.windows(3) {
+                let hash = window
+                    .iter()
+                    .fold(7u32, |h, c| h.wrapping_mul(31) ^ *c as u32);
+                vector[(hash % 256) as usize] += 1.0;
+            }
+            Ok(vector)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_semantic_assignment_and_similar_files() {
+        let temp_dir = tempdir().unwrap();
+        let db = DatabaseManager::new(temp_dir.path().join("test.db").to_str().unwrap()).unwrap();
+        let provider = TrigramEmbedder {
+            config: AiBackendSettings::default().ollama,
+            calls: AtomicUsize::new(0),
+        };
+        let index = SemanticIndex::new(&provider, &db, DEFAULT_EMBEDDING_MODEL);
+
+        let engine = JohnnyDecimalEngine::new().unwrap();
+        let files = vec![
+            serde_json::json!({"path": "/home/report.pdf", "extension": "pdf"}),
+            serde_json::json!({"path": "/home/photo.jpg", "extension": "jpg"}),
+        ];
+        let mut structure = engine.build_structure(&files, "/home");
+        let entries = structure_texts(&structure).len();
+        assert_eq!(index.index_structure(&structure).await.unwrap(), entries);
+        assert_eq!(index.index_structure(&structure).await.unwrap(), 0);
+
+        let documents = &structure.areas[0].categories[0];
+        let assignment = index
+            .assign(
+                &structure,
+                "/home/scan_0042.pdf",
+                "scan_0042.pdf",
+                Some(&format!("Quarterly {}", documents.name.to_lowercase())),
+            )
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(assignment.category_number, documents.number);
+
+        index
+            .index_file("/home/a.txt", "a.txt", Some("holiday photos from lisbon"))
+            .await
+            .unwrap();
+        index
+            .index_file("/home/b.txt", "b.txt", Some("lisbon holiday photo list"))
+            .await
+            .unwrap();
+        let similar = index.similar_files("/home/a.txt", 5).await.unwrap();
+        assert_eq!(similar[0].key, "/home/b.txt");
+        assert_eq!(similar.len(), 2);
+
+        // Renaming an item re-embeds just that item
+        let calls = provider.calls.load(Ordering::SeqCst);
+        structure.areas[0].categories[0].items[0].name = "Tax returns".to_string();
+        assert_eq!(index.index_structure(&structure).await.unwrap(), 1);
+        assert_eq!(provider.calls.load(Ordering::SeqCst), calls + 1);
+    }
+}
@@ -0,0 +1,181 @@
+use crate::jd_id::FolderLevel;
+use crate::johnny_decimal::JDStructure;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// An area, category or item, identified by its JD ID (`20-29`, `21`, `21.01`).
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DiffNode {
+    pub level: FolderLevel,
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RenamedNode {
+    pub level: FolderLevel,
+    pub id: String,
+    pub from: String,
+    pub to: String,
+}
+
+/// A file recorded under a different item ID in the newer revision.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileMove {
+    pub file: String,
+    pub from_id: String,
+    pub to_id: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StructureDiff {
+    pub added: Vec<DiffNode>,
+    pub removed: Vec<DiffNode>,
+    pub renamed: Vec<RenamedNode>,
+    pub moved_files: Vec<FileMove>,
+    pub added_files: Vec<String>,
+    pub removed_files: Vec<String>,
+}
+
+#[allow(dead_code)]
+impl StructureDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.renamed.is_empty()
+            && self.moved_files.is_empty()
+            && self.added_files.is_empty()
+            && self.removed_files.is_empty()
+    }
+}
+
+/// Compares two revisions of a structure. Nodes are matched by ID, so a
+/// renumbered node shows up as removed and added.
+pub fn diff_structures(before: &JDStructure, after: &JDStructure) -> StructureDiff {
+    let mut diff = StructureDiff::default();
+    let old_nodes = nodes(before);
+    let new_nodes = nodes(after);
+
+    for ((level, id), name) in &new_nodes {
+        match old_nodes.get(&(*level, id.clone())) {
+            None => diff.added.push(DiffNode {
+                level: *level,
+                id: id.clone(),
+                name: name.clone(),
+            }),
+            Some(old_name) if old_name != name => diff.renamed.push(RenamedNode {
+                level: *level,
+                id: id.clone(),
+                from: old_name.clone(),
+                to: name.clone(),
+            }),
+            Some(_) => {}
+        }
+    }
+    for ((level, id), name) in &old_nodes {
+        if !new_nodes.contains_key(&(*level, id.clone())) {
+            diff.removed.push(DiffNode {
+                level: *level,
+                id: id.clone(),
+                name: name.clone(),
+            });
+        }
+    }
+
+    let old_files = file_ids(before);
+    let new_files = file_ids(after);
+    for (file, to_id) in &new_files {
+        match old_files.get(file) {
+            None => diff.added_files.push(file.clone()),
+            Some(from_id) if from_id != to_id => diff.moved_files.push(FileMove {
+                file: file.clone(),
+                from_id: from_id.clone(),
+                to_id: to_id.clone(),
+            }),
+            Some(_) => {}
+        }
+    }
+    diff.removed_files = old_files
+        .into_keys()
+        .filter(|file| !new_files.contains_key(file))
+        .collect();
+
+    diff
+}
+
+fn nodes(structure: &JDStructure) -> BTreeMap<(FolderLevel, String), String> {
+    let mut nodes = BTreeMap::new();
+
+    for area in &structure.areas {
+        nodes.insert(
+            (
+                FolderLevel::Area,
+                format!("{}-{}", area.number, area.number + 9),
+            ),
+            area.label().to_string(),
+        );
+        for category in &area.categories {
+            nodes.insert(
+                (FolderLevel::Category, format!("{:02}", category.number)),
+                category.name.clone(),
+            );
+            for item in &category.items {
+                nodes.insert((FolderLevel::Item, item.number.clone()), item.name.clone());
+            }
+        }
+    }
+
+    nodes
+}
+
+fn file_ids(structure: &JDStructure) -> BTreeMap<String, String> {
+    structure
+        .areas
+        .iter()
+        .flat_map(|a| &a.categories)
+        .flat_map(|c| &c.items)
+        .flat_map(|item| item.files.iter().map(|f| (f.clone(), item.number.clone())))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::templates::builtin_template;
+
+    #[test]
+    fn test_diff_structures() {
+        let mut before = builtin_template("downloads").unwrap().instantiate("/dl");
+        before.areas[1].categories[0].items[0].files = vec!["/dl/visa.pdf".to_string()];
+        before.areas[1].categories[0].items[1].files = vec!["/dl/old.pdf".to_string()];
+        let mut after = before.clone();
+
+        assert!(diff_structures(&before, &after).is_empty());
+
+        after.areas[1].categories[0].name = "Immigration".to_string();
+        after.areas[0].categories.pop(); // 14 Photography Tools and 14.01
+        let visa = after.areas[1].categories[0].items[0].files.remove(0);
+        after.areas[1].categories[0].items[2].files.push(visa);
+        after.areas[1].categories[0].items[1].files.clear();
+
+        let diff = diff_structures(&before, &after);
+        assert!(diff.added.is_empty());
+        assert_eq!(
+            diff.removed
+                .iter()
+                .map(|n| n.id.as_str())
+                .collect::<Vec<_>>(),
+            vec!["14", "14.01"]
+        );
+        assert_eq!(diff.renamed[0].to, "Immigration");
+        assert_eq!(
+            diff.moved_files,
+            vec![FileMove {
+                file: "/dl/visa.pdf".to_string(),
+                from_id: "21.01".to_string(),
+                to_id: "21.03".to_string(),
+            }]
+        );
+        assert_eq!(diff.removed_files, vec!["/dl/old.pdf"]);
+    }
+}
@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+
+/// A default category and the file extensions that belong in it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TaxonomyCategory {
+    pub area: u8,
+    pub name: &'static str,
+    pub extensions: &'static [&'static str],
+}
+
+/// Where files go when no category claims their extension.
+pub const FALLBACK_CATEGORY: TaxonomyCategory = TaxonomyCategory {
+    area: 90,
+    name: "Miscellaneous",
+    extensions: &[],
+};
+
+/// The one extension-to-category table. Both `JohnnyDecimalEngine` and the
+/// rule-based AI fallback read from it, so they always agree. Area numbers
+/// follow the `general` template.
+pub const TAXONOMY: &[TaxonomyCategory] = &[
+    // Documents area (20-29)
+    TaxonomyCategory {
+        area: 20,
+        name: "Reports and Documents",
+        extensions: &["pdf"],
+    },
+    TaxonomyCategory {
+        area: 20,
+        name: "Text Documents",
+        extensions: &["doc", "docx", "txt", "rtf"],
+    },
+    TaxonomyCategory {
+        area: 20,
+        name: "Spreadsheets",
+        extensions: &["xls", "xlsx", "csv"],
+    },
+    TaxonomyCategory {
+        area: 20,
+        name: "Presentations",
+        extensions: &["ppt", "pptx"],
+    },
+    // Media area (30-39)
+    TaxonomyCategory {
+        area: 30,
+        name: "Images",
+        extensions: &["jpg", "jpeg", "png", "gif", "bmp", "svg"],
+    },
+    TaxonomyCategory {
+        area: 30,
+        name: "Videos",
+        extensions: &["mp4", "avi", "mkv", "mov", "wmv"],
+    },
+    TaxonomyCategory {
+        area: 30,
+        name: "Audio",
+        extensions: &["mp3", "wav", "flac", "aac"],
+    },
+    // Development area (40-49)
+    TaxonomyCategory {
+        area: 40,
+        name: "Source Code",
+        extensions: &["js", "ts", "py", "rs", "java", "cpp", "c"],
+    },
+    TaxonomyCategory {
+        area: 40,
+        name: "Web Files",
+        extensions: &["html", "css"],
+    },
+    TaxonomyCategory {
+        area: 40,
+        name: "Configuration",
+        extensions: &["json", "xml", "yaml", "yml"],
+    },
+    // Archives area (50-59)
+    TaxonomyCategory {
+        area: 50,
+        name: "Compressed Files",
+        extensions: &["zip", "rar", "7z", "tar", "gz"],
+    },
+    TaxonomyCategory {
+        area: 50,
+        name: "Installers",
+        extensions: &["exe", "msi", "dmg", "pkg"],
+    },
+];
+
+/// The category for an extension, without the leading dot.
+pub fn category_for_extension(extension: &str) -> Option<&'static TaxonomyCategory> {
+    let extension = extension.trim_start_matches('.').to_lowercase();
+    TAXONOMY
+        .iter()
+        .find(|c| c.extensions.contains(&extension.as_str()))
+}
+
+/// Like `category_for_extension`, but falls back to `FALLBACK_CATEGORY`.
+pub fn classify_extension(extension: &str) -> &'static TaxonomyCategory {
+    category_for_extension(extension).unwrap_or(&FALLBACK_CATEGORY)
+}
+
+/// extension -> (area, category name), the shape the engine indexes by.
+pub fn extension_map() -> HashMap<String, (u8, String)> {
+    TAXONOMY
+        .iter()
+        .flat_map(|category| {
+            category
+                .extensions
+                .iter()
+                .map(move |ext| (ext.to_string(), (category.area, category.name.to_string())))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::johnny_decimal::is_valid_area_number;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_taxonomy_is_consistent() {
+        let mut seen = HashSet::new();
+        for category in TAXONOMY {
+            assert!(is_valid_area_number(category.area));
+            for ext in category.extensions {
+                assert!(seen.insert(*ext), "{} is mapped twice", ext);
+            }
+        }
+        assert_eq!(extension_map().len(), seen.len());
+    }
+
+    #[test]
+    fn test_classify_extension() {
+        assert_eq!(classify_extension(".ZIP").name, "Compressed Files");
+        assert_eq!(classify_extension("zip").area, 50);
+        assert_eq!(classify_extension("xyz"), &FALLBACK_CATEGORY);
+    }
+}
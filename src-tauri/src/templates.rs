@@ -0,0 +1,305 @@
+use crate::error::{AppError, Result};
+use crate::johnny_decimal::{JDArea, JDCategory, JDItem, JDStructure};
+use crate::labels::{localized, DEFAULT_LOCALE};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+/// Template used for area names when a structure is generated from scratch.
+pub const DEFAULT_TEMPLATE_ID: &str = "general";
+
+/// Templates shipped with the app, embedded from `src-tauri/templates`.
+const BUILTIN_TEMPLATES: &[&str] = &[
+    include_str!("../templates/general.json"),
+    include_str!("../templates/personal_home.json"),
+    include_str!("../templates/downloads.json"),
+    include_str!("../templates/small_business.json"),
+    include_str!("../templates/software_developer.json"),
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StructureTemplate {
+    pub id: String,
+    pub name: String,
+    pub version: u32,
+    pub description: String,
+    #[serde(default)]
+    pub builtin: bool,
+    pub areas: Vec<TemplateArea>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateArea {
+    pub number: u8,
+    pub name: String, // Without the "10-19" range prefix
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub labels: HashMap<String, LocalizedLabel>, // locale -> translation
+    #[serde(default)]
+    pub categories: Vec<TemplateCategory>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateCategory {
+    pub number: u8,
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub labels: HashMap<String, LocalizedLabel>,
+    #[serde(default)]
+    pub items: Vec<TemplateItem>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateItem {
+    pub number: String,
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub labels: HashMap<String, LocalizedLabel>,
+}
+
+/// A name and description in one language.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LocalizedLabel {
+    pub name: String,
+    #[serde(default)]
+    pub description: Option<String>,
+}
+
+pub fn builtin_templates() -> Result<Vec<StructureTemplate>> {
+    BUILTIN_TEMPLATES
+        .iter()
+        .map(|data| {
+            let mut template: StructureTemplate = serde_json::from_str(data)?;
+            template.builtin = true;
+            Ok(template)
+        })
+        .collect()
+}
+
+pub fn builtin_template(id: &str) -> Result<StructureTemplate> {
+    builtin_templates()?
+        .into_iter()
+        .find(|t| t.id == id)
+        .ok_or_else(|| AppError::InvalidInput(format!("Unknown template: {}", id)))
+}
+
+#[allow(dead_code)]
+impl StructureTemplate {
+    /// Creates a fresh, empty structure for `root_path` from this template.
+    pub fn instantiate(&self, root_path: &str) -> JDStructure {
+        self.instantiate_in(root_path, DEFAULT_LOCALE)
+    }
+
+    /// Like `instantiate`, with names and descriptions in `locale` where the
+    /// template has them.
+    pub fn instantiate_in(&self, root_path: &str, locale: &str) -> JDStructure {
+        let now = chrono::Utc::now();
+
+        JDStructure {
+            id: Uuid::new_v4().to_string(),
+            name: self.name.clone(),
+            root_path: root_path.to_string(),
+            system: None,
+            locale: Some(locale.to_string()),
+            areas: self
+                .areas
+                .iter()
+                .map(|area| {
+                    let (name, description) =
+                        pick_label(&area.labels, locale, &area.name, &area.description);
+                    JDArea {
+                        number: area.number,
+                        name: format!("{}-{} {}", area.number, area.number + 9, name),
+                        description,
+                        categories: area
+                            .categories
+                            .iter()
+                            .map(|category| {
+                                let (name, description) = pick_label(
+                                    &category.labels,
+                                    locale,
+                                    &category.name,
+                                    &category.description,
+                                );
+                                JDCategory {
+                                    number: category.number,
+                                    name,
+                                    description,
+                                    items: category
+                                        .items
+                                        .iter()
+                                        .map(|item| {
+                                            let (name, description) = pick_label(
+                                                &item.labels,
+                                                locale,
+                                                &item.name,
+                                                &item.description,
+                                            );
+                                            JDItem {
+                                                number: item.number.clone(),
+                                                name,
+                                                description,
+                                                files: Vec::new(),
+                                            }
+                                        })
+                                        .collect(),
+                                }
+                            })
+                            .collect(),
+                    }
+                })
+                .collect(),
+            created_at: now,
+            modified_at: now,
+        }
+    }
+
+    /// Captures the areas, categories and items of a structure, without its
+    /// files, as a user template.
+    pub fn from_structure(
+        id: &str,
+        name: &str,
+        description: &str,
+        structure: &JDStructure,
+    ) -> Self {
+        Self {
+            id: id.to_string(),
+            name: name.to_string(),
+            version: 1,
+            description: description.to_string(),
+            builtin: false,
+            areas: structure
+                .areas
+                .iter()
+                .map(|area| TemplateArea {
+                    number: area.number,
+                    name: area.label().to_string(),
+                    description: area.description.clone(),
+                    labels: HashMap::new(),
+                    categories: area
+                        .categories
+                        .iter()
+                        .map(|category| TemplateCategory {
+                            number: category.number,
+                            name: category.name.clone(),
+                            description: category.description.clone(),
+                            labels: HashMap::new(),
+                            items: category
+                                .items
+                                .iter()
+                                .map(|item| TemplateItem {
+                                    number: item.number.clone(),
+                                    name: item.name.clone(),
+                                    description: item.description.clone(),
+                                    labels: HashMap::new(),
+                                })
+                                .collect(),
+                        })
+                        .collect(),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn area(&self, number: u8) -> Option<&TemplateArea> {
+        self.areas.iter().find(|a| a.number == number)
+    }
+}
+
+#[allow(dead_code)]
+impl TemplateArea {
+    pub fn localized_name(&self, locale: &str) -> &str {
+        localized(&self.labels, locale)
+            .map(|label| label.name.as_str())
+            .unwrap_or(&self.name)
+    }
+
+    pub fn localized_description(&self, locale: &str) -> Option<&str> {
+        localized(&self.labels, locale)
+            .and_then(|label| label.description.as_deref())
+            .or(self.description.as_deref())
+    }
+}
+
+/// The translation for `locale`, or the template's own name and description.
+fn pick_label(
+    labels: &HashMap<String, LocalizedLabel>,
+    locale: &str,
+    name: &str,
+    description: &Option<String>,
+) -> (String, Option<String>) {
+    match localized(labels, locale) {
+        Some(label) => (
+            label.name.clone(),
+            label.description.clone().or_else(|| description.clone()),
+        ),
+        None => (name.to_string(), description.clone()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::johnny_decimal::JohnnyDecimalEngine;
+
+    #[test]
+    fn test_builtin_templates_load() {
+        let templates = builtin_templates().unwrap();
+        let ids: Vec<_> = templates.iter().map(|t| t.id.as_str()).collect();
+
+        assert_eq!(
+            ids,
+            vec![
+                "general",
+                "personal_home",
+                "downloads",
+                "small_business",
+                "software_developer"
+            ]
+        );
+        assert!(templates.iter().all(|t| t.builtin && t.version >= 1));
+    }
+
+    #[tokio::test]
+    async fn test_builtin_templates_are_valid() {
+        let engine = JohnnyDecimalEngine::new().unwrap();
+
+        for template in builtin_templates().unwrap() {
+            let structure = template.instantiate("/test");
+            let result = engine.validate_structure(&structure).await.unwrap();
+            assert!(result.is_valid, "template {} is invalid", template.id);
+        }
+    }
+
+    #[test]
+    fn test_template_round_trip() {
+        let downloads = builtin_template("downloads").unwrap();
+        let structure = downloads.instantiate("/home/me/Downloads");
+        assert_eq!(structure.areas[0].name, "10-19 Photography & Creative");
+
+        let saved = StructureTemplate::from_structure("mine", "Mine", "", &structure);
+        assert_eq!(saved.areas[0].name, "Photography & Creative");
+        assert_eq!(
+            saved.areas[0].categories[0].items.len(),
+            downloads.areas[0].categories[0].items.len()
+        );
+    }
+
+    #[test]
+    fn test_localized_instantiate() {
+        let general = builtin_template("general").unwrap();
+
+        let german = general.instantiate_in("/home", "de-DE");
+        assert_eq!(german.locale.as_deref(), Some("de-DE"));
+        assert_eq!(german.areas[1].name, "20-29 Dokumente");
+        assert_eq!(german.areas[1].folder_name(), "20-29 Dokumente");
+
+        let japanese = general.instantiate_in("/home", "ja");
+        assert_eq!(japanese.areas[1].name, "20-29 Documents");
+        assert_eq!(general.areas[8].localized_name("es"), "Varios");
+    }
+}